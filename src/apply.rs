@@ -1,59 +1,274 @@
 use anyhow::Result;
 use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
 
 use crate::dock;
+use crate::history::{self, Trigger};
+use crate::hooks::{HookContext, HookEvent};
+use crate::compositor::{self, Compositor};
 use crate::hyprland;
 use crate::metadata::Metadata;
-use crate::profile::Profile;
+use crate::plan::Plan;
+use crate::profile::{best_monitor_match, MonitorMatch, Profile};
+use crate::revert::{PendingRevert, Snapshot};
 
-/// Apply a profile by name
-pub fn apply_profile(name: &str, no_runtime: bool) -> Result<()> {
-    apply_profile_inner(name, no_runtime, false)
+/// Result of an apply, printed by `apply --json` and sent back by the daemon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub profile: String,
+    pub previous_profile: Option<String>,
+    pub config_path: String,
+    /// Whether the layout was also applied to the running session
+    pub runtime: bool,
+    /// Problems that didn't stop the apply (unresolved monitor names,
+    /// failed workspace moves, failed non-blocking hooks, ...)
+    pub warnings: Vec<String>,
 }
 
-/// Apply a profile without printing (for TUI use)
-pub fn apply_profile_quiet(name: &str, no_runtime: bool) -> Result<()> {
-    apply_profile_inner(name, no_runtime, true)
+/// What a quiet apply hands back to the TUI
+pub struct Applied {
+    pub report: ApplyReport,
+    /// Set when the profile asks for the new layout to be confirmed
+    pub pending: Option<PendingRevert>,
 }
 
-fn apply_profile_inner(name: &str, no_runtime: bool, quiet: bool) -> Result<()> {
+/// Apply a profile by name. `confirm` overrides the profile's
+/// `revert_timeout`; when either is set, waits for the user to keep the new
+/// layout and reverts it otherwise. With `json`, nothing but the caller's
+/// report goes to stdout. `in_daemon` asks by notification only and waits
+/// in the background, so the report comes back before the layout is kept.
+pub fn apply_profile(
+    name: &str,
+    no_runtime: bool,
+    confirm: Option<u64>,
+    json: bool,
+    trigger: Trigger,
+    in_daemon: bool,
+) -> Result<ApplyReport> {
+    let applied = apply_profile_inner(
+        &*compositor::detect(),
+        name,
+        no_runtime,
+        confirm,
+        json,
+        trigger,
+    )?;
+    if !json {
+        for warning in &applied.report.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    let mut report = applied.report;
+    if let Some(pending) = applied.pending {
+        if in_daemon {
+            pending.wait_in_background();
+            return Ok(report);
+        }
+        let warnings = pending.wait()?;
+        if !json {
+            for warning in &warnings {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        report.warnings.extend(warnings);
+    }
+    Ok(report)
+}
+
+/// Whether applying this profile will ask the user to keep the new layout,
+/// which needs the caller's own terminal
+pub fn wants_confirmation(name: &str, confirm: Option<u64>) -> bool {
+    let profile_timeout = Profile::load(name).ok().and_then(|p| p.revert_timeout);
+    confirm.or(profile_timeout).is_some_and(|secs| secs > 0)
+}
+
+/// Apply a profile without printing (for TUI use). A pending revert is
+/// handed back for the caller to confirm or revert.
+pub fn apply_profile_quiet(name: &str, no_runtime: bool) -> Result<Applied> {
+    apply_profile_inner(&*compositor::detect(), name, no_runtime, None, true, Trigger::Tui)
+}
+
+/// Apply and record the outcome in the history
+fn apply_profile_inner(
+    compositor: &dyn Compositor,
+    name: &str,
+    no_runtime: bool,
+    confirm: Option<u64>,
+    quiet: bool,
+    trigger: Trigger,
+) -> Result<Applied> {
+    let mut result = apply_steps(compositor, name, no_runtime, confirm, quiet);
+    let docks = dock::detect_docks()
+        .map(|docks| docks.into_iter().map(|d| d.uuid).collect())
+        .unwrap_or_default();
+    let outcome = match &result {
+        Ok(applied) => Ok(applied.report.warnings.clone()),
+        Err(e) => Err(format!("{:#}", e)),
+    };
+    if let Err(e) = history::record(history::Entry::new(name, trigger, docks, outcome)) {
+        if let Ok(applied) = &mut result {
+            applied
+                .report
+                .warnings
+                .push(format!("Could not record apply history: {:#}", e));
+        }
+    }
+    result
+}
+
+fn apply_steps(
+    compositor: &dyn Compositor,
+    name: &str,
+    no_runtime: bool,
+    confirm: Option<u64>,
+    quiet: bool,
+) -> Result<Applied> {
     // The TUI validates on save, but the CLI accepts arbitrary names;
     // reject path-traversal names like `../../x` before touching the fs.
     crate::profile::validate_profile_name(name)?;
     let mut profile = Profile::load(name)?;
 
+    let mut warnings = Vec::new();
+
     // Resolve stored monitor descriptions to current port names
     // This handles dock reconnections that assign different port names
-    if let Err(e) = hyprland::resolve_monitor_names(&mut profile) {
-        if !quiet {
-            eprintln!("Warning: Could not resolve monitor names: {}", e);
-        }
+    if let Err(e) = hyprland::resolve_monitor_names(compositor, &mut profile) {
+        warnings.push(format!("Could not resolve monitor names: {}", e));
         // Continue anyway - will use stored names as fallback
     }
 
     // Fix stacking gaps (snap stacked monitors to adjacent edges)
     hyprland::fix_stacking_gaps(&mut profile.monitors);
 
+    let previous = Metadata::load()?.active_profile;
+    let hooks = HookContext::new(&profile, previous.clone(), quiet);
+
+    // Run on_fail hooks for an error, then hand the error back
+    let fail = |e: anyhow::Error, warnings: &mut Vec<String>| {
+        if let Ok(w) = hooks.run(HookEvent::OnFail, Some(&e)) {
+            warnings.extend(w);
+        }
+        if !quiet {
+            for warning in warnings.iter() {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        e
+    };
+
+    match hooks.run(HookEvent::PreApply, None) {
+        Ok(w) => warnings.extend(w),
+        Err(e) => {
+            let e = e.context("Blocking pre_apply hook failed; profile not applied");
+            return Err(fail(e, &mut warnings));
+        }
+    }
+
+    let runtime = !no_runtime && compositor.is_running();
+
+    // Snapshot before touching anything if this apply has to be confirmed;
+    // without a runtime apply there's nothing on screen to confirm
+    let timeout = confirm.or(profile.revert_timeout).filter(|&secs| secs > 0);
+    let snapshot = match timeout {
+        Some(_) if runtime => match Snapshot::capture(compositor) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                let e = e.context("Failed to snapshot the current layout for revert");
+                return Err(fail(e, &mut warnings));
+            }
+        },
+        _ => None,
+    };
+
     // Write config file
-    hyprland::write_config(&profile)?;
+    let config_path = match hyprland::write_config(&profile, compositor.dialect()) {
+        Ok(path) => path,
+        Err(e) => return Err(fail(e, &mut warnings)),
+    };
 
     // Apply at runtime if Hyprland is running and not disabled
-    if !no_runtime && hyprland::is_running() {
-        hyprland::apply_runtime(&profile)?;
+    if runtime {
+        match hyprland::apply_runtime(compositor, &profile) {
+            Ok(w) => warnings.extend(w),
+            Err(e) => {
+                // A half-applied layout is exactly what the snapshot is for
+                if let Some(snapshot) = &snapshot {
+                    if let Err(re) = snapshot.restore(compositor) {
+                        warnings.push(format!("Failed to restore previous layout: {:#}", re));
+                    }
+                }
+                return Err(fail(e, &mut warnings));
+            }
+        }
     }
 
     // Update metadata
     let mut metadata = Metadata::load()?;
     metadata.set_active(Some(name.to_string()));
+    if runtime {
+        if let Ok(connected) = hyprland::connected_monitor_descriptions(compositor) {
+            metadata.record_seen_monitors(&connected);
+        }
+    }
+    if metadata.reverted_profile.as_deref() == Some(name) {
+        metadata.reverted_profile = None;
+    }
     metadata.save()?;
 
+    // A layout that still has to be kept runs post_apply once it is (see
+    // PendingRevert), so a revert doesn't leave its side effects behind
+    if snapshot.is_none() {
+        match hooks.run(HookEvent::PostApply, None) {
+            Ok(w) => warnings.extend(w),
+            Err(e) => {
+                let e = e.context("Blocking post_apply hook failed");
+                return Err(fail(e, &mut warnings));
+            }
+        }
+    }
+
     if !quiet {
         println!("Applied profile: {}", name);
     }
+    Ok(Applied {
+        report: ApplyReport {
+            profile: name.to_string(),
+            previous_profile: previous,
+            config_path: config_path.display().to_string(),
+            runtime,
+            warnings,
+        },
+        pending: snapshot
+            .zip(timeout)
+            .map(|(s, secs)| PendingRevert::new(name, s, secs, hooks)),
+    })
+}
+
+/// Show what applying a profile would change, without writing the config,
+/// touching Hyprland, or updating metadata
+pub fn dry_run(name: &str, json: bool) -> Result<()> {
+    crate::profile::validate_profile_name(name)?;
+    let mut profile = Profile::load(name)?;
+
+    // Resolve and fix gaps exactly as a real apply would
+    let compositor = compositor::detect();
+    let resolve_err = hyprland::resolve_monitor_names(&*compositor, &mut profile).err();
+    hyprland::fix_stacking_gaps(&mut profile.monitors);
+
+    let mut plan = Plan::build(&*compositor, &profile)?;
+    if let Some(e) = resolve_err {
+        plan.warnings
+            .push(format!("Could not resolve monitor names: {}", e));
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+    } else {
+        plan.print();
+    }
     Ok(())
 }
 
-/// Check whether the written monitors.lua already matches what applying
+/// Check whether the written config already matches what applying
 /// this profile would generate (after port-name resolution and gap fixes).
 ///
 /// Metadata saying a profile is "active" isn't enough to skip a re-apply:
@@ -61,80 +276,205 @@ fn apply_profile_inner(name: &str, no_runtime: bool, quiet: bool) -> Result<()>
 /// stays the same (e.g. when no undocked fallback is configured). Any
 /// rename shows up in the generated config, so comparing against the file
 /// on disk catches exactly that case.
-fn profile_up_to_date(name: &str) -> bool {
+fn profile_up_to_date(compositor: &dyn Compositor, name: &str) -> bool {
     let Ok(mut profile) = Profile::load(name) else {
         return false;
     };
-    if hyprland::resolve_monitor_names(&mut profile).is_err() {
+    if hyprland::resolve_monitor_names(compositor, &mut profile).is_err() {
         return false;
     }
     hyprland::fix_stacking_gaps(&mut profile.monitors);
-    let expected = hyprland::generate_config(&profile);
+    let dialect = compositor.dialect();
+    let expected = dialect.generate_config(&profile);
 
-    let Ok(path) = crate::config::hyprland_monitors_lua() else {
+    let Ok(path) = dialect.config_path() else {
         return false;
     };
     std::fs::read_to_string(path).map(|cur| cur == expected).unwrap_or(false)
 }
 
+/// Why auto-apply leaves a profile alone, if it does: it's already active
+/// with a current config, or its last apply was reverted (retrying would
+/// just black out the screens again until the timeout).
+fn skip_auto_apply(
+    compositor: &dyn Compositor,
+    metadata: &Metadata,
+    name: &str,
+) -> Option<AutoOutcome> {
+    if metadata.reverted_profile.as_deref() == Some(name) {
+        return Some(AutoOutcome::Reverted {
+            profile: name.to_string(),
+        });
+    }
+    if metadata.active_profile.as_deref() == Some(name) && profile_up_to_date(compositor, name) {
+        return Some(AutoOutcome::UpToDate {
+            profile: name.to_string(),
+        });
+    }
+    None
+}
+
+/// Find the saved profile whose monitors best match the connected displays.
+/// Returns None when Hyprland can't be queried or nothing matches.
+fn match_profile_by_monitors(compositor: &dyn Compositor) -> Option<(String, MonitorMatch)> {
+    let connected = hyprland::connected_monitor_descriptions(compositor).ok()?;
+    let profiles: Vec<Profile> = crate::profile::list_profiles()
+        .ok()?
+        .iter()
+        .filter_map(|name| Profile::load(name).ok())
+        .collect();
+    best_monitor_match(&profiles, &connected).map(|(p, m)| (p.name.clone(), m))
+}
+
+/// What `apply --auto` decided, printed by `apply --auto --json` and sent
+/// back by the daemon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AutoOutcome {
+    /// A profile was applied; `via` is "dock", "monitors" or "undocked",
+    /// and `reason` says which dock rule or monitor match picked it
+    Applied {
+        via: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        report: ApplyReport,
+    },
+    /// The chosen profile is already active and its config is current
+    UpToDate { profile: String },
+    /// The chosen profile's last apply was reverted; left alone
+    Reverted { profile: String },
+    /// Nothing to apply: no linked dock, monitor match or undocked profile
+    NoProfile { dock_uuids: Vec<String> },
+}
+
 /// Auto-detect dock and apply appropriate profile
 ///
-/// Note: Only supports one dock at a time. If multiple docks are connected,
-/// the first one with a linked profile wins.
+/// Priority: a dock-linked profile, then (if `match_monitors` is enabled)
+/// the profile whose monitor set best matches the connected displays, then
+/// the undocked fallback.
+///
+/// With several docks connected, `Metadata::resolve_docks` picks between
+/// their links and dock combinations.
 ///
 /// Skips applying if the target profile is already active AND its generated
 /// config is still current (no duplicate notifications, but port-name
-/// reshuffles on replug still trigger a re-apply).
-pub fn apply_auto() -> Result<()> {
+/// reshuffles on replug still trigger a re-apply), or if its last apply was
+/// reverted for want of confirmation. `in_daemon` is as for
+/// `apply_profile`.
+pub fn apply_auto(json: bool, trigger: Trigger, in_daemon: bool) -> Result<AutoOutcome> {
+    let compositor = compositor::detect();
     let metadata = Metadata::load()?;
     let docks = dock::detect_docks()?;
-    let current_profile = metadata.active_profile.as_deref();
-
-    // Check if any connected dock has a linked profile
-    for d in &docks {
-        if let Some(profile_name) = metadata.get_dock_profile(&d.uuid) {
-            // Skip if already on this profile and the config is current
-            if current_profile == Some(profile_name) && profile_up_to_date(profile_name) {
-                return Ok(());
-            }
-            println!("Detected dock: {} ({})", d.name, d.uuid);
-            send_notification(
-                "Dock Connected",
-                &format!("Applying profile: {}", profile_name),
-            );
-            return apply_profile(profile_name, false);
+    let say = |msg: String| {
+        if !json {
+            println!("{}", msg);
         }
-    }
+    };
 
-    // No dock found or no linked profile - use undocked profile
-    if let Some(ref undocked) = metadata.undocked_profile {
-        // Skip if already on this profile and the config is current
-        if current_profile == Some(undocked.as_str()) && profile_up_to_date(undocked) {
-            return Ok(());
+    // Check if any connected dock has a linked profile, then (opt-in) pick
+    // by the displays that are actually plugged in, then fall back to the
+    // undocked profile
+    let uuids: Vec<String> = docks.iter().map(|d| d.uuid.clone()).collect();
+    let (name, via, reason) = if let Some(choice) = metadata.resolve_docks(&uuids) {
+        let profile_name = choice.chosen.profile.clone();
+        if let Some(skip) = skip_auto_apply(&*compositor, &metadata, &profile_name) {
+            return Ok(report_skip(skip, json));
+        }
+        for d in &docks {
+            say(format!("Detected dock: {} ({})", d.name, d.uuid));
+        }
+        let explanation = choice.explain(|uuid| {
+            docks
+                .iter()
+                .find(|d| d.uuid == uuid)
+                .map_or_else(|| uuid.to_string(), |d| d.name.clone())
+        });
+        say(explanation.clone());
+        send_notification("Dock Connected", &format!("Applying profile: {}", profile_name));
+        (profile_name, "dock", Some(explanation))
+    } else if let Some((name, how)) = metadata
+        .match_monitors
+        .then(|| match_profile_by_monitors(&*compositor))
+        .flatten()
+    {
+        if let Some(skip) = skip_auto_apply(&*compositor, &metadata, &name) {
+            return Ok(report_skip(skip, json));
+        }
+        say(format!("Matched connected monitors ({}), applying profile: {}", how, name));
+        send_notification("Monitors Matched", &format!("Applying profile: {}", name));
+        (name, "monitors", Some(how.to_string()))
+    } else if let Some(undocked) = metadata.undocked_profile.clone() {
+        if let Some(skip) = skip_auto_apply(&*compositor, &metadata, &undocked) {
+            return Ok(report_skip(skip, json));
         }
         if docks.is_empty() {
-            println!("No dock detected, applying undocked profile: {}", undocked);
+            say(format!("No dock detected, applying undocked profile: {}", undocked));
         } else {
-            println!(
+            say(format!(
                 "Dock detected but not linked, applying undocked profile: {}",
                 undocked
-            );
+            ));
         }
         send_notification("Undocked", &format!("Applying profile: {}", undocked));
-        return apply_profile(undocked, false);
-    }
-
-    // No undocked profile configured
-    if docks.is_empty() {
-        println!("No dock detected and no undocked profile configured");
+        (undocked, "undocked", None)
     } else {
-        println!("Dock detected but not linked, and no undocked profile configured");
-        for d in &docks {
-            println!("  - {} ({})", d.name, d.uuid);
+        // No undocked profile configured
+        if docks.is_empty() {
+            say("No dock detected and no undocked profile configured".to_string());
+        } else {
+            say("Dock detected but not linked, and no undocked profile configured".to_string());
+            for d in &docks {
+                say(format!("  - {} ({})", d.name, d.uuid));
+            }
+        }
+        return Ok(AutoOutcome::NoProfile {
+            dock_uuids: docks.into_iter().map(|d| d.uuid).collect(),
+        });
+    };
+
+    let report = apply_profile(&name, false, None, json, trigger, in_daemon)?;
+    Ok(AutoOutcome::Applied {
+        via: via.to_string(),
+        reason,
+        report,
+    })
+}
+
+impl AutoOutcome {
+    /// One line for the terminal, when the daemon did the work and its own
+    /// messages went to its log
+    pub fn describe(&self) -> String {
+        match self {
+            AutoOutcome::Applied {
+                via,
+                reason: Some(reason),
+                report,
+            } => format!("Applied profile: {} (matched by {})\n{}", report.profile, via, reason),
+            AutoOutcome::Applied { via, report, .. } => {
+                format!("Applied profile: {} (matched by {})", report.profile, via)
+            }
+            AutoOutcome::UpToDate { profile } => format!("Profile {} is already active", profile),
+            AutoOutcome::Reverted { profile } => format!(
+                "Skipping profile {}: its last apply was reverted (apply it by hand to retry)",
+                profile
+            ),
+            AutoOutcome::NoProfile { .. } => "No linked dock, matching or undocked profile to apply".to_string(),
         }
     }
+}
 
-    Ok(())
+/// Tell the user why auto-apply did nothing (only worth a line when the
+/// profile was reverted; up-to-date skips stay silent for the daemon's sake)
+fn report_skip(skip: AutoOutcome, json: bool) -> AutoOutcome {
+    if let AutoOutcome::Reverted { profile } = &skip {
+        if !json {
+            println!(
+                "Skipping profile {}: its last apply was reverted (apply it by hand to retry)",
+                profile
+            );
+        }
+    }
+    skip
 }
 
 /// Send a desktop notification
@@ -147,8 +487,49 @@ fn send_notification(summary: &str, body: &str) {
         .show();
 }
 
+/// A connected dock, as shown by `current --json`
+#[derive(Debug, Serialize)]
+struct DockStatus {
+    name: String,
+    uuid: String,
+    vendor: Option<String>,
+    linked_profile: Option<String>,
+}
+
+/// `current --json` output, also part of the daemon's `status` reply
+#[derive(Debug, Serialize)]
+pub struct CurrentStatus {
+    active_profile: Option<String>,
+    undocked_profile: Option<String>,
+    docks: Vec<DockStatus>,
+}
+
+/// The active profile and connected docks with their linked profiles
+pub fn current_status() -> Result<CurrentStatus> {
+    let metadata = Metadata::load()?;
+    let docks = dock::detect_docks()?
+        .into_iter()
+        .map(|d| DockStatus {
+            linked_profile: metadata.get_dock_profile(&d.uuid).cloned(),
+            name: d.name,
+            uuid: d.uuid,
+            vendor: d.vendor,
+        })
+        .collect();
+    Ok(CurrentStatus {
+        active_profile: metadata.active_profile,
+        undocked_profile: metadata.undocked_profile,
+        docks,
+    })
+}
+
 /// Show the currently active profile
-pub fn show_current() -> Result<()> {
+pub fn show_current(json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&current_status()?)?);
+        return Ok(());
+    }
+
     let metadata = Metadata::load()?;
 
     match metadata.active_profile {
@@ -159,8 +540,61 @@ pub fn show_current() -> Result<()> {
     Ok(())
 }
 
+/// One profile, as shown by `list --json`
+#[derive(Debug, Serialize)]
+struct ProfileSummary {
+    name: String,
+    description: Option<String>,
+    monitors: usize,
+    active: bool,
+    dock_uuid: Option<String>,
+    undocked: bool,
+    /// Set when the profile file couldn't be loaded
+    error: Option<String>,
+}
+
+/// `list --json` output, also the daemon's `list` reply
+#[derive(Debug, Serialize)]
+pub struct ProfileListing {
+    active_profile: Option<String>,
+    undocked_profile: Option<String>,
+    match_monitors: bool,
+    profiles: Vec<ProfileSummary>,
+}
+
+/// Every saved profile with its dock link and active/undocked flags
+pub fn profile_listing() -> Result<ProfileListing> {
+    let metadata = Metadata::load()?;
+    let profiles = crate::profile::list_profiles()?
+        .into_iter()
+        .map(|name| {
+            let loaded = Profile::load(&name);
+            ProfileSummary {
+                description: loaded.as_ref().ok().and_then(|p| p.description.clone()),
+                monitors: loaded.as_ref().map(|p| p.monitors.len()).unwrap_or(0),
+                error: loaded.err().map(|e| format!("{:#}", e)),
+                active: metadata.active_profile.as_ref() == Some(&name),
+                dock_uuid: metadata.get_profile_dock(&name).cloned(),
+                undocked: metadata.undocked_profile.as_ref() == Some(&name),
+                name,
+            }
+        })
+        .collect();
+    Ok(ProfileListing {
+        active_profile: metadata.active_profile,
+        undocked_profile: metadata.undocked_profile,
+        match_monitors: metadata.match_monitors,
+        profiles,
+    })
+}
+
 /// List all available profiles
-pub fn list_profiles() -> Result<()> {
+pub fn list_profiles(json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&profile_listing()?)?);
+        return Ok(());
+    }
+
     let profiles = crate::profile::list_profiles()?;
     let metadata = Metadata::load()?;
 
@@ -197,3 +631,143 @@ pub fn list_profiles() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compositor::fake::FakeCompositor;
+    use crate::profile::{Monitor, Position, Workspace};
+
+    /// Point the config dir at a fresh temp dir for this test thread
+    fn config_root(test: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "hyprpier-apply-test-{}-{}",
+            std::process::id(),
+            test
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        crate::config::set_test_config_dir(dir.clone());
+        crate::sysfs::set_test_root(crate::sysfs::fixture("dock"));
+        dir
+    }
+
+    /// External Dell on the left (saved as DP-1), laptop panel on the right
+    fn save_desk_profile() {
+        let mut dell = Monitor::test_fixture("DP-1", "2560x1440", 1.0, 0);
+        dell.description = Some("Dell U2720Q".to_string());
+        let mut panel = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        panel.position = Position { x: 2560, y: 0 };
+
+        let mut profile = Profile::new("desk");
+        profile.monitors = vec![dell, panel];
+        profile.workspaces = vec![
+            Workspace {
+                id: 1,
+                monitor: "DP-1".to_string(),
+                default: true,
+            },
+            Workspace {
+                id: 2,
+                monitor: "eDP-1".to_string(),
+                default: true,
+            },
+        ];
+        profile.save().unwrap();
+    }
+
+    /// The dock came back with the Dell on DP-3, and workspace 1 on the panel
+    fn docked_compositor() -> FakeCompositor {
+        let panel = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        let mut dell = Monitor::test_fixture("DP-3", "2560x1440", 1.0, 0);
+        dell.description = Some("Dell U2720Q".to_string());
+        FakeCompositor::new(vec![panel, dell])
+    }
+
+    #[test]
+    fn apply_resolves_ports_writes_config_and_moves_workspaces() {
+        let root = config_root("full");
+        save_desk_profile();
+        let fake = docked_compositor();
+
+        let applied = apply_profile_inner(&fake, "desk", false, None, true, Trigger::Cli).unwrap();
+        assert!(applied.pending.is_none());
+        assert!(applied.report.runtime);
+        assert!(applied.report.warnings.is_empty(), "{:?}", applied.report.warnings);
+
+        let config = std::fs::read_to_string(root.join("hypr/monitors.lua")).unwrap();
+        assert!(config.contains("\"DP-3\""), "config: {}", config);
+        assert!(!config.contains("\"DP-1\""), "config: {}", config);
+
+        assert_eq!(
+            fake.calls(),
+            vec![
+                "monitor DP-3",
+                "monitor eDP-1",
+                "move_workspace 1 DP-3",
+                "move_workspace 2 eDP-1",
+                "reload",
+            ]
+        );
+        let state = fake.state.borrow();
+        let panel = state.monitors.iter().find(|m| m.name == "eDP-1").unwrap();
+        assert_eq!((panel.position.x, panel.position.y), (2560, 0));
+        assert_eq!(state.workspaces[0].monitor, "DP-3");
+
+        assert_eq!(Metadata::load().unwrap().active_profile.as_deref(), Some("desk"));
+    }
+
+    #[test]
+    fn post_apply_waits_until_the_layout_is_kept() {
+        let root = config_root("keep");
+        save_desk_profile();
+        let marker = root.join("post-applied");
+        let mut profile = Profile::load("desk").unwrap();
+        profile.revert_timeout = Some(30);
+        profile.hooks.post_apply = vec![format!("touch '{}'", marker.display())];
+        profile.save().unwrap();
+        let fake = docked_compositor();
+
+        let applied = apply_profile_inner(&fake, "desk", false, None, true, Trigger::Cli).unwrap();
+        let pending = applied.pending.expect("revert_timeout asks for confirmation");
+        assert!(!marker.exists());
+
+        assert!(pending.keep().unwrap().is_empty());
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn failed_monitor_apply_fails_and_keeps_previous_profile() {
+        config_root("fail");
+        save_desk_profile();
+        let fake = docked_compositor();
+        fake.state
+            .borrow_mut()
+            .failing_monitors
+            .insert("eDP-1".to_string());
+
+        let err = apply_profile_inner(&fake, "desk", false, None, true, Trigger::Cli)
+            .err()
+            .expect("apply should fail");
+        assert!(format!("{:#}", err).contains("eDP-1"));
+        assert!(!fake.calls().contains(&"reload".to_string()));
+        assert_eq!(Metadata::load().unwrap().active_profile, None);
+
+        let entries = history::load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(!entries[0].ok);
+        assert!(entries[0].error.as_deref().unwrap().contains("eDP-1"));
+    }
+
+    #[test]
+    fn no_runtime_only_writes_config() {
+        let root = config_root("no-runtime");
+        save_desk_profile();
+        let fake = docked_compositor();
+
+        let applied = apply_profile_inner(&fake, "desk", true, None, true, Trigger::Cli).unwrap();
+        assert!(!applied.report.runtime);
+        assert!(fake.calls().is_empty());
+        assert!(root.join("hypr/monitors.lua").exists());
+        assert_eq!(Metadata::load().unwrap().active_profile.as_deref(), Some("desk"));
+    }
+}
@@ -0,0 +1,363 @@
+//! boltd (the Thunderbolt device manager) over D-Bus
+//!
+//! When boltd is running it owns Thunderbolt authorization, and a udev rule
+//! writing sysfs `authorized` races its policy. In that case hyprpier
+//! enrolls allowlisted docks with bolt (policy `auto`, so bolt authorizes
+//! them on every connect) instead of installing authorize rules. Calls go
+//! through `busctl` rather than a D-Bus library.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::process::Command;
+
+const SERVICE: &str = "org.freedesktop.bolt";
+const MANAGER_PATH: &str = "/org/freedesktop/bolt";
+const MANAGER_IFACE: &str = "org.freedesktop.bolt1.Manager";
+const DEVICE_IFACE: &str = "org.freedesktop.bolt1.Device";
+
+/// Device properties read by `device_at`, in the order they're parsed
+const DEVICE_PROPERTIES: [&str; 6] = ["Uid", "Name", "Vendor", "Status", "Stored", "Policy"];
+
+/// The bolt daemon's own settings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Manager {
+    pub version: u64,
+    /// Security level of the domains, e.g. "user" or "secure"
+    pub security_level: String,
+    /// "enabled", or "disabled" when bolt won't authorize anything
+    pub auth_mode: String,
+}
+
+/// A device as bolt sees it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoltDevice {
+    pub uid: String,
+    pub name: String,
+    pub vendor: String,
+    /// e.g. "connected", "authorized", "auth-error", "disconnected"
+    pub status: String,
+    /// Enrolled, i.e. remembered across connects
+    pub stored: bool,
+    /// "auto", "manual", "iommu" or "default"
+    pub policy: String,
+}
+
+impl BoltDevice {
+    /// Enrolled with a policy that authorizes it on connect
+    pub fn auto_authorized(&self) -> bool {
+        self.stored && matches!(self.policy.as_str(), "auto" | "iommu")
+    }
+}
+
+/// Stands in for busctl in tests: gets the arguments, returns the stdout
+#[cfg(test)]
+type FakeBusctl = Box<dyn FnMut(&[&str]) -> Result<String>>;
+
+#[cfg(test)]
+thread_local! {
+    static TEST_BUSCTL: std::cell::RefCell<Option<FakeBusctl>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Answer busctl calls with `fake` for the rest of this test thread
+#[cfg(test)]
+pub fn set_test_busctl(fake: impl FnMut(&[&str]) -> Result<String> + 'static) {
+    TEST_BUSCTL.with(|b| *b.borrow_mut() = Some(Box::new(fake)));
+}
+
+/// Run `busctl --system --json=short <args>` and return its stdout
+fn busctl(args: &[&str]) -> Result<String> {
+    #[cfg(test)]
+    if let Some(reply) = TEST_BUSCTL.with(|b| b.borrow_mut().as_mut().map(|fake| fake(args))) {
+        return reply;
+    }
+    let output = Command::new("busctl")
+        .args(["--system", "--json=short"])
+        .args(args)
+        .output()
+        .context("Failed to run busctl")?;
+    if !output.status.success() {
+        bail!(
+            "busctl {}: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Call a bolt method and return the reply's arguments
+fn call(path: &str, iface: &str, method: &str, args: &[&str]) -> Result<Vec<Value>> {
+    let mut all = vec!["call", SERVICE, path, iface, method];
+    all.extend_from_slice(args);
+    reply_data(&busctl(&all)?)
+}
+
+/// The `data` of a `busctl --json=short call` reply
+fn reply_data(text: &str) -> Result<Vec<Value>> {
+    let reply: Value = serde_json::from_str(text.trim()).context("Unexpected busctl output")?;
+    match reply.get("data") {
+        Some(Value::Array(data)) => Ok(data.clone()),
+        _ => bail!("Unexpected busctl output: {}", text.trim()),
+    }
+}
+
+/// Read properties; `get-property` prints one JSON object per property
+fn properties(path: &str, iface: &str, names: &[&str]) -> Result<Vec<Value>> {
+    let mut all = vec!["get-property", SERVICE, path, iface];
+    all.extend_from_slice(names);
+    parse_properties(&busctl(&all)?, names.len())
+}
+
+fn parse_properties(text: &str, count: usize) -> Result<Vec<Value>> {
+    let values = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let value: Value = serde_json::from_str(line).context("Unexpected busctl output")?;
+            Ok(value.get("data").cloned().unwrap_or(Value::Null))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if values.len() != count {
+        bail!("Expected {} properties from busctl, got {}", count, values.len());
+    }
+    Ok(values)
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// Whether boltd currently owns its bus name. Asking the bus instead of
+/// bolt itself keeps this from D-Bus-activating it.
+pub fn is_running() -> bool {
+    busctl(&[
+        "call",
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        "s",
+        SERVICE,
+    ])
+    .and_then(|text| reply_data(&text))
+    .is_ok_and(|data| data.first().and_then(Value::as_bool) == Some(true))
+}
+
+/// bolt's settings, or None when boltd isn't running
+pub fn manager() -> Option<Manager> {
+    if !is_running() {
+        return None;
+    }
+    let values = properties(
+        MANAGER_PATH,
+        MANAGER_IFACE,
+        &["Version", "SecurityLevel", "AuthMode"],
+    )
+    .ok()?;
+    Some(Manager {
+        version: values[0].as_u64().unwrap_or_default(),
+        security_level: string(&values[1]),
+        auth_mode: string(&values[2]),
+    })
+}
+
+/// Every device bolt knows: connected ones and enrolled ones
+pub fn devices() -> Result<Vec<BoltDevice>> {
+    let data = call(MANAGER_PATH, MANAGER_IFACE, "ListDevices", &[])?;
+    let paths = data
+        .first()
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    paths
+        .iter()
+        .filter_map(Value::as_str)
+        .map(device_at)
+        .collect()
+}
+
+/// The device with this Thunderbolt UUID; fails if bolt doesn't know it
+pub fn device(uid: &str) -> Result<BoltDevice> {
+    let data = call(MANAGER_PATH, MANAGER_IFACE, "DeviceByUid", &["s", uid])?;
+    match data.first().and_then(Value::as_str) {
+        Some(path) => device_at(path),
+        None => bail!("boltd has no device {}", uid),
+    }
+}
+
+fn device_at(path: &str) -> Result<BoltDevice> {
+    let values = properties(path, DEVICE_IFACE, &DEVICE_PROPERTIES)?;
+    Ok(device_from_properties(&values))
+}
+
+fn device_from_properties(values: &[Value]) -> BoltDevice {
+    BoltDevice {
+        uid: string(&values[0]),
+        name: string(&values[1]),
+        vendor: string(&values[2]),
+        status: string(&values[3]),
+        stored: values[4].as_bool().unwrap_or(false),
+        policy: string(&values[5]),
+    }
+}
+
+/// Enroll a connected device with policy `auto`, which also authorizes it
+/// now. Returns false when it was already enrolled that way, so this is
+/// cheap to call on every connect. A device the user enrolled with another
+/// policy is left alone. bolt asks polkit, so outside of root this can be
+/// refused.
+pub fn enroll(uid: &str) -> Result<bool> {
+    let device = device(uid)?;
+    if device.auto_authorized() {
+        return Ok(false);
+    }
+    if device.stored {
+        bail!(
+            "{} is enrolled with bolt with policy '{}'; leaving it as is \
+             (`boltctl forget {}` lets hyprpier enroll it)",
+            device.name,
+            device.policy,
+            uid
+        );
+    }
+    if device.status == "disconnected" {
+        bail!("{} isn't connected; bolt only enrolls connected devices", device.name);
+    }
+    call(
+        MANAGER_PATH,
+        MANAGER_IFACE,
+        "EnrollDevice",
+        &["sss", uid, "auto", ""],
+    )
+    .with_context(|| format!("boltd refused to enroll {}", device.name))?;
+    Ok(true)
+}
+
+/// Remove a device from bolt's store, so it's no longer authorized on
+/// connect. Not knowing the device is fine.
+pub fn forget(uid: &str) -> Result<()> {
+    match device(uid) {
+        Ok(device) if device.stored => {
+            call(MANAGER_PATH, MANAGER_IFACE, "ForgetDevice", &["s", uid])?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Have bolt forget the devices that were on the allowlist `before` and
+/// aren't on `after`, so they stop being authorized on connect. Call it
+/// whenever an unlink, untrust or profile deletion shrinks the allowlist.
+/// Nothing to do without boltd.
+pub fn forget_dropped(before: &[String], after: &[String]) -> Result<()> {
+    let dropped: Vec<&String> = before.iter().filter(|uuid| !after.contains(uuid)).collect();
+    if dropped.is_empty() || !is_running() {
+        return Ok(());
+    }
+    for uuid in dropped {
+        forget(uuid)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_device_properties() {
+        let text = r#"{"type":"s","data":"2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f"}
+{"type":"s","data":"UltraFine Display"}
+{"type":"s","data":"LG Electronics"}
+{"type":"s","data":"authorized"}
+{"type":"b","data":true}
+{"type":"s","data":"auto"}
+"#;
+        let values = parse_properties(text, DEVICE_PROPERTIES.len()).unwrap();
+        let device = device_from_properties(&values);
+        assert_eq!(device.uid, "2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f");
+        assert_eq!(device.vendor, "LG Electronics");
+        assert_eq!(device.status, "authorized");
+        assert!(device.auto_authorized());
+
+        // A property busctl didn't print is an error, not a shifted read
+        assert!(parse_properties(text, DEVICE_PROPERTIES.len() + 1).is_err());
+    }
+
+    #[test]
+    fn parses_call_replies() {
+        let data = reply_data(
+            r#"{"type":"ao","data":[["/org/freedesktop/bolt/devices/2b7e4f91_1d3a"]]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            data[0][0].as_str(),
+            Some("/org/freedesktop/bolt/devices/2b7e4f91_1d3a")
+        );
+        assert!(reply_data("Call failed").is_err());
+    }
+
+    /// A boltd that knows one device per uid, stored with `policy`, and
+    /// logs every method called
+    fn fake_bolt(policy: &'static str) -> std::rc::Rc<std::cell::RefCell<Vec<String>>> {
+        let calls = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = calls.clone();
+        set_test_busctl(move |args| {
+            let reply = match args {
+                ["call", .., "NameHasOwner", _, _] => r#"{"type":"b","data":[true]}"#.to_string(),
+                ["call", .., "DeviceByUid", "s", uid] => {
+                    format!(r#"{{"type":"o","data":["/org/freedesktop/bolt/devices/{}"]}}"#, uid)
+                }
+                ["get-property", _, path, ..] => {
+                    let uid = path.rsplit('/').next().unwrap_or_default();
+                    // One line per DEVICE_PROPERTIES entry, like get-property prints
+                    let values = [
+                        Value::from(uid),
+                        Value::from("Dock"),
+                        Value::from("Vendor"),
+                        Value::from("authorized"),
+                        Value::from(true),
+                        Value::from(policy),
+                    ];
+                    values
+                        .iter()
+                        .map(|v| format!("{}\n", serde_json::json!({ "data": v })))
+                        .collect()
+                }
+                ["call", _, _, _, method, rest @ ..] => {
+                    log.borrow_mut().push(format!("{} {}", method, rest.join(" ")));
+                    r#"{"type":"","data":[]}"#.to_string()
+                }
+                _ => bail!("unexpected busctl {:?}", args),
+            };
+            Ok(reply)
+        });
+        calls
+    }
+
+    #[test]
+    fn forgets_devices_that_leave_the_allowlist() {
+        let calls = fake_bolt("auto");
+        let before = vec!["dock-a".to_string(), "dock-b".to_string()];
+        forget_dropped(&before, &before[1..]).unwrap();
+        assert_eq!(*calls.borrow(), ["ForgetDevice s dock-a"]);
+
+        calls.borrow_mut().clear();
+        forget_dropped(&before, &before).unwrap();
+        assert!(calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn enroll_leaves_a_manual_policy_alone() {
+        let calls = fake_bolt("manual");
+        let e = enroll("dock-a").unwrap_err();
+        assert!(format!("{:#}", e).contains("policy 'manual'"), "{:#}", e);
+        assert!(calls.borrow().is_empty());
+
+        let calls = fake_bolt("auto");
+        assert!(!enroll("dock-a").unwrap());
+        assert!(calls.borrow().is_empty());
+    }
+}
@@ -1,10 +1,15 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "hyprpier")]
 #[command(about = "Hyprland monitor profile manager with dock detection")]
 #[command(version)]
 pub struct Cli {
+    /// Print machine-readable JSON instead of human text
+    #[arg(long, global = true)]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -23,6 +28,18 @@ pub enum Commands {
         /// Generate config only, don't apply via hyprctl
         #[arg(long)]
         no_runtime: bool,
+
+        /// Show what would change (monitors, workspaces, config diff) without applying
+        #[arg(long, conflicts_with = "auto")]
+        dry_run: bool,
+
+        /// Revert unless the new layout is kept within SECS (overrides the profile's setting; 0 disables)
+        #[arg(long, value_name = "SECS", conflicts_with_all = ["auto", "dry_run"])]
+        confirm: Option<u64>,
+
+        /// Go back to the profile applied before the current one (from the history)
+        #[arg(long, conflicts_with_all = ["profile", "auto"])]
+        previous: bool,
     },
 
     /// Launch the TUI manager
@@ -34,15 +51,79 @@ pub enum Commands {
     /// Show currently active profile
     Current,
 
+    /// Save the current monitor layout and workspaces as a profile
+    Save {
+        /// Profile name
+        name: String,
+
+        /// Link the profile to the connected dock (several Thunderbolt or USB docks: to that
+        /// combination)
+        #[arg(long)]
+        link_dock: bool,
+
+        /// Make it the undocked profile
+        #[arg(long)]
+        undocked: bool,
+
+        /// Keep the positions the compositor reports instead of arranging left to right
+        #[arg(long)]
+        keep_positions: bool,
+
+        /// Replace an existing profile of that name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show recent applies: profile, trigger, docks, outcome and warnings
+    History {
+        /// How many entries to show, newest first
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+
     /// Show Thunderbolt device information
     Thunderbolt {
-        /// List all Thunderbolt devices
+        /// List Thunderbolt devices and other docks (USB, DisplayLink, displays)
         #[arg(long)]
         list: bool,
 
+        /// With --list: show Thunderbolt devices as a daisy-chain tree,
+        /// with link speed, generation and authorization
+        #[arg(long)]
+        tree: bool,
+
         /// Show Thunderbolt security status
         #[arg(long)]
         status: bool,
+
+        /// Authorize this device UUID on connect, without linking it
+        #[arg(long, value_name = "UUID")]
+        trust: Option<String>,
+
+        /// Stop authorizing a device added with --trust
+        #[arg(long, value_name = "UUID", conflicts_with = "trust")]
+        untrust: Option<String>,
+    },
+
+    /// Import profiles from another tool's config
+    Import {
+        /// Config format to read
+        #[arg(value_enum)]
+        format: ExternalFormat,
+
+        /// Config file to read
+        file: PathBuf,
+
+        /// Replace existing profiles with the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print all profiles in another tool's config format
+    Export {
+        /// Config format to write
+        #[arg(value_enum)]
+        format: ExternalFormat,
     },
 
     /// Install/uninstall udev rules for auto-switching
@@ -54,12 +135,29 @@ pub enum Commands {
         /// Install/uninstall resume service (resets Thunderbolt controller on wake)
         #[arg(long)]
         resume: bool,
+
+        /// Install/uninstall the daemon as a systemd user service (no sudo)
+        #[arg(long, conflicts_with = "resume")]
+        daemon: bool,
     },
 
+    /// Check the setup (compositor, config include, daemon, udev, resume
+    /// service, Thunderbolt security, metadata) and suggest fixes
+    Doctor,
+
     /// Start the background daemon
     Daemon,
 
+    /// Stream daemon events (docks, applies, lid) until interrupted
+    Watch,
+
     /// Notify the daemon of a dock event (used by udev)
     #[command(hide = true)]
     Notify,
 }
+
+/// Other monitor managers' config formats
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExternalFormat {
+    Kanshi,
+}
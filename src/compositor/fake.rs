@@ -0,0 +1,190 @@
+//! In-memory compositor for tests
+//!
+//! Holds a live layout (monitors, workspaces, windows, focus), updates it
+//! the way Hyprland would for the calls the apply pipeline makes, and keeps
+//! a log of every call so tests can assert on what was done and in what
+//! order.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::{
+    Compositor, Dialect, LiveClient, LiveMonitorState, LiveWorkspace, Mode, OutputModes,
+    WorkspaceRef,
+};
+use crate::hyprland::ConfigFormat;
+use crate::profile::Monitor;
+
+#[derive(Debug, Default)]
+pub struct FakeState {
+    pub running: bool,
+    /// Every connected monitor, disabled ones included
+    pub monitors: Vec<Monitor>,
+    pub workspaces: Vec<LiveWorkspace>,
+    pub clients: Vec<LiveClient>,
+    pub focused_monitor: Option<String>,
+    /// Monitors whose runtime apply fails
+    pub failing_monitors: HashSet<String>,
+    /// One entry per call, e.g. "monitor DP-1", "move_workspace 2 DP-1"
+    pub calls: Vec<String>,
+}
+
+pub struct FakeCompositor {
+    pub dialect: Dialect,
+    pub state: RefCell<FakeState>,
+}
+
+impl FakeCompositor {
+    /// A running compositor with these monitors connected and workspace
+    /// `i + 1` shown on monitor `i`
+    pub fn new(monitors: Vec<Monitor>) -> Self {
+        let workspaces = monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| LiveWorkspace {
+                id: i as i64 + 1,
+                monitor: m.name.clone(),
+            })
+            .collect();
+        let focused_monitor = monitors.first().map(|m| m.name.clone());
+        Self {
+            dialect: Dialect::Hyprland(ConfigFormat::Lua),
+            state: RefCell::new(FakeState {
+                running: true,
+                monitors,
+                workspaces,
+                focused_monitor,
+                ..FakeState::default()
+            }),
+        }
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.state.borrow().calls.clone()
+    }
+}
+
+impl Compositor for FakeCompositor {
+    fn is_running(&self) -> bool {
+        self.state.borrow().running
+    }
+
+    fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    fn monitors(&self, all: bool) -> Result<Vec<Monitor>> {
+        let state = self.state.borrow();
+        if !state.running {
+            anyhow::bail!("compositor is not running");
+        }
+        Ok(state
+            .monitors
+            .iter()
+            .filter(|m| all || m.enabled)
+            .cloned()
+            .collect())
+    }
+
+    /// Each monitor supports just the mode it's in
+    fn available_modes(&self) -> Result<Vec<OutputModes>> {
+        Ok(self
+            .state
+            .borrow()
+            .monitors
+            .iter()
+            .map(|m| OutputModes {
+                name: m.name.clone(),
+                description: m.description.clone(),
+                modes: Mode::parse(&m.mode).into_iter().collect(),
+            })
+            .collect())
+    }
+
+    fn workspaces(&self) -> Result<Vec<LiveWorkspace>> {
+        Ok(self.state.borrow().workspaces.clone())
+    }
+
+    fn clients(&self) -> Result<Vec<LiveClient>> {
+        Ok(self.state.borrow().clients.clone())
+    }
+
+    fn monitor_states(&self) -> Result<Vec<LiveMonitorState>> {
+        let state = self.state.borrow();
+        Ok(state
+            .monitors
+            .iter()
+            .filter(|m| m.enabled)
+            .map(|m| LiveMonitorState {
+                focused: state.focused_monitor.as_ref() == Some(&m.name),
+                active_workspace: WorkspaceRef {
+                    id: state
+                        .workspaces
+                        .iter()
+                        .find(|w| w.monitor == m.name)
+                        .map_or(0, |w| w.id),
+                },
+            })
+            .collect())
+    }
+
+    fn eval(&self, steps: &[String]) -> Vec<Result<()>> {
+        let mut state = self.state.borrow_mut();
+        steps
+            .iter()
+            .map(|step| {
+                state.calls.push(format!("eval {}", step));
+                Ok(())
+            })
+            .collect()
+    }
+
+    fn reload(&self) -> Result<()> {
+        self.state.borrow_mut().calls.push("reload".to_string());
+        Ok(())
+    }
+
+    /// Like Hyprland, settings for an output that isn't connected are
+    /// accepted and simply do nothing
+    fn apply_monitors(&self, monitors: &[Monitor]) -> Vec<Result<()>> {
+        let mut state = self.state.borrow_mut();
+        monitors
+            .iter()
+            .map(|wanted| {
+                state.calls.push(format!("monitor {}", wanted.name));
+                if state.failing_monitors.contains(&wanted.name) {
+                    anyhow::bail!("error: could not apply {}", wanted.name);
+                }
+                if let Some(live) = state.monitors.iter_mut().find(|m| m.name == wanted.name) {
+                    // The description is the hardware's, not the profile's
+                    let description = live.description.take();
+                    *live = wanted.clone();
+                    live.description = description;
+                }
+                Ok(())
+            })
+            .collect()
+    }
+
+    fn move_workspace(&self, workspace_id: u8, monitor: &str) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        state
+            .calls
+            .push(format!("move_workspace {} {}", workspace_id, monitor));
+        if !state.monitors.iter().any(|m| m.name == monitor && m.enabled) {
+            anyhow::bail!("no such monitor {}", monitor);
+        }
+        if let Some(ws) = state
+            .workspaces
+            .iter_mut()
+            .find(|w| w.id == i64::from(workspace_id))
+        {
+            ws.monitor = monitor.to_string();
+        }
+        Ok(())
+    }
+
+    fn sleep(&self, _duration: Duration) {}
+}
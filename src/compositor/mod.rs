@@ -0,0 +1,216 @@
+//! Compositor backends
+//!
+//! Everything an apply needs from the running compositor goes through the
+//! `Compositor` trait. The Hyprland backend lives in `hyprland`, the sway
+//! one in `sway`; tests use `fake::FakeCompositor`, which keeps a layout in
+//! memory and records every call, so the apply pipeline runs without a
+//! compositor.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::hyprland::{self, ConfigFormat};
+use crate::profile::{Monitor, Profile};
+use crate::sway;
+
+#[cfg(test)]
+pub mod fake;
+
+/// A live workspace and the monitor it's on
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LiveWorkspace {
+    pub id: i64,
+    pub monitor: String,
+}
+
+/// Workspace reference nested in client and monitor state
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WorkspaceRef {
+    pub id: i64,
+}
+
+/// A live window and the workspace it's on
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LiveClient {
+    pub address: String,
+    pub workspace: WorkspaceRef,
+}
+
+/// Focus and active workspace of a live monitor
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LiveMonitorState {
+    #[serde(default)]
+    pub focused: bool,
+    #[serde(rename = "activeWorkspace")]
+    pub active_workspace: WorkspaceRef,
+}
+
+/// A mode an output supports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    pub refresh_rate: f64,
+}
+
+impl Mode {
+    /// Parse Hyprland's `availableModes` entries, e.g. "3840x2160@59.94Hz"
+    pub fn parse(text: &str) -> Option<Self> {
+        let (size, rate) = text.split_once('@')?;
+        let (width, height) = size.split_once('x')?;
+        Some(Mode {
+            width: width.trim().parse().ok()?,
+            height: height.trim().parse().ok()?,
+            refresh_rate: rate.trim().trim_end_matches("Hz").parse().ok()?,
+        })
+    }
+
+    pub fn resolution(&self) -> String {
+        format!("{}x{}", self.width, self.height)
+    }
+
+    /// `Monitor.mode` for this mode, e.g. "2560x1440@59.95" or "1920x1080@60"
+    pub fn mode_string(&self) -> String {
+        let rate = format!("{:.2}", self.refresh_rate);
+        let rate = rate.trim_end_matches('0').trim_end_matches('.');
+        format!("{}x{}@{}", self.width, self.height, rate)
+    }
+}
+
+/// The modes one connected output supports, as the compositor lists them
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputModes {
+    pub name: String,
+    pub description: Option<String>,
+    pub modes: Vec<Mode>,
+}
+
+/// The config and runtime command language a backend speaks. Hyprland's
+/// depends on its version; sway only has the one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Hyprland(ConfigFormat),
+    Sway,
+}
+
+impl Dialect {
+    /// Where the generated config lives
+    pub fn config_path(self) -> Result<PathBuf> {
+        match self {
+            Dialect::Hyprland(format) => format.config_path(),
+            Dialect::Sway => crate::config::sway_monitors_conf(),
+        }
+    }
+
+    pub fn generate_config(self, profile: &Profile) -> String {
+        match self {
+            Dialect::Hyprland(format) => hyprland::generate_config(profile, format),
+            Dialect::Sway => sway::generate_config(profile),
+        }
+    }
+
+    /// Runtime step that applies one monitor
+    pub fn monitor_step(self, monitor: &Monitor) -> String {
+        match self {
+            Dialect::Hyprland(format) => hyprland::monitor_step(format, monitor),
+            Dialect::Sway => sway::output_command(monitor),
+        }
+    }
+
+    /// Runtime step that moves a workspace to a monitor
+    pub fn move_workspace_step(self, workspace_id: u8, monitor: &str) -> String {
+        match self {
+            Dialect::Hyprland(format) => {
+                hyprland::move_workspace_step(format, workspace_id, monitor)
+            }
+            Dialect::Sway => sway::move_workspace_command(workspace_id, monitor),
+        }
+    }
+
+    /// Runtime step that moves a window to a workspace without following it
+    pub fn move_window_step(self, address: &str, workspace_id: u8) -> String {
+        match self {
+            Dialect::Hyprland(format) => hyprland::move_window_step(format, address, workspace_id),
+            Dialect::Sway => sway::move_window_command(address, workspace_id),
+        }
+    }
+
+    /// Runtime step that focuses a workspace
+    pub fn focus_workspace_step(self, workspace_id: i64) -> String {
+        match self {
+            Dialect::Hyprland(format) => hyprland::focus_workspace_step(format, workspace_id),
+            Dialect::Sway => sway::focus_workspace_command(workspace_id),
+        }
+    }
+}
+
+/// What hyprpier needs from the running compositor. Runtime steps are
+/// strings in the language of `dialect()`, built by `Dialect`'s step
+/// helpers.
+pub trait Compositor {
+    fn is_running(&self) -> bool;
+
+    /// The config language to generate and to build runtime steps in
+    fn dialect(&self) -> Dialect;
+
+    /// Connected monitors; with `all`, disabled ones too (`enabled: false`)
+    fn monitors(&self, all: bool) -> Result<Vec<Monitor>>;
+
+    /// Modes each connected output supports, disabled outputs included
+    fn available_modes(&self) -> Result<Vec<OutputModes>>;
+
+    fn workspaces(&self) -> Result<Vec<LiveWorkspace>>;
+
+    fn clients(&self) -> Result<Vec<LiveClient>>;
+
+    fn monitor_states(&self) -> Result<Vec<LiveMonitorState>>;
+
+    /// Run runtime steps in order, batched where the backend can; one
+    /// result per step
+    fn eval(&self, steps: &[String]) -> Vec<Result<()>>;
+
+    /// Re-read the compositor's config files
+    fn reload(&self) -> Result<()>;
+
+    /// Apply monitor settings at runtime; one result per monitor
+    fn apply_monitors(&self, monitors: &[Monitor]) -> Vec<Result<()>> {
+        let dialect = self.dialect();
+        let steps: Vec<String> = monitors.iter().map(|m| dialect.monitor_step(m)).collect();
+        self.eval(&steps)
+    }
+
+    fn apply_monitor(&self, monitor: &Monitor) -> Result<()> {
+        self.apply_monitors(std::slice::from_ref(monitor))
+            .pop()
+            .unwrap_or(Ok(()))
+            .with_context(|| format!("Failed to apply monitor {}", monitor.name))
+    }
+
+    fn move_workspace(&self, workspace_id: u8, monitor: &str) -> Result<()> {
+        let step = self.dialect().move_workspace_step(workspace_id, monitor);
+        self.eval(&[step])
+            .pop()
+            .unwrap_or(Ok(()))
+            .with_context(|| format!("Failed to move workspace {}", workspace_id))
+    }
+
+    /// Give the compositor time to settle between runtime steps
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The backend for the running session: sway when the environment says
+/// so, Hyprland otherwise. Hyprland's config format is resolved here, once
+/// per backend rather than once per runtime step.
+pub fn detect() -> Box<dyn Compositor> {
+    if sway::detected() {
+        Box::new(sway::Sway)
+    } else {
+        Box::new(hyprland::Hyprland {
+            format: ConfigFormat::current(),
+        })
+    }
+}
@@ -1,8 +1,27 @@
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 
+#[cfg(test)]
+thread_local! {
+    /// Config root for the current test thread, so tests that save profiles
+    /// or metadata never touch the developer's real config
+    static TEST_CONFIG_DIR: std::cell::RefCell<Option<PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Use `dir` in place of ~/.config for the rest of this test thread
+#[cfg(test)]
+pub fn set_test_config_dir(dir: PathBuf) {
+    TEST_CONFIG_DIR.with(|d| *d.borrow_mut() = Some(dir));
+}
+
 /// Get the config directory, respecting SUDO_USER when running with sudo
 fn config_dir() -> Result<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_CONFIG_DIR.with(|d| d.borrow().clone()) {
+        return Ok(dir);
+    }
+
     // If running with sudo (e.g., hyprpier setup), use the original user's config
     if let Ok(sudo_user) = std::env::var("SUDO_USER") {
         // Validate username: alphanumeric, underscore, hyphen only (no path traversal)
@@ -51,11 +70,39 @@ pub fn metadata_path() -> Result<PathBuf> {
     Ok(profile_dir()?.join(".metadata.json"))
 }
 
+/// Get the apply history path (~/.config/hyprpier/.history.json)
+pub fn history_path() -> Result<PathBuf> {
+    Ok(profile_dir()?.join(".history.json"))
+}
+
+/// Get the global hooks directory (~/.config/hyprpier/hooks/)
+pub fn hooks_dir() -> Result<PathBuf> {
+    Ok(profile_dir()?.join("hooks"))
+}
+
 /// Get the Hyprland monitors.lua output path (~/.config/hypr/monitors.lua)
 pub fn hyprland_monitors_lua() -> Result<PathBuf> {
     Ok(config_dir()?.join("hypr").join("monitors.lua"))
 }
 
+/// Get the legacy hyprlang output path (~/.config/hypr/monitors.conf), used
+/// for Hyprland releases before 0.55
+pub fn hyprland_monitors_conf() -> Result<PathBuf> {
+    Ok(config_dir()?.join("hypr").join("monitors.conf"))
+}
+
+/// Get the sway output config path (~/.config/sway/monitors.conf), pulled
+/// into the sway config with `include`
+pub fn sway_monitors_conf() -> Result<PathBuf> {
+    Ok(config_dir()?.join("sway").join("monitors.conf"))
+}
+
+/// Get the daemon's systemd user unit path
+/// (~/.config/systemd/user/hyprpier.service)
+pub fn daemon_user_unit() -> Result<PathBuf> {
+    Ok(config_dir()?.join("systemd").join("user").join("hyprpier.service"))
+}
+
 /// Ensure the profile directory exists
 pub fn ensure_profile_dir() -> Result<()> {
     let dir = profile_dir()?;
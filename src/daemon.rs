@@ -1,20 +1,37 @@
 //! Hyprpier daemon for handling dock events from udev
 //!
-//! The daemon listens on a Unix socket for commands from udev rules.
-//! Running in the user session gives it access to D-Bus, Hyprland, and notifications.
+//! The daemon listens on a Unix socket for commands from udev rules and
+//! clients (see `protocol`), and on Hyprland's event socket for displays
+//! being connected or disconnected. Running in the user session gives it
+//! access to D-Bus, Hyprland, and notifications.
 
 use anyhow::{Context, Result};
-use std::io::{Read, Write};
+use notify_rust::{Notification, Timeout};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::PathBuf;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::apply;
+use crate::apply::{self, ApplyReport, AutoOutcome, CurrentStatus};
+use crate::bolt;
+use crate::dock::{self, Dock, DockSource};
+use crate::events::{self, Event};
+use crate::history::Trigger;
+use crate::hyprland;
 use crate::metadata::Metadata;
+use crate::protocol::{self, Request};
+use crate::setup::{self, DaemonService};
+use crate::sway;
+use crate::thunderbolt;
 
 const SOCKET_NAME: &str = "hyprpier.sock";
 const SETTLE_DELAY_MS: u64 = 3000;
+const EVENT_RECONNECT_DELAY_MS: u64 = 5000;
+const LID_POLL_MS: u64 = 2000;
+/// A subscriber that stops reading is dropped rather than stalling applies
+const SUBSCRIBER_WRITE_TIMEOUT_MS: u64 = 1000;
 
 /// Get the socket path ($XDG_RUNTIME_DIR/hyprpier.sock)
 pub fn get_socket_path() -> Result<PathBuf> {
@@ -23,6 +40,37 @@ pub fn get_socket_path() -> Result<PathBuf> {
     Ok(PathBuf::from(runtime_dir).join(SOCKET_NAME))
 }
 
+/// How the daemon is doing, for `thunderbolt --status`
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub service: DaemonService,
+    pub service_active: bool,
+    pub socket_reachable: bool,
+    /// Version the running daemon reports; None for daemons from before
+    /// `status` carried one
+    pub version: Option<String>,
+    /// The running daemon is this binary's version, so it applies profiles
+    /// the way this binary would
+    pub version_matches: bool,
+}
+
+/// Check the user unit and ask the daemon (if any) for its version
+pub fn health() -> Health {
+    let status = request(&Request::Status).ok().flatten();
+    let version = status
+        .as_ref()
+        .and_then(|s| s.get("version"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    Health {
+        service: setup::daemon_service(),
+        service_active: setup::daemon_service_active(),
+        socket_reachable: status.is_some(),
+        version_matches: version.as_deref() == Some(env!("CARGO_PKG_VERSION")),
+        version,
+    }
+}
+
 /// Find any hyprpier socket (for notify command running as root from udev)
 /// Searches /run/user/*/hyprpier.sock
 fn find_socket_path() -> Result<PathBuf> {
@@ -51,18 +99,369 @@ fn find_socket_path() -> Result<PathBuf> {
 pub struct Daemon {
     socket_path: PathBuf,
     listener: UnixListener,
-    refresh_tx: mpsc::Sender<()>,
+    jobs: mpsc::Sender<Job>,
+    state: SharedState,
+}
+
+/// Work for the apply worker. Every apply goes through it, one at a time.
+enum Job {
+    /// A dock or monitor event: debounced auto-apply, skipped while paused
+    Refresh,
+    /// Auto-apply requested by a client; runs even while paused
+    Auto(mpsc::Sender<Result<AutoOutcome>>),
+    Apply {
+        profile: String,
+        no_runtime: bool,
+        reply: mpsc::Sender<Result<ApplyReport>>,
+    },
+}
+
+/// The last apply the worker attempted, for `status`
+#[derive(Debug, Clone, Serialize)]
+struct LastApply {
+    /// None when auto-apply failed before picking a profile
+    profile: Option<String>,
+    /// "refresh", "auto" or "request"
+    trigger: &'static str,
+    ok: bool,
+    error: Option<String>,
+    /// Unix timestamp (seconds)
+    at: u64,
+}
+
+#[derive(Default)]
+struct State {
+    paused: bool,
+    /// A refresh is waiting out the debounce
+    refresh_pending: bool,
+    last_apply: Option<LastApply>,
+    subscribers: Vec<UnixStream>,
+    /// Docks seen at the last scan, to report connects and disconnects
+    docks: Vec<Dock>,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+/// `status` reply
+#[derive(Serialize)]
+struct DaemonStatus {
+    /// Version of the running daemon, so clients can spot a stale one
+    version: &'static str,
+    #[serde(flatten)]
+    current: CurrentStatus,
+    paused: bool,
+    refresh_pending: bool,
+    last_apply: Option<LastApply>,
+}
+
+fn lock(state: &SharedState) -> std::sync::MutexGuard<'_, State> {
+    // A panicked client thread leaves nothing half-written worth refusing
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Send an event line to every subscriber, dropping the ones that are gone.
+/// Writes happen with the state unlocked: a stalled subscriber can take
+/// SUBSCRIBER_WRITE_TIMEOUT_MS per event, and client requests shouldn't
+/// wait on it.
+fn broadcast(state: &SharedState, event: &Event) {
+    // Broadcasts still go one at a time, or a second one would find the
+    // list taken and its event would be lost
+    static SENDING: Mutex<()> = Mutex::new(());
+    let _sending = SENDING.lock().unwrap_or_else(|e| e.into_inner());
+
+    let line = protocol::encode_event(event);
+    let mut subscribers = std::mem::take(&mut lock(state).subscribers);
+    subscribers.retain_mut(|s| s.write_all(line.as_bytes()).is_ok());
+    // Keep whoever subscribed in the meantime too
+    lock(state).subscribers.append(&mut subscribers);
+}
+
+/// Record the outcome of an apply for `status` and tell subscribers
+fn record(state: &SharedState, trigger: &'static str, profile: Option<String>, result: Result<(), String>) {
+    let event = match &result {
+        Ok(()) => Event::Applied {
+            profile: profile.clone().unwrap_or_default(),
+            trigger: trigger.to_string(),
+        },
+        Err(e) => Event::ApplyFailed {
+            profile: profile.clone(),
+            trigger: trigger.to_string(),
+            error: e.clone(),
+        },
+    };
+    let at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    lock(state).last_apply = Some(LastApply {
+        profile,
+        trigger,
+        ok: result.is_ok(),
+        error: result.err(),
+        at,
+    });
+    broadcast(state, &event);
+}
+
+/// Run auto-apply and record it, unless it decided there was nothing to do
+fn run_auto(state: &SharedState, trigger: &'static str) -> Result<AutoOutcome> {
+    // Only refreshes are the daemon's own doing; the rest are client requests
+    let source = if trigger == "refresh" {
+        Trigger::Daemon
+    } else {
+        Trigger::Cli
+    };
+    let result = apply::apply_auto(false, source, true);
+    match &result {
+        Ok(AutoOutcome::Applied { report, .. }) => {
+            record(state, trigger, Some(report.profile.clone()), Ok(()))
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Auto-apply failed: {}", e);
+            record(state, trigger, None, Err(format!("{:#}", e)));
+        }
+    }
+    result
+}
+
+/// Rescan docks and tell subscribers what came and went since last time
+fn check_docks(state: &SharedState) {
+    let Ok(docks) = dock::detect_docks() else {
+        return;
+    };
+    // Only the swap happens under the lock; the allowlist and bolt work
+    // below loads metadata and talks D-Bus, and can block on polkit
+    let before = std::mem::replace(&mut lock(state).docks, docks.clone());
+    let changes = events::dock_changes(&before, &docks);
+    for event in &changes {
+        broadcast(state, event);
+    }
+    if bolt::is_running() {
+        enroll_allowlisted(&before, &docks);
+    }
+    for dock in unknown_thunderbolt_docks(&before, &docks) {
+        std::thread::spawn(move || offer_trust(dock));
+    }
+}
+
+/// With boltd managing authorization, enroll newly connected allowlisted
+/// Thunderbolt docks with it; this is how docks linked while unplugged
+/// get enrolled
+fn enroll_allowlisted(before: &[Dock], after: &[Dock]) {
+    let allowlist = Metadata::load().map(|m| m.allowlist()).unwrap_or_default();
+    let new = after
+        .iter()
+        .filter(|d| d.source == DockSource::Thunderbolt && allowlist.contains(&d.uuid))
+        .filter(|d| !before.iter().any(|b| b.uuid == d.uuid));
+    for dock in new {
+        if let Err(e) = bolt::enroll(&dock.uuid) {
+            eprintln!("{:#}", e);
+        }
+    }
+}
+
+/// Newly connected Thunderbolt docks the udev rules left unauthorized
+/// because they aren't on the allowlist
+fn unknown_thunderbolt_docks(before: &[Dock], after: &[Dock]) -> Vec<Dock> {
+    let allowlist = Metadata::load().map(|m| m.allowlist()).unwrap_or_default();
+    after
+        .iter()
+        .filter(|d| d.source == DockSource::Thunderbolt && d.is_dock())
+        .filter(|d| !before.iter().any(|b| b.uuid == d.uuid))
+        .filter(|d| !allowlist.contains(&d.uuid) && thunderbolt::is_unauthorized(&d.device_id))
+        .cloned()
+        .collect()
+}
+
+/// Ask whether to trust an unknown Thunderbolt dock, and link it to the
+/// active profile if asked to. Either way it lands on the allowlist. With
+/// boltd it's enrolled right away; otherwise it takes a root `hyprpier
+/// setup` to reach the udev rules, and a second notification says so.
+/// Blocks until the notification is answered or dismissed.
+fn offer_trust(dock: Dock) {
+    let active = Metadata::load().ok().and_then(|m| m.active_profile);
+    let vendor = dock.vendor.as_deref().unwrap_or("unknown vendor");
+    let mut notification = Notification::new();
+    notification
+        .summary("Unknown Thunderbolt device")
+        .body(&format!(
+            "{} ({}) was left unauthorized",
+            dock.name, vendor
+        ))
+        .appname("hyprpier")
+        .action("trust", "Trust")
+        .timeout(Timeout::Never);
+    let link_label = active.as_ref().map(|p| format!("Trust and link to {}", p));
+    if let Some(label) = &link_label {
+        notification.action("link", label);
+    }
+    let Ok(handle) = notification.show() else {
+        return;
+    };
+
+    handle.wait_for_action(|action| {
+        let link = match action {
+            "trust" => None,
+            "link" => active.as_deref(),
+            _ => return,
+        };
+        let bolt_managed = bolt::is_running();
+        let trusted = Metadata::load()
+            .and_then(|mut metadata| {
+                match link {
+                    Some(profile) => metadata.link_dock(&dock.uuid, profile),
+                    None => metadata.trust_device(&dock.uuid),
+                }
+                metadata.save()
+            })
+            .and_then(|()| {
+                if bolt_managed {
+                    bolt::enroll(&dock.uuid)?;
+                }
+                Ok(())
+            });
+        let (summary, body) = match trusted {
+            Ok(()) if bolt_managed => (
+                format!("Trusted {}", dock.name),
+                "Enrolled with boltd".to_string(),
+            ),
+            Ok(()) => (
+                format!("Trusted {}", dock.name),
+                "Run `sudo hyprpier setup` to authorize it now and on every connect".to_string(),
+            ),
+            Err(e) => (
+                format!("Could not trust {}", dock.name),
+                format!("{:#}", e),
+            ),
+        };
+        let _ = Notification::new()
+            .summary(&summary)
+            .body(&body)
+            .appname("hyprpier")
+            .show();
+    });
 }
 
-/// Debounced apply worker. A single dock plug fires several udev events;
-/// absorb them until SETTLE_DELAY_MS of quiet, then apply once. Keeping the
+fn run_job(state: &SharedState, job: Job) {
+    match job {
+        Job::Refresh => {
+            if !lock(state).paused {
+                let _ = run_auto(state, "refresh");
+            }
+        }
+        Job::Auto(reply) => {
+            let _ = reply.send(run_auto(state, "auto"));
+        }
+        Job::Apply {
+            profile,
+            no_runtime,
+            reply,
+        } => {
+            let result =
+                apply::apply_profile(&profile, no_runtime, None, false, Trigger::Cli, true);
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| format!("{:#}", e));
+            record(state, "request", Some(profile), outcome);
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Apply worker. A single dock plug fires several udev events; absorb them
+/// until SETTLE_DELAY_MS of quiet, then apply once. Client requests that
+/// arrive meanwhile run after that apply, so nothing races it. Keeping the
 /// sleep here (not in the accept loop) lets notify clients return instantly
 /// instead of blocking udev RUN+= processes for seconds.
-fn refresh_worker(rx: mpsc::Receiver<()>) {
-    while rx.recv().is_ok() {
-        while rx.recv_timeout(Duration::from_millis(SETTLE_DELAY_MS)).is_ok() {}
-        if let Err(e) = apply::apply_auto() {
-            eprintln!("Auto-apply failed: {}", e);
+fn apply_worker(rx: mpsc::Receiver<Job>, state: SharedState) {
+    while let Ok(job) = rx.recv() {
+        if !matches!(job, Job::Refresh) {
+            run_job(&state, job);
+            continue;
+        }
+
+        lock(&state).refresh_pending = true;
+        broadcast(
+            &state,
+            &Event::DebounceStarted {
+                delay_ms: SETTLE_DELAY_MS,
+            },
+        );
+        let mut deferred = Vec::new();
+        loop {
+            match rx.recv_timeout(Duration::from_millis(SETTLE_DELAY_MS)) {
+                Ok(Job::Refresh) => {}
+                Ok(other) => deferred.push(other),
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        lock(&state).refresh_pending = false;
+
+        // Docks are scanned once things settle, when a freshly plugged one
+        // has been authorized
+        check_docks(&state);
+        run_job(&state, Job::Refresh);
+        for job in deferred {
+            run_job(&state, job);
+        }
+    }
+}
+
+/// Follow Hyprland's event socket and queue a refresh on every monitor
+/// hotplug. This catches displays udev never tells us about (plain HDMI,
+/// USB-C alt mode, non-Thunderbolt docks) and needs no root rules. The
+/// events land in the same debounced worker as udev notifications, so a
+/// dock that fires both only applies once.
+///
+/// Reconnects when Hyprland isn't up yet or restarts; the instance
+/// signature (and so the socket path) is re-resolved on every attempt.
+fn hyprland_event_listener(tx: mpsc::Sender<Job>) {
+    loop {
+        if let Some(path) = hyprland::event_socket_path() {
+            if let Ok(stream) = UnixStream::connect(&path) {
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else { break };
+                    if hyprland::is_monitor_event(&line) && tx.send(Job::Refresh).is_err() {
+                        // Apply worker is gone; nothing left to feed
+                        return;
+                    }
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(EVENT_RECONNECT_DELAY_MS));
+    }
+}
+
+/// Same as `hyprland_event_listener`, for sway's output events
+fn sway_event_listener(tx: mpsc::Sender<Job>) {
+    loop {
+        if let Ok(mut stream) = sway::subscribe_outputs() {
+            while sway::next_event(&mut stream).is_ok() {
+                if tx.send(Job::Refresh).is_err() {
+                    return;
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(EVENT_RECONNECT_DELAY_MS));
+    }
+}
+
+/// Poll the ACPI lid state and tell subscribers when it flips. There's no
+/// event to wait on without root, and the file is cheap to read.
+fn lid_watcher(state: SharedState) {
+    let mut closed = events::lid_closed();
+    if closed.is_none() {
+        // No lid on this machine
+        return;
+    }
+    loop {
+        std::thread::sleep(Duration::from_millis(LID_POLL_MS));
+        let now = events::lid_closed();
+        if now != closed {
+            if let Some(closed) = now {
+                broadcast(&state, &Event::LidChanged { closed });
+            }
+            closed = now;
         }
     }
 }
@@ -94,13 +493,27 @@ impl Daemon {
 
         println!("Hyprpier daemon listening on {}", socket_path.display());
 
-        let (refresh_tx, refresh_rx) = mpsc::channel();
-        std::thread::spawn(move || refresh_worker(refresh_rx));
+        let state = SharedState::default();
+        lock(&state).docks = dock::detect_docks().unwrap_or_default();
+        let (jobs, jobs_rx) = mpsc::channel();
+        let worker_state = state.clone();
+        std::thread::spawn(move || apply_worker(jobs_rx, worker_state));
+
+        let event_tx = jobs.clone();
+        if sway::detected() {
+            std::thread::spawn(move || sway_event_listener(event_tx));
+        } else {
+            std::thread::spawn(move || hyprland_event_listener(event_tx));
+        }
+
+        let lid_state = state.clone();
+        std::thread::spawn(move || lid_watcher(lid_state));
 
         Ok(Self {
             socket_path,
             listener,
-            refresh_tx,
+            jobs,
+            state,
         })
     }
 
@@ -108,14 +521,23 @@ impl Daemon {
     pub fn run(&mut self) -> Result<()> {
         // Apply correct profile on startup (handles boot-with-dock-connected
         // case). Routed through the worker so all applies are serialized.
-        let _ = self.refresh_tx.send(());
+        let _ = self.jobs.send(Job::Refresh);
 
         loop {
             match self.listener.accept() {
                 Ok((stream, _)) => {
-                    if let Err(e) = self.handle_client(stream) {
-                        eprintln!("Error handling client: {}", e);
-                    }
+                    // One thread per client: an apply request blocks its
+                    // client until the worker gets to it, and subscribers
+                    // stay connected indefinitely
+                    let client = Client {
+                        jobs: self.jobs.clone(),
+                        state: self.state.clone(),
+                    };
+                    std::thread::spawn(move || {
+                        if let Err(e) = client.handle(stream) {
+                            eprintln!("Error handling client: {}", e);
+                        }
+                    });
                 }
                 Err(e) => {
                     eprintln!("Accept error: {}", e);
@@ -123,48 +545,136 @@ impl Daemon {
             }
         }
     }
+}
+
+/// One connection to the daemon socket
+struct Client {
+    jobs: mpsc::Sender<Job>,
+    state: SharedState,
+}
 
-    /// Handle a single client connection
-    fn handle_client(&mut self, mut stream: UnixStream) -> Result<()> {
-        let mut buf = [0u8; 256];
+impl Client {
+    fn handle(&self, mut stream: UnixStream) -> Result<()> {
+        let mut buf = [0u8; 4096];
         let n = stream.read(&mut buf)?;
 
         if n == 0 {
             return Ok(());
         }
 
-        let cmd = String::from_utf8_lossy(&buf[..n]);
-        let response = self.process_command(cmd.trim());
+        // Plain-text commands come as a single unterminated write; the
+        // client waits for the reply without sending a newline
+        if !buf[..n].trim_ascii_start().starts_with(b"{") {
+            let cmd = String::from_utf8_lossy(&buf[..n]);
+            let response = self.process_legacy_command(cmd.trim());
+            stream.write_all(response.as_bytes())?;
+            return Ok(());
+        }
 
-        stream.write_all(response.as_bytes())?;
+        let reader = BufReader::new(Cursor::new(buf[..n].to_vec()).chain(stream.try_clone()?));
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match protocol::parse_request(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    stream.write_all(protocol::error_reply(&format!("{:#}", e)).as_bytes())?;
+                    continue;
+                }
+            };
+            if request == Request::Subscribe {
+                stream.write_all(protocol::ok_reply(()).as_bytes())?;
+                stream.set_write_timeout(Some(Duration::from_millis(SUBSCRIBER_WRITE_TIMEOUT_MS)))?;
+                lock(&self.state).subscribers.push(stream);
+                return Ok(());
+            }
+            let reply = match self.process_request(request) {
+                Ok(data) => protocol::ok_reply(data),
+                Err(e) => protocol::error_reply(&format!("{:#}", e)),
+            };
+            stream.write_all(reply.as_bytes())?;
+        }
         Ok(())
     }
 
-    /// Process a command and return a response
-    fn process_command(&mut self, cmd: &str) -> String {
-        match cmd {
-            "refresh" => self.handle_refresh(),
-            "status" => self.handle_status(),
-            _ => format!("ERROR: Unknown command: {}\n", cmd),
-        }
+    fn process_request(&self, request: Request) -> Result<serde_json::Value> {
+        let value = match request {
+            Request::Apply {
+                profile,
+                no_runtime,
+            } => {
+                let (reply, rx) = mpsc::channel();
+                self.submit(Job::Apply {
+                    profile,
+                    no_runtime,
+                    reply,
+                })?;
+                serde_json::to_value(rx.recv().context("Apply worker is gone")??)?
+            }
+            Request::ApplyAuto => {
+                let (reply, rx) = mpsc::channel();
+                self.submit(Job::Auto(reply))?;
+                serde_json::to_value(rx.recv().context("Apply worker is gone")??)?
+            }
+            Request::Refresh => {
+                self.submit(Job::Refresh)?;
+                serde_json::Value::Null
+            }
+            Request::Status => {
+                let current = apply::current_status()?;
+                let state = lock(&self.state);
+                serde_json::to_value(DaemonStatus {
+                    version: env!("CARGO_PKG_VERSION"),
+                    current,
+                    paused: state.paused,
+                    refresh_pending: state.refresh_pending,
+                    last_apply: state.last_apply.clone(),
+                })?
+            }
+            Request::List => serde_json::to_value(apply::profile_listing()?)?,
+            Request::ReloadMetadata => {
+                // Metadata is read fresh for every apply; what a reload can
+                // change is which profile auto-apply should pick
+                Metadata::load()?;
+                self.submit(Job::Refresh)?;
+                serde_json::Value::Null
+            }
+            Request::Pause | Request::Resume => {
+                let paused = request == Request::Pause;
+                lock(&self.state).paused = paused;
+                broadcast(&self.state, if paused { &Event::Paused } else { &Event::Resumed });
+                if !paused {
+                    // Catch up on whatever was plugged in meanwhile
+                    self.submit(Job::Refresh)?;
+                }
+                serde_json::json!({ "paused": paused })
+            }
+            Request::Subscribe => unreachable!("handled by the connection loop"),
+        };
+        Ok(value)
     }
 
-    /// Handle refresh command - queue a debounced apply and return at once
-    fn handle_refresh(&mut self) -> String {
-        match self.refresh_tx.send(()) {
-            Ok(()) => "OK\n".to_string(),
-            Err(_) => "ERROR: refresh worker is gone\n".to_string(),
-        }
+    fn submit(&self, job: Job) -> Result<()> {
+        self.jobs.send(job).ok().context("Apply worker is gone")
     }
 
-    /// Handle status command - return current profile
-    fn handle_status(&self) -> String {
-        match Metadata::load() {
-            Ok(metadata) => {
-                let profile = metadata.active_profile.as_deref().unwrap_or("none");
-                format!("OK: {}\n", profile)
-            }
-            Err(e) => format!("ERROR: {}\n", e),
+    /// Plain-text commands from udev rules and older clients
+    fn process_legacy_command(&self, cmd: &str) -> String {
+        match cmd {
+            "refresh" => match self.submit(Job::Refresh) {
+                Ok(()) => "OK\n".to_string(),
+                Err(_) => "ERROR: refresh worker is gone\n".to_string(),
+            },
+            "status" => match Metadata::load() {
+                Ok(metadata) => {
+                    let profile = metadata.active_profile.as_deref().unwrap_or("none");
+                    format!("OK: {}\n", profile)
+                }
+                Err(e) => format!("ERROR: {}\n", e),
+            },
+            _ => format!("ERROR: Unknown command: {}\n", cmd),
         }
     }
 }
@@ -195,3 +705,51 @@ pub fn notify(cmd: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// Send one protocol request to the daemon and return the reply's data, or
+/// None when no daemon is running so the caller can do the work itself
+pub fn request(request: &Request) -> Result<Option<serde_json::Value>> {
+    let Ok(socket_path) = get_socket_path() else {
+        return Ok(None);
+    };
+    let Ok(mut stream) = UnixStream::connect(&socket_path) else {
+        return Ok(None);
+    };
+
+    stream.write_all(protocol::encode_request(request).as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut reply)
+        .context("Failed to read daemon reply")?;
+    if reply.is_empty() {
+        anyhow::bail!("Daemon closed the connection without replying");
+    }
+    protocol::parse_reply(&reply).map(Some)
+}
+
+/// Subscribe to the daemon's events and print them until it goes away
+pub fn watch(json: bool) -> Result<()> {
+    let socket_path = get_socket_path()?;
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| "Failed to connect to daemon - is it running?")?;
+    stream.write_all(protocol::encode_request(&Request::Subscribe).as_bytes())?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let reply = lines.next().context("Daemon closed the connection")??;
+    protocol::parse_reply(&reply)?;
+
+    for line in lines {
+        let line = line?;
+        if json {
+            // Passed through as-is so status bars see exactly what the
+            // daemon sent
+            println!("{}", line);
+        } else {
+            match protocol::parse_event(&line) {
+                Ok(event) => println!("{}", event.describe()),
+                Err(e) => eprintln!("Warning: {:#}", e),
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,35 +1,83 @@
-//! Thunderbolt dock detection and management
+//! Dock detection and management
 //!
-//! This module provides high-level dock operations built on top of the
-//! thunderbolt sysfs module.
+//! A dock is anything whose presence picks a profile: a Thunderbolt device
+//! (by UUID), a USB hub or DisplayLink adapter (by vendor:product and
+//! serial, or the port it's plugged into when it has none), or a display on a DRM connector (by EDID), which is all a
+//! USB-C DP alt mode dock exposes. Every source yields a `Dock` with a
+//! stable `uuid` that metadata links to profiles; non-Thunderbolt ids carry
+//! a `usb:`, `displaylink:` or `drm:` prefix so they can't collide.
 
 use anyhow::{Context, Result};
+use serde::Serialize;
 use std::fs;
-use std::path::Path;
+use std::path::PathBuf;
 
-use crate::thunderbolt;
+use crate::sysfs;
+use crate::thunderbolt::{self, ThunderboltInfo};
 
-const THUNDERBOLT_PATH: &str = "/sys/bus/thunderbolt/devices";
+const USB_HUB_CLASS: &str = "09";
+const DISPLAYLINK_VENDOR: &str = "17e9";
+/// Built-in panels are never a dock
+const INTERNAL_CONNECTORS: [&str; 3] = ["eDP", "LVDS", "DSI"];
 
-#[derive(Debug, Clone)]
-pub struct ThunderboltDevice {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DockSource {
+    Thunderbolt,
+    /// A DisplayLink adapter (driven by evdi), found on the USB bus
+    DisplayLink,
+    Usb,
+    Drm,
+}
+
+impl std::fmt::Display for DockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DockSource::Thunderbolt => "thunderbolt",
+            DockSource::DisplayLink => "displaylink",
+            DockSource::Usb => "usb",
+            DockSource::Drm => "drm",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Dock {
     pub name: String,
+    /// Stable identifier that profiles are linked by: the Thunderbolt UUID,
+    /// or a prefixed id for the other sources
     pub uuid: String,
     pub vendor: Option<String>,
     pub is_host: bool,
-    pub device_id: String, // e.g., "0-0", "0-1"
+    pub device_id: String, // e.g., "0-0", "0-1", "3-2", "card1-DP-3"
+    pub source: DockSource,
+    /// Topology and link details, for Thunderbolt devices
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thunderbolt: Option<ThunderboltInfo>,
 }
 
-impl ThunderboltDevice {
+impl Dock {
     /// Check if this is a dock/peripheral (not the host controller)
     pub fn is_dock(&self) -> bool {
         !self.is_host
     }
 }
 
-/// Detect all Thunderbolt devices from sysfs
-pub fn list_all_devices() -> Result<Vec<ThunderboltDevice>> {
-    let tb_path = Path::new(THUNDERBOLT_PATH);
+/// Every device from every source: Thunderbolt (hosts included) in
+/// topology order, then DisplayLink, USB and DRM, each in device_id order
+pub fn list_all_devices() -> Result<Vec<Dock>> {
+    let mut devices = thunderbolt_devices()?;
+    let mut usb = usb_devices();
+    usb.sort_by_key(|d| d.source == DockSource::Usb);
+    devices.extend(usb);
+    devices.extend(drm_connectors());
+    Ok(devices)
+}
+
+/// Thunderbolt devices (hosts included) from /sys/bus/thunderbolt
+fn thunderbolt_devices() -> Result<Vec<Dock>> {
+    let tb_path = sysfs::thunderbolt_devices();
 
     if !tb_path.exists() {
         return Ok(Vec::new());
@@ -37,7 +85,7 @@ pub fn list_all_devices() -> Result<Vec<ThunderboltDevice>> {
 
     let mut devices = Vec::new();
 
-    let entries = fs::read_dir(tb_path)
+    let entries = fs::read_dir(&tb_path)
         .context("Failed to read Thunderbolt sysfs")?;
 
     for entry in entries.flatten() {
@@ -62,23 +110,186 @@ pub fn list_all_devices() -> Result<Vec<ThunderboltDevice>> {
         // Host controller is typically "X-0" (e.g., "0-0", "1-0")
         let is_host = name_str.ends_with("-0");
 
-        devices.push(ThunderboltDevice {
+        devices.push(Dock {
             name: device_name,
             uuid,
             vendor,
             is_host,
             device_id: name_str.to_string(),
+            source: DockSource::Thunderbolt,
+            thunderbolt: Some(thunderbolt::device_info(&device_path, &name_str)),
         });
     }
 
-    // Sort by device_id for consistent ordering
-    devices.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+    // Topology order: each device right after the one it's plugged into
+    devices.sort_by_cached_key(|d| thunderbolt::topology_key(&d.device_id));
 
     Ok(devices)
 }
 
-/// Detect connected Thunderbolt docks (peripherals only, not host)
-pub fn detect_docks() -> Result<Vec<ThunderboltDevice>> {
+/// USB hubs the kernel marks removable (the hub in a USB-C dock) and
+/// DisplayLink adapters. Hubs behind another listed hub are part of the
+/// same dock and skipped, and so is the USB 2.0 companion of a USB 3 hub,
+/// which shows up on the other bus behind the peer of the hub's port.
+/// Root hubs ("usbN") and built-in hubs are not docks.
+fn usb_devices() -> Vec<Dock> {
+    let Ok(entries) = fs::read_dir(sysfs::usb_devices()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        // Interfaces ("1-2:1.0") and root hubs aren't devices of interest
+        .filter(|n| !n.contains(':') && !n.starts_with("usb"))
+        .collect();
+    names.sort();
+
+    let mut hubs: Vec<String> = Vec::new();
+    // Ports the listed hubs are plugged into
+    let mut hub_ports: Vec<PathBuf> = Vec::new();
+    let mut devices = Vec::new();
+    for name in names {
+        let path = sysfs::usb_devices().join(&name);
+        let attr = |a: &str| thunderbolt::read_attr(&path, a);
+        let (Some(vendor_id), Some(product_id)) = (attr("idVendor"), attr("idProduct")) else {
+            continue;
+        };
+
+        let source = if vendor_id == DISPLAYLINK_VENDOR {
+            DockSource::DisplayLink
+        } else if attr("bDeviceClass").as_deref() == Some(USB_HUB_CLASS)
+            && attr("removable").as_deref() == Some("removable")
+        {
+            // Hubs inside the dock report "unknown", so look past them
+            let nested = hubs.iter().any(|h| name.starts_with(&format!("{}.", h)));
+            let companion = fs::canonicalize(path.join("port/peer"))
+                .is_ok_and(|peer| hub_ports.contains(&peer));
+            hubs.push(name.clone());
+            if nested || companion {
+                continue;
+            }
+            if let Ok(port) = fs::canonicalize(path.join("port")) {
+                hub_ports.push(port);
+            }
+            DockSource::Usb
+        } else {
+            continue;
+        };
+
+        // Without a serial, two of the same model are told apart by the
+        // port path ("3-2"), so each stays linked to its own profile
+        let mut uuid = format!("{}:{}:{}", source, vendor_id, product_id);
+        match attr("serial").filter(|s| !s.is_empty()) {
+            Some(serial) => {
+                uuid.push(':');
+                uuid.push_str(&serial);
+            }
+            None => {
+                uuid.push('@');
+                uuid.push_str(&name);
+            }
+        }
+        devices.push(Dock {
+            name: attr("product").unwrap_or_else(|| format!("USB device {}:{}", vendor_id, product_id)),
+            uuid,
+            vendor: attr("manufacturer"),
+            is_host: false,
+            device_id: name,
+            source,
+            thunderbolt: None,
+        });
+    }
+    devices
+}
+
+/// Displays connected to external DRM connectors, identified by EDID
+fn drm_connectors() -> Vec<Dock> {
+    let Ok(entries) = fs::read_dir(sysfs::drm_class()) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<Dock> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // "card1-DP-3" -> connector "DP-3"
+            let (_, connector) = name.split_once('-')?;
+            if INTERNAL_CONNECTORS
+                .iter()
+                .any(|i| connector.starts_with(&format!("{}-", i)))
+            {
+                return None;
+            }
+            if thunderbolt::read_attr(&entry.path(), "status").as_deref() != Some("connected") {
+                return None;
+            }
+            let edid = fs::read(entry.path().join("edid")).ok()?;
+            let info = parse_edid(&edid)?;
+            Some(Dock {
+                name: info.name.unwrap_or_else(|| format!("Display on {}", connector)),
+                uuid: format!("drm:{}:{:04x}:{}", info.manufacturer, info.product, info.serial),
+                vendor: Some(info.manufacturer),
+                is_host: false,
+                device_id: name,
+                source: DockSource::Drm,
+                thunderbolt: None,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+    devices
+}
+
+/// The identifying parts of an EDID base block
+#[derive(Debug, PartialEq)]
+struct EdidInfo {
+    /// Three-letter PNP id, e.g. "DEL"
+    manufacturer: String,
+    product: u16,
+    /// The serial string descriptor if present, else the numeric serial
+    serial: String,
+    /// The monitor name descriptor
+    name: Option<String>,
+}
+
+fn parse_edid(edid: &[u8]) -> Option<EdidInfo> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER {
+        return None;
+    }
+    let id = u16::from_be_bytes([edid[8], edid[9]]);
+    let manufacturer: String = [10, 5, 0]
+        .iter()
+        .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char)
+        .collect();
+    let product = u16::from_le_bytes([edid[10], edid[11]]);
+    let serial_number = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+
+    // Four 18-byte descriptors; display descriptors start with 0 0 0 <tag>
+    let descriptor = |tag: u8| {
+        (0..4).find_map(|i| {
+            let d = &edid[54 + i * 18..72 + i * 18];
+            if d[..3] != [0, 0, 0] || d[3] != tag {
+                return None;
+            }
+            let text: String = d[5..]
+                .iter()
+                .take_while(|&&b| b != b'\n')
+                .map(|&b| b as char)
+                .collect();
+            Some(text.trim().to_string()).filter(|t| !t.is_empty())
+        })
+    };
+
+    Some(EdidInfo {
+        manufacturer,
+        product,
+        serial: descriptor(0xff).unwrap_or_else(|| serial_number.to_string()),
+        name: descriptor(0xfc),
+    })
+}
+
+/// Detect connected docks (peripherals only, not host)
+pub fn detect_docks() -> Result<Vec<Dock>> {
     let devices = list_all_devices()?;
     Ok(devices.into_iter().filter(|d| d.is_dock()).collect())
 }
@@ -87,3 +298,89 @@ pub fn detect_docks() -> Result<Vec<ThunderboltDevice>> {
 pub fn get_security_mode() -> Result<String> {
     thunderbolt::get_security_mode()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs;
+
+    fn ids(devices: &[Dock]) -> Vec<&str> {
+        devices.iter().map(|d| d.device_id.as_str()).collect()
+    }
+
+    #[test]
+    fn host_only_has_no_docks() {
+        sysfs::set_test_root(sysfs::fixture("laptop-usb4"));
+        let devices = list_all_devices().unwrap();
+        // Protocol interfaces like 0-0:1.1 and domains aren't devices
+        assert_eq!(ids(&devices), ["0-0"]);
+        assert!(devices[0].is_host);
+        assert_eq!(devices[0].vendor.as_deref(), Some("Intel"));
+        assert!(detect_docks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn detects_dock_by_uuid() {
+        sysfs::set_test_root(sysfs::fixture("dock"));
+        let docks = detect_docks().unwrap();
+        assert_eq!(ids(&docks), ["0-1"]);
+        assert_eq!(docks[0].name, "TS4");
+        assert_eq!(docks[0].uuid, "f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b");
+    }
+
+    #[test]
+    fn daisy_chain_across_domains() {
+        sysfs::set_test_root(sysfs::fixture("daisy-chain"));
+        let devices = list_all_devices().unwrap();
+        assert_eq!(ids(&devices), ["0-0", "0-1", "0-301", "1-0"]);
+        assert_eq!(ids(&detect_docks().unwrap()), ["0-1", "0-301"]);
+    }
+
+    #[test]
+    fn usb_c_dock_sources() {
+        sysfs::set_test_root(sysfs::fixture("usb-c-dock"));
+        let docks = detect_docks().unwrap();
+        let found: Vec<(DockSource, &str, &str)> = docks
+            .iter()
+            .map(|d| (d.source, d.device_id.as_str(), d.uuid.as_str()))
+            .collect();
+        // Not listed: the root hubs, the hub inside the dock's hub, its
+        // USB 2.0 companion (4-2), the keyboard, the built-in hub, the
+        // laptop panel and the empty HDMI port
+        assert_eq!(
+            found,
+            [
+                (DockSource::DisplayLink, "3-2.4", "displaylink:17e9:6006:1810150012"),
+                (DockSource::Usb, "3-2", "usb:2109:0817@3-2"),
+                (DockSource::Drm, "card1-DP-3", "drm:DEL:a0b1:8KXYZ12"),
+            ]
+        );
+        assert_eq!(docks[2].name, "DELL U2720Q");
+    }
+
+    #[test]
+    fn edid_without_descriptors_uses_numeric_serial() {
+        let mut edid = vec![0u8; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        edid[8..10].copy_from_slice(&[0x1e, 0x6d]); // "GSM"
+        edid[10..12].copy_from_slice(&[0x01, 0x77]);
+        edid[12..16].copy_from_slice(&7u32.to_le_bytes());
+        let info = parse_edid(&edid).unwrap();
+        assert_eq!(
+            info,
+            EdidInfo {
+                manufacturer: "GSM".to_string(),
+                product: 0x7701,
+                serial: "7".to_string(),
+                name: None,
+            }
+        );
+        assert!(parse_edid(&edid[..100]).is_none());
+    }
+
+    #[test]
+    fn missing_thunderbolt_bus_is_empty() {
+        sysfs::set_test_root(sysfs::fixture("does-not-exist"));
+        assert!(list_all_devices().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,465 @@
+//! `hyprpier doctor`: environment checks with suggested fixes
+//!
+//! Each check looks at one thing auto-switching depends on (the compositor,
+//! the config include, the daemon, udev, the resume service, Thunderbolt
+//! security, metadata) and reports pass, warn or fail. Warnings are setups
+//! that work but miss something; failures mean profiles won't apply or
+//! switch as expected.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+
+use crate::compositor::{self, Dialect};
+use crate::daemon;
+use crate::hyprland::{self, ConfigFormat};
+use crate::metadata::Metadata;
+use crate::profile::{self, Profile};
+use crate::setup::{self, DaemonService};
+use crate::sway;
+use crate::thunderbolt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to run or change to fix a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Run every check and print the results. Returns false if any failed, so
+/// the command can exit non-zero.
+pub fn run(json: bool) -> Result<bool> {
+    let metadata = Metadata::load()?;
+    let names = profile::list_profiles()?;
+    let profiles: Vec<Profile> = names
+        .iter()
+        .filter_map(|name| Profile::load(name).ok())
+        .collect();
+    let dialect = if sway::detected() {
+        Dialect::Sway
+    } else {
+        Dialect::Hyprland(ConfigFormat::current())
+    };
+
+    let checks = vec![
+        check_compositor(dialect),
+        check_config_include(dialect),
+        check_daemon(),
+        check_udev_rules(),
+        check_resume_service(),
+        check_security_mode(),
+        check_metadata_references(&metadata, &names),
+        check_profile_monitors(&metadata, &profiles),
+    ];
+    let ok = checks.iter().all(|c| c.status != Status::Fail);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "ok": ok, "checks": checks }))?
+        );
+        return Ok(ok);
+    }
+
+    for check in &checks {
+        let label = match check.status {
+            Status::Pass => " ok ",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", label, check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            println!("       fix: {}", fix);
+        }
+    }
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    println!();
+    println!(
+        "{} passed, {} warnings, {} failed",
+        count(Status::Pass),
+        count(Status::Warn),
+        count(Status::Fail)
+    );
+
+    Ok(ok)
+}
+
+fn check_compositor(dialect: Dialect) -> Check {
+    const NAME: &str = "Compositor";
+    if dialect == Dialect::Sway {
+        return if compositor::detect().is_running() {
+            Check::pass(NAME, "sway")
+        } else {
+            Check::fail(
+                NAME,
+                "$SWAYSOCK is set but sway isn't answering",
+                "Run hyprpier from inside the sway session",
+            )
+        };
+    }
+    match hyprland::running_version() {
+        Some(version) => Check::pass(NAME, format!("Hyprland {}", version)),
+        None if hyprland::is_running() => Check::warn(
+            NAME,
+            "Hyprland is running but `hyprctl version` didn't answer",
+            "Run hyprpier from inside the Hyprland session ($HYPRLAND_INSTANCE_SIGNATURE set)",
+        ),
+        None => Check::fail(
+            NAME,
+            "Hyprland isn't running; profiles can only be written, not applied",
+            "Run hyprpier from inside a Hyprland or sway session",
+        ),
+    }
+}
+
+/// Whether a main compositor config pulls in the generated monitors file.
+/// Commented-out lines don't count.
+fn includes_monitors(dialect: Dialect, config: &str) -> bool {
+    let comment = match dialect {
+        Dialect::Hyprland(ConfigFormat::Lua) => "--",
+        Dialect::Hyprland(ConfigFormat::Legacy) | Dialect::Sway => "#",
+    };
+    config.lines().any(|line| {
+        let line = line.split(comment).next().unwrap_or_default().trim();
+        match dialect {
+            Dialect::Hyprland(ConfigFormat::Lua) => {
+                line.contains("require")
+                    && (line.contains("\"monitors\"") || line.contains("'monitors'"))
+            }
+            Dialect::Hyprland(ConfigFormat::Legacy) => {
+                line.starts_with("source") && line.contains("monitors.conf")
+            }
+            Dialect::Sway => line.starts_with("include") && line.contains("monitors.conf"),
+        }
+    })
+}
+
+fn check_config_include(dialect: Dialect) -> Check {
+    const NAME: &str = "Config include";
+    let (main_file, line) = match dialect {
+        Dialect::Hyprland(ConfigFormat::Lua) => ("hyprland.lua", "pcall(require, \"monitors\")"),
+        Dialect::Hyprland(ConfigFormat::Legacy) => {
+            ("hyprland.conf", "source = ~/.config/hypr/monitors.conf")
+        }
+        Dialect::Sway => ("config", "include ~/.config/sway/monitors.conf"),
+    };
+    let main_path = match dialect.config_path() {
+        Ok(path) => path.with_file_name(main_file),
+        Err(e) => return Check::fail(NAME, format!("{:#}", e), "Set $HOME"),
+    };
+    match fs::read_to_string(&main_path) {
+        Ok(text) if includes_monitors(dialect, &text) => Check::pass(
+            NAME,
+            format!("{} loads the generated config", main_path.display()),
+        ),
+        Ok(_) => Check::fail(
+            NAME,
+            format!("{} doesn't load the generated config", main_path.display()),
+            format!("Add `{}` to {}", line, main_path.display()),
+        ),
+        Err(_) => Check::fail(
+            NAME,
+            format!("{} not found", main_path.display()),
+            format!("Create it with `{}`", line),
+        ),
+    }
+}
+
+fn check_daemon() -> Check {
+    const NAME: &str = "Daemon";
+    let health = daemon::health();
+    if health.service != DaemonService::NotInstalled && !setup::graphical_session_active() {
+        return Check::warn(
+            NAME,
+            "the user service starts with graphical-session.target, which isn't active",
+            "Start the compositor through uwsm, or use `exec-once = hyprpier daemon` \
+             and `hyprpier setup --daemon --uninstall`",
+        );
+    }
+    if !health.socket_reachable {
+        let fix = match health.service {
+            DaemonService::NotInstalled => {
+                "Run `hyprpier setup --daemon` to start it with your session"
+            }
+            DaemonService::Outdated => {
+                "Run `hyprpier setup --daemon` to point the service at this binary"
+            }
+            DaemonService::Installed => {
+                "Check `journalctl --user -u hyprpier` for why it isn't running"
+            }
+        };
+        return Check::warn(NAME, "socket not reachable; docks won't switch profiles", fix);
+    }
+    let version = health.version.as_deref().unwrap_or("an older version");
+    if !health.version_matches {
+        return Check::warn(
+            NAME,
+            format!(
+                "running {}, but this binary is {}",
+                version,
+                env!("CARGO_PKG_VERSION")
+            ),
+            "Restart the daemon (`systemctl --user restart hyprpier`) to pick up this binary",
+        );
+    }
+    if health.service == DaemonService::Outdated {
+        return Check::warn(
+            NAME,
+            format!("running {}, but the user service points at another binary", version),
+            "Run `hyprpier setup --daemon`",
+        );
+    }
+    Check::pass(NAME, format!("running {}", version))
+}
+
+fn check_udev_rules() -> Check {
+    const NAME: &str = "udev rules";
+    if !setup::is_installed() {
+        return Check::warn(
+            NAME,
+            "not installed; connecting a dock won't trigger auto-switching",
+            "Run `sudo hyprpier setup`",
+        );
+    }
+    let exe = std::env::current_exe().map(|p| p.to_string_lossy().to_string());
+    match (setup::rules_exe(), exe) {
+        (Some(rules_exe), Ok(exe)) if rules_exe != exe => Check::fail(
+            NAME,
+            format!("installed rules run {}, not {}", rules_exe, exe),
+            "Run `sudo hyprpier setup` to point them at this binary",
+        ),
+        (None, _) => Check::fail(
+            NAME,
+            "installed rules don't run hyprpier",
+            "Run `sudo hyprpier setup`",
+        ),
+        _ if setup::rules_outdated() => Check::warn(
+            NAME,
+            "installed, but the allowlist changed since",
+            "Run `sudo hyprpier setup` to authorize the current allowlist",
+        ),
+        _ => Check::pass(NAME, format!("installed (authorization: {})", setup::authorizer())),
+    }
+}
+
+fn check_resume_service() -> Check {
+    const NAME: &str = "Resume service";
+    if setup::is_resume_service_installed() {
+        Check::pass(NAME, "installed")
+    } else if thunderbolt::get_controller_pci_addresses().is_empty() {
+        Check::pass(NAME, "not needed (no Thunderbolt/USB4 controller)")
+    } else {
+        Check::warn(
+            NAME,
+            "not installed; docks may stay dead after suspend",
+            "Run `sudo hyprpier setup --resume`",
+        )
+    }
+}
+
+fn check_security_mode() -> Check {
+    const NAME: &str = "Thunderbolt security";
+    let mode = thunderbolt::get_security_mode().unwrap_or_else(|_| "unknown".to_string());
+    match mode.as_str() {
+        "none" => Check::warn(
+            NAME,
+            "none: every device is authorized on connect, so the allowlist does nothing",
+            "Set Thunderbolt security to \"user\" in the firmware setup",
+        ),
+        "dponly" => Check::warn(
+            NAME,
+            "dponly: only DisplayPort is tunneled, so dock USB and Ethernet won't work",
+            "Set Thunderbolt security to \"user\" in the firmware setup",
+        ),
+        "unknown" => Check::pass(NAME, "no Thunderbolt domain"),
+        _ => Check::pass(NAME, mode),
+    }
+}
+
+/// Metadata entries naming a profile that doesn't exist
+fn dangling_references(metadata: &Metadata, profiles: &[String]) -> Vec<String> {
+    let missing = |name: &str| !profiles.iter().any(|p| p == name);
+    let mut dangling = Vec::new();
+    for (what, profile) in [
+        ("active profile", &metadata.active_profile),
+        ("undocked profile", &metadata.undocked_profile),
+        ("reverted profile", &metadata.reverted_profile),
+    ] {
+        if let Some(profile) = profile.as_deref().filter(|p| missing(p)) {
+            dangling.push(format!("{} '{}'", what, profile));
+        }
+    }
+    let mut links: Vec<_> = metadata.dock_profiles.iter().collect();
+    links.sort();
+    for (uuid, profile) in links {
+        if missing(profile) {
+            dangling.push(format!("dock {} -> '{}'", uuid, profile));
+        }
+    }
+    for combo in &metadata.dock_combos {
+        if missing(&combo.profile) {
+            dangling.push(format!("combo {} -> '{}'", combo.docks.join(" + "), combo.profile));
+        }
+    }
+    dangling
+}
+
+fn check_metadata_references(metadata: &Metadata, names: &[String]) -> Check {
+    const NAME: &str = "Metadata";
+    let dangling = dangling_references(metadata, names);
+    if dangling.is_empty() {
+        Check::pass(NAME, "every reference names an existing profile")
+    } else {
+        Check::warn(
+            NAME,
+            format!("missing profiles: {}", dangling.join(", ")),
+            "Recreate the profiles or relink the docks in `hyprpier mgr`",
+        )
+    }
+}
+
+/// (profile, description) for every profile monitor whose description
+/// isn't among `known`
+fn unmatched_monitors(profiles: &[Profile], known: &HashSet<String>) -> Vec<(String, String)> {
+    profiles
+        .iter()
+        .flat_map(|profile| {
+            profile
+                .monitors
+                .iter()
+                .filter_map(|m| m.description.as_ref())
+                .filter(|description| !known.contains(*description))
+                .map(|description| (profile.name.clone(), description.clone()))
+        })
+        .collect()
+}
+
+fn check_profile_monitors(metadata: &Metadata, profiles: &[Profile]) -> Check {
+    const NAME: &str = "Profile monitors";
+    let mut known: HashSet<String> = metadata.seen_monitors.iter().cloned().collect();
+    if let Ok(connected) = hyprland::connected_monitor_descriptions(compositor::detect().as_ref()) {
+        known.extend(connected);
+    }
+    let unmatched = unmatched_monitors(profiles, &known);
+    if unmatched.is_empty() {
+        return Check::pass(NAME, "every described monitor has been seen connected");
+    }
+    let list = unmatched
+        .iter()
+        .map(|(profile, description)| format!("{}: \"{}\"", profile, description))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Check::warn(
+        NAME,
+        format!("never seen connected: {}", list),
+        "Connect the monitor and apply the profile, or re-save it from the TUI to pick up the current description",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::DockCombo;
+    use crate::profile::Monitor;
+
+    #[test]
+    fn finds_the_monitors_include() {
+        let lua = Dialect::Hyprland(ConfigFormat::Lua);
+        let legacy = Dialect::Hyprland(ConfigFormat::Legacy);
+        assert!(includes_monitors(lua, "pcall(require, \"monitors\")\n"));
+        assert!(includes_monitors(lua, "require('monitors')"));
+        assert!(!includes_monitors(lua, "-- pcall(require, \"monitors\")"));
+        assert!(includes_monitors(legacy, "source = ~/.config/hypr/monitors.conf # outputs"));
+        assert!(!includes_monitors(legacy, "#source = ~/.config/hypr/monitors.conf"));
+        assert!(includes_monitors(Dialect::Sway, "include ~/.config/sway/monitors.conf"));
+        assert!(!includes_monitors(Dialect::Sway, "include ~/.config/sway/keys.conf"));
+    }
+
+    #[test]
+    fn reports_references_to_missing_profiles() {
+        let mut metadata = Metadata {
+            active_profile: Some("desk".to_string()),
+            undocked_profile: Some("gone".to_string()),
+            ..Default::default()
+        };
+        metadata.link_dock("2b7e4f91", "desk");
+        metadata.link_dock("9c0d", "old-office");
+        metadata.dock_combos.push(DockCombo {
+            docks: vec!["2b7e4f91".to_string(), "9c0d".to_string()],
+            profile: "both".to_string(),
+            priority: 0,
+        });
+
+        assert_eq!(
+            dangling_references(&metadata, &["desk".to_string()]),
+            vec![
+                "undocked profile 'gone'",
+                "dock 9c0d -> 'old-office'",
+                "combo 2b7e4f91 + 9c0d -> 'both'",
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_monitors_never_seen() {
+        let mut desk = Profile::new("desk");
+        let mut seen = Monitor::test_fixture("DP-1", "2560x1440", 1.0, 0);
+        seen.description = Some("Dell Inc. DELL U2723QE ABC123".to_string());
+        let mut unseen = Monitor::test_fixture("DP-2", "1920x1080", 1.0, 0);
+        unseen.description = Some("LG Electronics LG HDR 4K 0x0001".to_string());
+        // Monitors without a description can't be matched either way
+        let undescribed = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        desk.monitors = vec![seen, unseen, undescribed];
+
+        let known = HashSet::from(["Dell Inc. DELL U2723QE ABC123".to_string()]);
+        assert_eq!(
+            unmatched_monitors(&[desk], &known),
+            vec![(
+                "desk".to_string(),
+                "LG Electronics LG HDR 4K 0x0001".to_string()
+            )]
+        );
+    }
+}
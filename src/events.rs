@@ -0,0 +1,149 @@
+//! Events the daemon streams to `subscribe` clients (and `hyprpier watch`)
+//!
+//! Each event is one JSON line tagged by `event`, e.g.
+//! `{"v":1,"event":"dock-connected","uuid":"...","name":"..."}`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dock::Dock;
+use crate::sysfs;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    DockConnected { uuid: String, name: String },
+    DockDisconnected { uuid: String, name: String },
+    /// A dock or display event arrived; auto-apply runs after the delay
+    /// unless more events keep coming
+    DebounceStarted { delay_ms: u64 },
+    Applied { profile: String, trigger: String },
+    ApplyFailed {
+        profile: Option<String>,
+        trigger: String,
+        error: String,
+    },
+    LidChanged { closed: bool },
+    Paused,
+    Resumed,
+}
+
+impl Event {
+    /// One line for `hyprpier watch` without `--json`
+    pub fn describe(&self) -> String {
+        match self {
+            Event::DockConnected { uuid, name } => format!("Dock connected: {} ({})", name, uuid),
+            Event::DockDisconnected { uuid, name } => {
+                format!("Dock disconnected: {} ({})", name, uuid)
+            }
+            Event::DebounceStarted { delay_ms } => {
+                format!("Display change detected, applying in {}ms", delay_ms)
+            }
+            Event::Applied { profile, trigger } => {
+                format!("Applied profile: {} ({})", profile, trigger)
+            }
+            Event::ApplyFailed {
+                profile, error, ..
+            } => match profile {
+                Some(p) => format!("Failed to apply profile {}: {}", p, error),
+                None => format!("Auto-apply failed: {}", error),
+            },
+            Event::LidChanged { closed: true } => "Lid closed".to_string(),
+            Event::LidChanged { closed: false } => "Lid opened".to_string(),
+            Event::Paused => "Auto-switching paused".to_string(),
+            Event::Resumed => "Auto-switching resumed".to_string(),
+        }
+    }
+}
+
+/// Connect/disconnect events between two dock scans, by UUID
+pub fn dock_changes(before: &[Dock], after: &[Dock]) -> Vec<Event> {
+    let gone = before
+        .iter()
+        .filter(|d| !after.iter().any(|a| a.uuid == d.uuid))
+        .map(|d| Event::DockDisconnected {
+            uuid: d.uuid.clone(),
+            name: d.name.clone(),
+        });
+    let new = after
+        .iter()
+        .filter(|d| !before.iter().any(|b| b.uuid == d.uuid))
+        .map(|d| Event::DockConnected {
+            uuid: d.uuid.clone(),
+            name: d.name.clone(),
+        });
+    gone.chain(new).collect()
+}
+
+/// Whether the laptop lid is closed, from ACPI; None on machines without
+/// a lid (or without the ACPI button driver)
+pub fn lid_closed() -> Option<bool> {
+    let entries = std::fs::read_dir(sysfs::lid_dir()).ok()?;
+    entries.flatten().find_map(|entry| {
+        let state = std::fs::read_to_string(entry.path().join("state")).ok()?;
+        parse_lid_state(&state)
+    })
+}
+
+/// Parse /proc/acpi/button/lid/*/state ("state:      open")
+fn parse_lid_state(text: &str) -> Option<bool> {
+    match text.trim().strip_prefix("state:")?.trim() {
+        "closed" => Some(true),
+        "open" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dock(uuid: &str, name: &str) -> Dock {
+        Dock {
+            name: name.to_string(),
+            uuid: uuid.to_string(),
+            vendor: None,
+            is_host: false,
+            device_id: "0-1".to_string(),
+            source: crate::dock::DockSource::Thunderbolt,
+            thunderbolt: None,
+        }
+    }
+
+    #[test]
+    fn dock_changes_by_uuid() {
+        let desk = vec![dock("aaaa", "Desk Dock")];
+        let travel = vec![dock("bbbb", "Travel Dock")];
+        assert!(dock_changes(&desk, &desk).is_empty());
+        assert_eq!(
+            dock_changes(&desk, &travel),
+            vec![
+                Event::DockDisconnected {
+                    uuid: "aaaa".to_string(),
+                    name: "Desk Dock".to_string()
+                },
+                Event::DockConnected {
+                    uuid: "bbbb".to_string(),
+                    name: "Travel Dock".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_lid_state() {
+        assert_eq!(parse_lid_state("state:      open\n"), Some(false));
+        assert_eq!(parse_lid_state("state:      closed\n"), Some(true));
+        assert_eq!(parse_lid_state("garbage"), None);
+    }
+
+    #[test]
+    fn lid_state_from_fixtures() {
+        sysfs::set_test_root(sysfs::fixture("laptop-usb4"));
+        assert_eq!(lid_closed(), Some(false));
+        sysfs::set_test_root(sysfs::fixture("dock"));
+        assert_eq!(lid_closed(), Some(true));
+        // A desktop has no lid device at all
+        sysfs::set_test_root(sysfs::fixture("daisy-chain"));
+        assert_eq!(lid_closed(), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! Apply history
+//!
+//! Every apply, from the CLI, the TUI or the daemon, successful or not, is
+//! appended to `.history.json` next to the metadata. `hyprpier history`
+//! prints it, and `apply --previous` uses it to go back to the profile that
+//! was active before the current one.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::metadata::Metadata;
+
+/// Oldest entries are dropped past this many
+const MAX_ENTRIES: usize = 200;
+
+/// What started an apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// `hyprpier apply`, run here or handed to the daemon
+    Cli,
+    Tui,
+    /// The daemon reacting to a dock, monitor or lid event
+    Daemon,
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Trigger::Cli => "cli",
+            Trigger::Tui => "tui",
+            Trigger::Daemon => "daemon",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix timestamp (seconds)
+    pub at: u64,
+    pub profile: String,
+    pub trigger: Trigger,
+    /// UUIDs of the docks connected at the time
+    #[serde(default)]
+    pub docks: Vec<String>,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// The layout wasn't kept within the profile's revert timeout
+    #[serde(default)]
+    pub reverted: bool,
+}
+
+impl Entry {
+    /// An entry stamped with the current time
+    pub fn new(
+        profile: &str,
+        trigger: Trigger,
+        docks: Vec<String>,
+        result: Result<Vec<String>, String>,
+    ) -> Self {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (ok, error, warnings) = match result {
+            Ok(warnings) => (true, None, warnings),
+            Err(e) => (false, Some(e), Vec::new()),
+        };
+        Entry {
+            at,
+            profile: profile.to_string(),
+            trigger,
+            docks,
+            ok,
+            error,
+            warnings,
+            reverted: false,
+        }
+    }
+}
+
+/// The history, oldest first; empty when nothing was applied yet
+pub fn load() -> Result<Vec<Entry>> {
+    let path = config::history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).context("Failed to read apply history")?;
+    serde_json::from_str(&content).context("Failed to parse apply history")
+}
+
+/// Save the history (atomic write via temp file + rename)
+fn save(entries: &[Entry]) -> Result<()> {
+    config::ensure_profile_dir()?;
+    let path = config::history_path()?;
+    let content =
+        serde_json::to_string_pretty(entries).context("Failed to serialize apply history")?;
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, &content).context("Failed to write apply history")?;
+    fs::rename(&temp_path, &path).context("Failed to save apply history")?;
+    Ok(())
+}
+
+/// Append an entry, dropping the oldest past MAX_ENTRIES
+pub fn record(entry: Entry) -> Result<()> {
+    let mut entries = load().unwrap_or_default();
+    entries.push(entry);
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+    save(&entries)
+}
+
+/// Flag the latest successful apply of a profile as reverted
+pub fn mark_reverted(profile: &str) -> Result<()> {
+    let mut entries = load()?;
+    if let Some(entry) = entries
+        .iter_mut()
+        .rev()
+        .find(|e| e.ok && e.profile == profile)
+    {
+        entry.reverted = true;
+        save(&entries)?;
+    }
+    Ok(())
+}
+
+/// The most recent profile that was applied and kept, other than `active`
+fn previous_in<'a>(entries: &'a [Entry], active: Option<&str>) -> Option<&'a str> {
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.ok && !e.reverted)
+        .map(|e| e.profile.as_str())
+        .find(|&p| Some(p) != active && crate::profile::validate_profile_name(p).is_ok())
+}
+
+/// The profile `apply --previous` goes back to
+pub fn previous_profile() -> Result<String> {
+    let active = Metadata::load()?.active_profile;
+    let entries = load()?;
+    match previous_in(&entries, active.as_deref()) {
+        Some(profile) => Ok(profile.to_string()),
+        None => bail!("No earlier profile in the apply history (see `hyprpier history`)"),
+    }
+}
+
+/// "42s ago", "5m ago", "3h ago", "2d ago"
+fn ago(at: u64, now: u64) -> String {
+    let secs = now.saturating_sub(at);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Print the newest `limit` entries, newest first
+pub fn show(limit: usize, json: bool) -> Result<()> {
+    let entries = load()?;
+    let newest: Vec<&Entry> = entries.iter().rev().take(limit).collect();
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "entries": newest }))?
+        );
+        return Ok(());
+    }
+
+    if newest.is_empty() {
+        println!("No applies recorded yet");
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let width = newest.iter().map(|e| e.profile.len()).max().unwrap_or(0);
+    for entry in newest {
+        let outcome = if !entry.ok {
+            "failed"
+        } else if entry.reverted {
+            "reverted"
+        } else {
+            "ok"
+        };
+        let docks = if entry.docks.is_empty() {
+            String::new()
+        } else {
+            format!("  docks: {}", entry.docks.join(", "))
+        };
+        println!(
+            "{:>8}  {:<width$}  {:<6}  {:<8}{}",
+            ago(entry.at, now),
+            entry.profile,
+            entry.trigger,
+            outcome,
+            docks,
+            width = width
+        );
+        if let Some(error) = &entry.error {
+            println!("          error: {}", error);
+        }
+        for warning in &entry.warnings {
+            println!("          warning: {}", warning);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(profile: &str, ok: bool) -> Entry {
+        let result = if ok {
+            Ok(Vec::new())
+        } else {
+            Err("failed".to_string())
+        };
+        Entry::new(profile, Trigger::Cli, Vec::new(), result)
+    }
+
+    #[test]
+    fn previous_skips_the_active_failed_and_reverted_profiles() {
+        let mut reverted = entry("presenting", true);
+        reverted.reverted = true;
+        let entries = vec![
+            entry("laptop", true),
+            entry("desk", true),
+            entry("couch", false),
+            reverted,
+            entry("desk", true),
+        ];
+        assert_eq!(previous_in(&entries, Some("desk")), Some("laptop"));
+        assert_eq!(previous_in(&entries, Some("laptop")), Some("desk"));
+        assert_eq!(previous_in(&entries[..2], Some("desk")), Some("laptop"));
+        assert_eq!(previous_in(&entries[1..2], Some("desk")), None);
+    }
+
+    #[test]
+    fn records_and_caps_the_history() {
+        let dir =
+            std::env::temp_dir().join(format!("hyprpier-history-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        config::set_test_config_dir(dir);
+
+        for i in 0..MAX_ENTRIES + 5 {
+            record(entry(&format!("p{}", i), true)).unwrap();
+        }
+        mark_reverted(&format!("p{}", MAX_ENTRIES + 4)).unwrap();
+
+        let entries = load().unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0].profile, "p5");
+        assert!(entries.last().unwrap().reverted);
+        assert!(!entries[entries.len() - 2].reverted);
+    }
+
+    #[test]
+    fn formats_age() {
+        assert_eq!(ago(100, 142), "42s ago");
+        assert_eq!(ago(0, 300), "5m ago");
+        assert_eq!(ago(0, 3 * 3600 + 5), "3h ago");
+        assert_eq!(ago(0, 2 * 86400), "2d ago");
+    }
+}
@@ -0,0 +1,262 @@
+//! Pre/post apply hooks
+//!
+//! Shell commands a profile lists under `hooks`, plus global executables in
+//! ~/.config/hyprpier/hooks/ named after the event (`pre_apply`,
+//! `post_apply`, `on_fail`). Global hooks run first. Each gets the apply's
+//! details in `HYPRPIER_*` environment variables.
+
+use anyhow::{Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::dock;
+use crate::metadata::Metadata;
+use crate::profile::{Hooks, Profile};
+
+/// A hook that hangs must not hold up the apply (or the daemon) forever
+const HOOK_TIMEOUT_SECS: u64 = 30;
+const HOOK_POLL_MS: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PreApply,
+    PostApply,
+    OnFail,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreApply => "pre_apply",
+            HookEvent::PostApply => "post_apply",
+            HookEvent::OnFail => "on_fail",
+        }
+    }
+}
+
+/// One hook to run: a global executable or a profile shell command
+enum Hook {
+    Global(PathBuf),
+    Shell(String),
+}
+
+impl Hook {
+    fn label(&self) -> String {
+        match self {
+            Hook::Global(path) => path.display().to_string(),
+            Hook::Shell(cmd) => format!("`{}`", cmd),
+        }
+    }
+
+    fn command(&self) -> Command {
+        match self {
+            Hook::Global(path) => Command::new(path),
+            Hook::Shell(cmd) => {
+                let mut c = Command::new("sh");
+                c.arg("-c").arg(cmd);
+                c
+            }
+        }
+    }
+}
+
+/// Everything a profile's hooks need to know about the apply
+pub struct HookContext {
+    profile: String,
+    previous: Option<String>,
+    /// Resolved port names of the profile's enabled monitors
+    monitors: Vec<String>,
+    hooks: Hooks,
+    /// Where global hooks live; None skips them
+    global_dir: Option<PathBuf>,
+    /// Hooks write to the terminal unless the caller owns it (the TUI)
+    quiet: bool,
+}
+
+impl HookContext {
+    /// `profile` should already have its monitor names resolved
+    pub fn new(profile: &Profile, previous: Option<String>, quiet: bool) -> Self {
+        Self {
+            profile: profile.name.clone(),
+            previous,
+            monitors: profile
+                .monitors
+                .iter()
+                .filter(|m| m.enabled)
+                .map(|m| m.name.clone())
+                .collect(),
+            hooks: profile.hooks.clone(),
+            global_dir: crate::config::hooks_dir().ok(),
+            quiet,
+        }
+    }
+
+    /// Run every hook for an event. Failures are returned as warnings, or
+    /// as an error when the profile marks its hooks blocking (`on_fail`
+    /// never blocks; the apply has already failed). `error` is passed to
+    /// `on_fail` hooks.
+    pub fn run(&self, event: HookEvent, error: Option<&anyhow::Error>) -> Result<Vec<String>> {
+        let hooks = self.hooks_for(event);
+        if hooks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let env = self.env(event, dock_uuid(&self.profile), error);
+        let mut warnings = Vec::new();
+        for hook in hooks {
+            let mut cmd = hook.command();
+            cmd.envs(env.iter().map(|(k, v)| (*k, v)));
+            if let Err(e) = run_with_timeout(cmd, &hook.label(), self.quiet) {
+                if self.hooks.blocking && event != HookEvent::OnFail {
+                    return Err(e);
+                }
+                warnings.push(format!("{} hook failed: {:#}", event.name(), e));
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Global hook (if present and executable) followed by the profile's
+    fn hooks_for(&self, event: HookEvent) -> Vec<Hook> {
+        let mut hooks = Vec::new();
+        if let Some(dir) = &self.global_dir {
+            let path = dir.join(event.name());
+            let executable = std::fs::metadata(&path)
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if executable {
+                hooks.push(Hook::Global(path));
+            }
+        }
+        let commands = match event {
+            HookEvent::PreApply => &self.hooks.pre_apply,
+            HookEvent::PostApply => &self.hooks.post_apply,
+            HookEvent::OnFail => &self.hooks.on_fail,
+        };
+        hooks.extend(commands.iter().cloned().map(Hook::Shell));
+        hooks
+    }
+
+    fn env(
+        &self,
+        event: HookEvent,
+        dock_uuid: Option<String>,
+        error: Option<&anyhow::Error>,
+    ) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("HYPRPIER_EVENT", event.name().to_string()),
+            ("HYPRPIER_PROFILE", self.profile.clone()),
+            ("HYPRPIER_PREVIOUS_PROFILE", self.previous.clone().unwrap_or_default()),
+            ("HYPRPIER_DOCK_UUID", dock_uuid.unwrap_or_default()),
+            ("HYPRPIER_MONITORS", self.monitors.join(" ")),
+        ];
+        if let Some(e) = error {
+            env.push(("HYPRPIER_ERROR", format!("{:#}", e)));
+        }
+        env
+    }
+}
+
+/// The connected dock linked to this profile, or else the first connected
+/// dock; None when undocked
+fn dock_uuid(profile: &str) -> Option<String> {
+    let docks = dock::detect_docks().ok()?;
+    let linked = Metadata::load()
+        .ok()
+        .and_then(|m| m.get_profile_dock(profile).cloned());
+    docks
+        .iter()
+        .find(|d| Some(&d.uuid) == linked.as_ref())
+        .or(docks.first())
+        .map(|d| d.uuid.clone())
+}
+
+/// Run a hook to completion, killing it after HOOK_TIMEOUT_SECS
+fn run_with_timeout(mut cmd: Command, label: &str, quiet: bool) -> Result<()> {
+    cmd.stdin(Stdio::null());
+    if quiet {
+        cmd.stdout(Stdio::null()).stderr(Stdio::null());
+    }
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run {}", label))?;
+
+    let deadline = Instant::now() + Duration::from_secs(HOOK_TIMEOUT_SECS);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                return Ok(());
+            }
+            anyhow::bail!("{} exited with {}", label, status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("{} timed out after {}s", label, HOOK_TIMEOUT_SECS);
+        }
+        thread::sleep(Duration::from_millis(HOOK_POLL_MS));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Monitor;
+
+    fn context(hooks: Hooks) -> HookContext {
+        let mut profile = Profile::new("desk");
+        profile.monitors.push(Monitor::test_fixture("DP-1", "1920x1080", 1.0, 0));
+        let mut off = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        off.enabled = false;
+        profile.monitors.push(off);
+        profile.hooks = hooks;
+        let mut ctx = HookContext::new(&profile, Some("laptop".to_string()), true);
+        // Never run the developer's own global hooks
+        ctx.global_dir = None;
+        ctx
+    }
+
+    #[test]
+    fn env_describes_the_apply() {
+        let ctx = context(Hooks::default());
+        let err = anyhow::anyhow!("boom");
+        let env = ctx.env(HookEvent::OnFail, Some("uuid-1".to_string()), Some(&err));
+        assert_eq!(
+            env,
+            vec![
+                ("HYPRPIER_EVENT", "on_fail".to_string()),
+                ("HYPRPIER_PROFILE", "desk".to_string()),
+                ("HYPRPIER_PREVIOUS_PROFILE", "laptop".to_string()),
+                ("HYPRPIER_DOCK_UUID", "uuid-1".to_string()),
+                ("HYPRPIER_MONITORS", "DP-1".to_string()),
+                ("HYPRPIER_ERROR", "boom".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_hook_warns_unless_blocking() {
+        let mut hooks = Hooks {
+            post_apply: vec!["true".to_string(), "exit 3".to_string()],
+            ..Hooks::default()
+        };
+        let warnings = context(hooks.clone()).run(HookEvent::PostApply, None).unwrap();
+        assert_eq!(warnings.len(), 1, "got: {:?}", warnings);
+
+        hooks.blocking = true;
+        assert!(context(hooks).run(HookEvent::PostApply, None).is_err());
+    }
+
+    #[test]
+    fn on_fail_never_blocks() {
+        let hooks = Hooks {
+            on_fail: vec!["false".to_string()],
+            blocking: true,
+            ..Hooks::default()
+        };
+        let warnings = context(hooks).run(HookEvent::OnFail, None).unwrap();
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::process::Command;
-use std::thread;
 use std::time::Duration;
 
-use crate::profile::{LidSwitch, Monitor, Position, Profile, Workspace};
+use crate::compositor::{
+    Compositor, Dialect, LiveClient, LiveMonitorState, LiveWorkspace, Mode, OutputModes,
+};
+use crate::ipc;
+use crate::profile::{LidSwitch, Monitor, MonitorOptions, Position, Profile, Workspace};
 
 /// Get the Hyprland instance signature, with fallback discovery
 /// Usually set in the environment, but we can discover it if needed
@@ -42,6 +45,35 @@ fn get_hyprland_instance_signature() -> Option<String> {
     best.map(|(_, sig)| sig)
 }
 
+/// Runtime directory of the Hyprland instance ($XDG_RUNTIME_DIR/hypr/<sig>)
+fn instance_dir() -> Option<std::path::PathBuf> {
+    let sig = get_hyprland_instance_signature()?;
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    Some(std::path::Path::new(&runtime_dir).join("hypr").join(sig))
+}
+
+/// Path to Hyprland's event socket ($XDG_RUNTIME_DIR/hypr/<sig>/.socket2.sock)
+pub fn event_socket_path() -> Option<std::path::PathBuf> {
+    Some(instance_dir()?.join(".socket2.sock"))
+}
+
+/// Open a connection to Hyprland's request socket (.socket.sock), or None
+/// if it isn't reachable and callers should fall back to the hyprctl binary.
+fn ipc_stream() -> Option<std::os::unix::net::UnixStream> {
+    let path = instance_dir()?.join(".socket.sock");
+    ipc::connect(&path).ok()
+}
+
+/// Whether a line from the event socket (`EVENT>>DATA`) reports a display
+/// being connected or disconnected, in either the v1 or v2 event form.
+pub fn is_monitor_event(line: &str) -> bool {
+    let event = line.split_once(">>").map_or(line, |(event, _)| event);
+    matches!(
+        event,
+        "monitoradded" | "monitorremoved" | "monitoraddedv2" | "monitorremovedv2"
+    )
+}
+
 /// Create a hyprctl Command with the instance signature set
 fn hyprctl_command() -> Command {
     let mut cmd = Command::new("hyprctl");
@@ -56,35 +88,6 @@ const MONITOR_APPLY_DELAY_MS: u64 = 1000;
 const WORKSPACE_MOVE_RETRY_DELAY_MS: u64 = 500;
 const WORKSPACE_MOVE_MAX_RETRIES: u8 = 3;
 
-/// Raw workspace info from hyprctl workspaces -j
-#[derive(Debug, Deserialize)]
-struct HyprWorkspace {
-    id: i64,
-    monitor: String,
-}
-
-/// Workspace reference nested in hyprctl clients/monitors -j output
-#[derive(Debug, Deserialize)]
-struct HyprWorkspaceRef {
-    id: i64,
-}
-
-/// Raw client info from hyprctl clients -j
-#[derive(Debug, Deserialize)]
-struct HyprClient {
-    address: String,
-    workspace: HyprWorkspaceRef,
-}
-
-/// Focus/active-workspace state from hyprctl monitors -j
-#[derive(Debug, Deserialize)]
-struct HyprMonitorState {
-    #[serde(default)]
-    focused: bool,
-    #[serde(rename = "activeWorkspace")]
-    active_workspace: HyprWorkspaceRef,
-}
-
 /// Raw monitor info from hyprctl monitors -j
 #[derive(Debug, Deserialize)]
 struct HyprMonitor {
@@ -98,10 +101,68 @@ struct HyprMonitor {
     y: i32,
     scale: f64,
     transform: u8,
+    /// Only present (and true) in `monitors all` output for disabled outputs
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    vrr: bool,
+    /// DRM pixel format, e.g. "XRGB8888" or "XRGB2101010" for 10-bit
+    #[serde(default, rename = "currentFormat")]
+    current_format: String,
+    /// Name of the mirrored monitor, or "none"
+    #[serde(default, rename = "mirrorOf")]
+    mirror_of: String,
+    #[serde(default, rename = "colorManagementPreset")]
+    color_management_preset: Option<String>,
+    #[serde(default, rename = "sdrBrightness")]
+    sdr_brightness: Option<f64>,
+    /// e.g. "3840x2160@59.94Hz", best first
+    #[serde(default, rename = "availableModes")]
+    available_modes: Vec<String>,
+}
+
+impl From<HyprMonitor> for Monitor {
+    fn from(m: HyprMonitor) -> Self {
+        Monitor {
+            resolution: format!("{}x{}", m.width, m.height),
+            mode: format!("{}x{}@{:.0}", m.width, m.height, m.refresh_rate),
+            name: m.name,
+            description: Some(m.description),
+            enabled: !m.disabled,
+            refresh_rate: m.refresh_rate,
+            position: Position { x: m.x, y: m.y },
+            scale: m.scale,
+            transform: m.transform,
+            // Only what differs from Hyprland's defaults, so saving a
+            // live layout doesn't pin every option
+            options: MonitorOptions {
+                // Whether VRR is active now, not the configured mode;
+                // `Profile::keep_vrr` restores a saved one on re-save
+                vrr: m.vrr.then_some(1),
+                bitdepth: m.current_format.contains("2101010").then_some(10),
+                cm: m.color_management_preset.filter(|cm| cm != "srgb"),
+                sdrbrightness: m.sdr_brightness.filter(|b| (b - 1.0).abs() > 1e-3),
+                mirror: Some(m.mirror_of).filter(|name| !name.is_empty() && name != "none"),
+            },
+        }
+    }
+}
+
+fn output_modes(m: HyprMonitor) -> OutputModes {
+    let mut modes: Vec<Mode> = m.available_modes.iter().filter_map(|s| Mode::parse(s)).collect();
+    modes.dedup();
+    OutputModes {
+        name: m.name,
+        description: Some(m.description),
+        modes,
+    }
 }
 
 /// Check if Hyprland is currently running
 pub fn is_running() -> bool {
+    if ipc_stream().is_some() {
+        return true;
+    }
     Command::new("pgrep")
         .args(["-x", "Hyprland"])
         .output()
@@ -109,46 +170,181 @@ pub fn is_running() -> bool {
         .unwrap_or(false)
 }
 
-/// Detect currently connected monitors using hyprctl
-pub fn detect_monitors() -> Result<Vec<Monitor>> {
-    let output = hyprctl_command()
-        .args(["monitors", "-j"])
-        .output()
-        .context("Failed to run hyprctl monitors")?;
+/// Which config language the running Hyprland speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    /// Hyprland 0.55+: monitors.lua with `hl.*` calls, runtime via `eval`
+    Lua,
+    /// Earlier releases: hyprlang monitors.conf, runtime via `keyword`/`dispatch`
+    Legacy,
+}
 
-    if !output.status.success() {
-        anyhow::bail!("hyprctl monitors failed");
+/// Version info from hyprctl version -j
+#[derive(Debug, Deserialize)]
+struct HyprVersion {
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    tag: String,
+}
+
+impl ConfigFormat {
+    /// The format to write and apply in: the `config_format` override from
+    /// metadata if set, otherwise detected from the Hyprland version. Falls
+    /// back to Lua when the version can't be determined.
+    pub fn current() -> Self {
+        if let Some(format) = crate::metadata::Metadata::load()
+            .ok()
+            .and_then(|m| m.config_format)
+        {
+            return format;
+        }
+        detect_version()
+            .map(Self::for_version)
+            .unwrap_or(ConfigFormat::Lua)
     }
 
-    let hypr_monitors: Vec<HyprMonitor> = serde_json::from_slice(&output.stdout)
-        .context("Failed to parse hyprctl output")?;
+    fn for_version((major, minor): (u32, u32)) -> Self {
+        if (major, minor) < (0, 55) {
+            ConfigFormat::Legacy
+        } else {
+            ConfigFormat::Lua
+        }
+    }
 
-    let monitors = hypr_monitors
-        .into_iter()
-        .map(|m| {
-            let resolution = format!("{}x{}", m.width, m.height);
-            let mode = format!("{}x{}@{:.0}", m.width, m.height, m.refresh_rate);
-            Monitor {
-                name: m.name,
-                description: Some(m.description),
-                enabled: true,
-                resolution,
-                refresh_rate: m.refresh_rate,
-                position: Position { x: m.x, y: m.y },
-                scale: m.scale,
-                transform: m.transform,
-                mode,
+    /// Where this format's generated config lives
+    pub fn config_path(self) -> Result<std::path::PathBuf> {
+        match self {
+            ConfigFormat::Lua => crate::config::hyprland_monitors_lua(),
+            ConfigFormat::Legacy => crate::config::hyprland_monitors_conf(),
+        }
+    }
+
+    /// The socket request that runs one runtime step: Lua steps are
+    /// expressions for `eval`, legacy steps are complete hyprctl commands
+    fn request(self, step: &str) -> String {
+        match self {
+            ConfigFormat::Lua => format!("eval {}", step),
+            ConfigFormat::Legacy => step.to_string(),
+        }
+    }
+
+    /// hyprctl exits 0 on in-band failures: eval replies "error: ...",
+    /// keyword/dispatch reply with anything other than "ok"
+    fn check_reply(self, reply: &str) -> Result<()> {
+        let trimmed = reply.trim();
+        match self {
+            ConfigFormat::Lua if trimmed.starts_with("error:") => {
+                anyhow::bail!("hyprctl eval failed: {}", trimmed)
             }
-        })
-        .collect();
+            ConfigFormat::Legacy if !trimmed.is_empty() && trimmed != "ok" => {
+                anyhow::bail!("hyprctl failed: {}", trimmed)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hyprland's (major, minor) version: from the running instance when there
+/// is one, otherwise from the installed binary
+fn detect_version() -> Option<(u32, u32)> {
+    if is_running() {
+        if let Ok(v) = hyprctl_json::<HyprVersion>("version") {
+            return parse_version(&v.version).or_else(|| parse_version(&v.tag));
+        }
+    }
+    let output = Command::new("Hyprland").arg("--version").output().ok()?;
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The running Hyprland's version tag (e.g. "v0.55.0"), for `doctor`
+pub fn running_version() -> Option<String> {
+    if !is_running() {
+        return None;
+    }
+    let v = hyprctl_json::<HyprVersion>("version").ok()?;
+    Some(if v.tag.is_empty() { v.version } else { v.tag })
+}
+
+/// Find the first `X.Y[.Z]` version in text like "v0.54.2-b1" or
+/// "Hyprland 0.54.2 built from branch ..."
+fn parse_version(text: &str) -> Option<(u32, u32)> {
+    text.split_whitespace().find_map(|word| {
+        let word = word.strip_prefix('v').unwrap_or(word);
+        let mut parts = word.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor: String = parts.next()?.chars().take_while(|c| c.is_ascii_digit()).collect();
+        Some((major, minor.parse().ok()?))
+    })
+}
 
-    Ok(monitors)
+/// The running Hyprland instance, reached over its request socket (or the
+/// hyprctl binary when the socket isn't reachable)
+pub struct Hyprland {
+    /// Resolved once by `compositor::detect()`: finding it loads metadata
+    /// and asks Hyprland for its version, and every runtime step needs it
+    pub format: ConfigFormat,
+}
+
+impl Compositor for Hyprland {
+    fn is_running(&self) -> bool {
+        is_running()
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Hyprland(self.format)
+    }
+
+    /// `hyprctl monitors` lists only enabled outputs; `monitors all` adds
+    /// the disabled ones
+    fn monitors(&self, all: bool) -> Result<Vec<Monitor>> {
+        let cmd = if all { "monitors all" } else { "monitors" };
+        let hypr_monitors: Vec<HyprMonitor> = hyprctl_json(cmd)?;
+        Ok(hypr_monitors.into_iter().map(Monitor::from).collect())
+    }
+
+    fn available_modes(&self) -> Result<Vec<OutputModes>> {
+        let hypr_monitors: Vec<HyprMonitor> = hyprctl_json("monitors all")?;
+        Ok(hypr_monitors.into_iter().map(output_modes).collect())
+    }
+
+    fn workspaces(&self) -> Result<Vec<LiveWorkspace>> {
+        hyprctl_json("workspaces")
+    }
+
+    fn clients(&self) -> Result<Vec<LiveClient>> {
+        hyprctl_json("clients")
+    }
+
+    fn monitor_states(&self) -> Result<Vec<LiveMonitorState>> {
+        hyprctl_json("monitors")
+    }
+
+    fn eval(&self, steps: &[String]) -> Vec<Result<()>> {
+        run_batch(self.format, steps)
+    }
+
+    fn reload(&self) -> Result<()> {
+        hyprctl_reload()
+    }
+}
+
+/// Descriptions of every connected display, including ones a profile (or
+/// the lid bind) has disabled. Used for monitor-set matching, where a
+/// disabled panel still counts as present.
+pub fn connected_monitor_descriptions(compositor: &dyn Compositor) -> Result<Vec<String>> {
+    Ok(compositor
+        .monitors(true)?
+        .into_iter()
+        .filter_map(|m| m.description)
+        .collect())
 }
 
 /// Resolve stored monitor descriptions to current port names
 /// This allows profiles to work even when dock assigns different port names
-pub fn resolve_monitor_names(profile: &mut Profile) -> Result<()> {
-    let current_monitors = detect_monitors()?;
+pub fn resolve_monitor_names(compositor: &dyn Compositor, profile: &mut Profile) -> Result<()> {
+    let current_monitors = compositor.monitors(false)?;
     resolve_monitor_names_with(profile, &current_monitors);
     Ok(())
 }
@@ -185,6 +381,11 @@ fn resolve_monitor_names_with(profile: &mut Profile, current: &[Monitor]) {
             monitor.name = new_name.clone();
         }
     }
+    for monitor in &mut profile.monitors {
+        if let Some(new_name) = monitor.options.mirror.as_ref().and_then(|m| renames.get(m)) {
+            monitor.options.mirror = Some(new_name.clone());
+        }
+    }
     for ws in &mut profile.workspaces {
         if let Some(new_name) = renames.get(&ws.monitor) {
             ws.monitor = new_name.clone();
@@ -197,11 +398,19 @@ fn resolve_monitor_names_with(profile: &mut Profile, current: &[Monitor]) {
     }
 }
 
+/// Generate Hyprland config content from a profile in the given format
+pub fn generate_config(profile: &Profile, format: ConfigFormat) -> String {
+    match format {
+        ConfigFormat::Lua => generate_lua_config(profile),
+        ConfigFormat::Legacy => generate_legacy_config(profile),
+    }
+}
+
 /// Generate Hyprland Lua config content from a profile
 ///
 /// Emits `hl.monitor`, `hl.workspace_rule`, and `hl.bind` calls for Hyprland 0.55+.
 /// Loaded from `hyprland.lua` via `pcall(require, "monitors")`.
-pub fn generate_config(profile: &Profile) -> String {
+fn generate_lua_config(profile: &Profile) -> String {
     let mut lines = vec![format!(
         "-- Generated by hyprpier from profile: {}",
         profile.name
@@ -270,10 +479,144 @@ pub fn generate_config(profile: &Profile) -> String {
     lines.join("\n")
 }
 
+/// Generate hyprlang config content (`monitor=`, `workspace=`, `bindl=`)
+/// for Hyprland releases before 0.55. Loaded from `hyprland.conf` via
+/// `source = ~/.config/hypr/monitors.conf`.
+fn generate_legacy_config(profile: &Profile) -> String {
+    let mut lines = vec![format!(
+        "# Generated by hyprpier from profile: {}",
+        profile.name
+    )];
+    lines.push(format!(
+        "# {}",
+        profile.description.as_deref().unwrap_or("No description")
+    ));
+    lines.push(String::new());
+
+    for monitor in &profile.monitors {
+        lines.push(format!("monitor = {}", legacy_monitor_fields(monitor).join(", ")));
+    }
+
+    if !profile.workspaces.is_empty() {
+        lines.push(String::new());
+        for ws in &profile.workspaces {
+            let default_part = if ws.default { ", default:true" } else { "" };
+            lines.push(format!(
+                "workspace = {}, monitor:{}{}",
+                ws.id, ws.monitor, default_part
+            ));
+        }
+    }
+
+    // Same lid handling as the Lua config: disable on close, reload on open
+    // (a runtime-disabled monitor only comes back via the config), then
+    // re-assert DPMS once the panel has settled.
+    if let Some(ref lid) = profile.lid_switch {
+        if lid.enabled && profile.monitors.iter().any(|m| m.name == lid.monitor) {
+            lines.push(String::new());
+            lines.push("# Lid switch handling".to_string());
+            lines.push(format!(
+                "bindl = , switch:on:Lid Switch, exec, hyprctl keyword monitor \"{}, disable\"",
+                lid.monitor
+            ));
+            lines.push(format!(
+                "bindl = , switch:off:Lid Switch, exec, hyprctl reload && sleep 0.5 && hyprctl dispatch dpms on {}",
+                lid.monitor
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Fields of a hyprlang `monitor` rule: `name, mode, position, scale`
+/// plus `transform, N` when nonzero and `key, value` pairs for the set
+/// options, or `name, disable`
+fn legacy_monitor_fields(monitor: &Monitor) -> Vec<String> {
+    if !monitor.enabled {
+        return vec![monitor.name.clone(), "disable".to_string()];
+    }
+    let mut fields = vec![
+        monitor.name.clone(),
+        monitor.mode.clone(),
+        format!("{}x{}", monitor.position.x, monitor.position.y),
+        lua_num(monitor.scale),
+    ];
+    if monitor.transform != 0 {
+        fields.push("transform".to_string());
+        fields.push(monitor.transform.to_string());
+    }
+    let options = &monitor.options;
+    let extra = [
+        ("mirror", options.mirror.clone()),
+        ("bitdepth", options.bitdepth.map(|b| b.to_string())),
+        ("vrr", options.vrr.map(|v| v.to_string())),
+        ("cm", options.cm.clone()),
+        ("sdrbrightness", options.sdrbrightness.map(lua_num)),
+    ];
+    for (key, value) in extra {
+        if let Some(value) = value {
+            fields.push(key.to_string());
+            fields.push(value);
+        }
+    }
+    fields
+}
+
+/// Runtime step that applies one monitor
+pub fn monitor_step(format: ConfigFormat, monitor: &Monitor) -> String {
+    match format {
+        ConfigFormat::Lua => format!("hl.monitor({{ {} }})", lua_monitor_fields(monitor)),
+        ConfigFormat::Legacy => {
+            format!("keyword monitor {}", legacy_monitor_fields(monitor).join(","))
+        }
+    }
+}
+
+/// Runtime step that moves a workspace to a monitor
+pub fn move_workspace_step(format: ConfigFormat, workspace_id: u8, monitor: &str) -> String {
+    match format {
+        ConfigFormat::Lua => format!(
+            "hl.dispatch(hl.dsp.workspace.move({{ workspace = {}, monitor = {} }}))",
+            workspace_id,
+            lua_str(monitor)
+        ),
+        ConfigFormat::Legacy => {
+            format!("dispatch moveworkspacetomonitor {} {}", workspace_id, monitor)
+        }
+    }
+}
+
+/// Runtime step that moves a window to a workspace without following it
+pub fn move_window_step(format: ConfigFormat, address: &str, workspace_id: u8) -> String {
+    match format {
+        ConfigFormat::Lua => format!(
+            "hl.dispatch(hl.dsp.window.move({{ window = {}, workspace = {} }}))",
+            lua_str(&format!("address:{}", address)),
+            workspace_id
+        ),
+        ConfigFormat::Legacy => format!(
+            "dispatch movetoworkspacesilent {},address:{}",
+            workspace_id, address
+        ),
+    }
+}
+
+/// Runtime step that focuses a workspace
+pub fn focus_workspace_step(format: ConfigFormat, workspace_id: i64) -> String {
+    match format {
+        ConfigFormat::Lua => format!("hl.dispatch(hl.dsp.focus({{ workspace = {} }}))", workspace_id),
+        ConfigFormat::Legacy => format!("dispatch workspace {}", workspace_id),
+    }
+}
+
 /// Format the inner fields of an `hl.monitor({...})` call for a monitor.
 ///
 /// Disabled monitors emit `output = "X", disabled = true`. Enabled monitors
-/// emit `output`, `mode`, `position`, `scale`, and `transform` (when nonzero).
+/// emit `output`, `mode`, `position`, `scale`, and `transform` (when nonzero),
+/// then whichever of `mirror`, `bitdepth`, `vrr`, `cm` and `sdrbrightness`
+/// are set.
 fn lua_monitor_fields(monitor: &Monitor) -> String {
     if !monitor.enabled {
         return format!("output = {}, disabled = true", lua_str(&monitor.name));
@@ -290,6 +633,22 @@ fn lua_monitor_fields(monitor: &Monitor) -> String {
     if monitor.transform != 0 {
         fields.push(format!("transform = {}", monitor.transform));
     }
+    let options = &monitor.options;
+    if let Some(mirror) = &options.mirror {
+        fields.push(format!("mirror = {}", lua_str(mirror)));
+    }
+    if let Some(bitdepth) = options.bitdepth {
+        fields.push(format!("bitdepth = {}", bitdepth));
+    }
+    if let Some(vrr) = options.vrr {
+        fields.push(format!("vrr = {}", vrr));
+    }
+    if let Some(cm) = &options.cm {
+        fields.push(format!("cm = {}", lua_str(cm)));
+    }
+    if let Some(brightness) = options.sdrbrightness {
+        fields.push(format!("sdrbrightness = {}", lua_num(brightness)));
+    }
     fields.join(", ")
 }
 
@@ -311,6 +670,7 @@ fn lua_str(s: &str) -> String {
 }
 
 /// Format a finite f64 for Lua, preserving fractional part (1.0 stays "1.0").
+/// hyprlang accepts the same form, so the legacy generator uses it too.
 fn lua_num(n: f64) -> String {
     if !n.is_finite() {
         return "1.0".to_string();
@@ -322,10 +682,17 @@ fn lua_num(n: f64) -> String {
     }
 }
 
-/// Write the config to ~/.config/hypr/monitors.lua
-pub fn write_config(profile: &Profile) -> Result<()> {
-    let config = generate_config(profile);
-    let path = crate::config::hyprland_monitors_lua()?;
+/// Write the config to ~/.config/hypr/monitors.lua (or monitors.conf for
+/// the legacy format, ~/.config/sway/monitors.conf under sway). Returns
+/// the path written.
+pub fn write_config(profile: &Profile, dialect: Dialect) -> Result<std::path::PathBuf> {
+    write_config_text(dialect, &dialect.generate_config(profile))?;
+    dialect.config_path()
+}
+
+/// Write raw config content for a dialect, e.g. a snapshot being restored
+pub fn write_config_text(dialect: Dialect, config: &str) -> Result<()> {
+    let path = dialect.config_path()?;
 
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
@@ -334,7 +701,9 @@ pub fn write_config(profile: &Profile) -> Result<()> {
 
     // Temp + rename: Hyprland reads this file on `hyprctl reload` (including
     // from the lid-open bind), so it must never observe a partial write.
-    let temp_path = path.with_extension("lua.tmp");
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(".tmp");
+    let temp_path = std::path::PathBuf::from(temp_path);
     std::fs::write(&temp_path, config)
         .with_context(|| format!("Failed to write {}", temp_path.display()))?;
     std::fs::rename(&temp_path, &path)
@@ -343,32 +712,19 @@ pub fn write_config(profile: &Profile) -> Result<()> {
     Ok(())
 }
 
-/// Apply a single monitor configuration via hyprctl eval.
-///
-/// Hyprland 0.55's non-legacy (Lua) parser rejects `hyprctl keyword monitor`,
-/// so we invoke the native `hl.monitor({...})` API through `hyprctl eval`.
-pub fn apply_monitor(monitor: &Monitor) -> Result<()> {
-    let expr = format!("hl.monitor({{ {} }})", lua_monitor_fields(monitor));
-    hyprctl_eval(&expr).with_context(|| format!("Failed to apply monitor {}", monitor.name))
-}
-
-/// Move a workspace to a monitor via hyprctl eval.
-///
-/// Replaces `hyprctl dispatch moveworkspacetomonitor`, which is rejected by
-/// Hyprland 0.55's non-legacy parser.
-pub fn move_workspace(workspace_id: u8, monitor: &str) -> Result<()> {
-    let expr = format!(
-        "hl.dispatch(hl.dsp.workspace.move({{ workspace = {}, monitor = {} }}))",
-        workspace_id,
-        lua_str(monitor)
-    );
-    hyprctl_eval(&expr).with_context(|| format!("Failed to move workspace {}", workspace_id))
-}
-
-/// Run `hyprctl <cmd> -j` and deserialize the JSON output.
+/// Run `hyprctl <cmd> -j` and deserialize the JSON output. `cmd` may carry
+/// arguments (e.g. "monitors all"). Goes over the request socket when it's
+/// reachable, otherwise spawns hyprctl.
 fn hyprctl_json<T: serde::de::DeserializeOwned>(cmd: &str) -> Result<T> {
+    if let Some(stream) = ipc_stream() {
+        let reply = ipc::request(stream, "j", cmd)?;
+        return serde_json::from_str(&reply)
+            .with_context(|| format!("Failed to parse hyprctl {} output", cmd));
+    }
+
     let output = hyprctl_command()
-        .args([cmd, "-j"])
+        .args(cmd.split_whitespace())
+        .arg("-j")
         .output()
         .with_context(|| format!("Failed to run hyprctl {}", cmd))?;
 
@@ -380,6 +736,37 @@ fn hyprctl_json<T: serde::de::DeserializeOwned>(cmd: &str) -> Result<T> {
         .with_context(|| format!("Failed to parse hyprctl {} output", cmd))
 }
 
+/// A live workspace that applying a profile would move to another monitor
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct WorkspaceMove {
+    pub id: u8,
+    pub from: String,
+    pub to: String,
+}
+
+/// Query live workspaces and list the ones the profile would move
+pub fn workspace_move_plan(compositor: &dyn Compositor, profile: &Profile) -> Result<Vec<WorkspaceMove>> {
+    Ok(plan_workspace_moves(profile, &compositor.workspaces()?))
+}
+
+/// Profile workspaces that currently exist on a different monitor than the
+/// one the profile assigns. Workspaces that don't exist yet aren't moves;
+/// their rule just takes effect when they're created.
+fn plan_workspace_moves(profile: &Profile, live: &[LiveWorkspace]) -> Vec<WorkspaceMove> {
+    profile
+        .workspaces
+        .iter()
+        .filter_map(|ws| {
+            let current = live.iter().find(|l| l.id == i64::from(ws.id))?;
+            (current.monitor != ws.monitor).then(|| WorkspaceMove {
+                id: ws.id,
+                from: current.monitor.clone(),
+                to: ws.monitor.clone(),
+            })
+        })
+        .collect()
+}
+
 /// The workspace a monitor should land on per the profile: its `default`
 /// workspace, or the first one listed for it.
 fn default_workspace_for(profile: &Profile, monitor: &str) -> Option<u8> {
@@ -393,7 +780,7 @@ fn default_workspace_for(profile: &Profile, monitor: &str) -> Option<u8> {
     first
 }
 
-/// Plan the eval expressions that fold orphan workspaces back into the
+/// Plan the runtime steps that fold orphan workspaces back into the
 /// profile's layout. Split from cleanup_orphan_workspaces for testability.
 ///
 /// A monitor that comes up mid dock-transition, before the fresh workspace
@@ -408,10 +795,11 @@ fn default_workspace_for(profile: &Profile, monitor: &str) -> Option<u8> {
 /// nowhere else to go). Focus dispatches are ordered so focus ends up back
 /// on the monitor that had it.
 fn plan_orphan_cleanup(
+    dialect: Dialect,
     profile: &Profile,
-    workspaces: &[HyprWorkspace],
-    clients: &[HyprClient],
-    monitors: &[HyprMonitorState],
+    workspaces: &[LiveWorkspace],
+    clients: &[LiveClient],
+    monitors: &[LiveMonitorState],
 ) -> Vec<String> {
     let mut exprs = Vec::new();
     if profile.workspaces.is_empty() {
@@ -431,15 +819,11 @@ fn plan_orphan_cleanup(
         return exprs;
     }
 
-    // Re-home each orphan's windows. window.move without `follow` keeps
-    // focus where it is.
+    // Re-home each orphan's windows. window.move without `follow` (or
+    // movetoworkspacesilent) keeps focus where it is.
     for client in clients {
-        if let Some(target) = orphan_targets.get(&client.workspace.id) {
-            exprs.push(format!(
-                "hl.dispatch(hl.dsp.window.move({{ window = {}, workspace = {} }}))",
-                lua_str(&format!("address:{}", client.address)),
-                target
-            ));
+        if let Some(&target) = orphan_targets.get(&client.workspace.id) {
+            exprs.push(dialect.move_window_step(&client.address, target));
         }
     }
 
@@ -467,76 +851,125 @@ fn plan_orphan_cleanup(
         }
     }
     for id in focus_ids {
-        exprs.push(format!(
-            "hl.dispatch(hl.dsp.focus({{ workspace = {} }}))",
-            id
-        ));
+        exprs.push(dialect.focus_workspace_step(id));
     }
 
     exprs
 }
 
-/// Query current Hyprland state and execute the orphan-workspace cleanup.
-pub fn cleanup_orphan_workspaces(profile: &Profile) -> Result<()> {
-    let workspaces: Vec<HyprWorkspace> = hyprctl_json("workspaces")?;
-    let clients: Vec<HyprClient> = hyprctl_json("clients")?;
-    let monitors: Vec<HyprMonitorState> = hyprctl_json("monitors")?;
+/// Query current compositor state and plan the orphan-workspace cleanup
+/// without executing it.
+pub fn orphan_cleanup_plan(
+    compositor: &dyn Compositor,
+    profile: &Profile,
+    dialect: Dialect,
+) -> Result<Vec<String>> {
+    let workspaces = compositor.workspaces()?;
+    let clients = compositor.clients()?;
+    let monitors = compositor.monitor_states()?;
+    Ok(plan_orphan_cleanup(dialect, profile, &workspaces, &clients, &monitors))
+}
+
+/// Query current compositor state and execute the orphan-workspace cleanup.
+/// Returns a warning per step that failed.
+fn cleanup_orphan_workspaces(
+    compositor: &dyn Compositor,
+    profile: &Profile,
+    dialect: Dialect,
+) -> Result<Vec<String>> {
+    let steps = orphan_cleanup_plan(compositor, profile, dialect)?;
+    Ok(compositor
+        .eval(&steps)
+        .into_iter()
+        .filter_map(|r| r.err())
+        .map(|e| format!("Orphan workspace cleanup step failed: {}", e))
+        .collect())
+}
 
-    for expr in plan_orphan_cleanup(profile, &workspaces, &clients, &monitors) {
-        if let Err(e) = hyprctl_eval(&expr) {
-            eprintln!("Warning: orphan workspace cleanup step failed: {}", e);
+/// Run several runtime steps, in order, as a single `[[BATCH]]` request
+/// when the socket is reachable. Returns one result per step. Falls back to
+/// one hyprctl call per step when the socket is unavailable or a step
+/// contains the batch separator `;`.
+fn run_batch(format: ConfigFormat, steps: &[String]) -> Vec<Result<()>> {
+    if steps.is_empty() {
+        return Vec::new();
+    }
+    let commands: Vec<String> = steps.iter().map(|s| format.request(s)).collect();
+    if ipc::can_batch(&commands) {
+        if let Some(stream) = ipc_stream() {
+            return match ipc::batch(stream, &commands) {
+                Ok(replies) => replies.iter().map(|r| format.check_reply(r)).collect(),
+                Err(e) => {
+                    let msg = format!("{:#}", e);
+                    steps.iter().map(|_| Err(anyhow::anyhow!("{}", msg))).collect()
+                }
+            };
         }
     }
-    Ok(())
+    steps.iter().map(|s| run_step(format, s)).collect()
 }
 
-/// Run `hyprctl eval <expr>` and surface non-zero exit or in-band Lua errors.
-fn hyprctl_eval(expr: &str) -> Result<()> {
-    let output = hyprctl_command()
-        .args(["eval", expr])
-        .output()
-        .context("Failed to run hyprctl eval")?;
+/// Run one runtime step (`hyprctl eval <expr>`, or a legacy hyprctl
+/// command) and surface non-zero exit or in-band errors.
+fn run_step(format: ConfigFormat, step: &str) -> Result<()> {
+    let request = format.request(step);
+    if let Some(stream) = ipc_stream() {
+        let reply = ipc::request(stream, "", &request)?;
+        return format.check_reply(&reply);
+    }
+
+    // Eval expressions keep their spaces as one argument; legacy commands
+    // have none inside their arguments and hyprctl re-joins them anyway
+    let mut cmd = hyprctl_command();
+    match format {
+        ConfigFormat::Lua => cmd.args(["eval", step]),
+        ConfigFormat::Legacy => cmd.args(step.split_whitespace()),
+    };
+    let output = cmd.output().context("Failed to run hyprctl")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("hyprctl eval failed: {}", stderr.trim());
-    }
-
-    // hyprctl eval surfaces Lua errors as stdout starting with "error:" but
-    // exits 0. Treat that as a failure so callers (and retries) notice.
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let trimmed = stdout.trim();
-    if trimmed.starts_with("error:") {
-        anyhow::bail!("hyprctl eval failed: {}", trimmed);
+        anyhow::bail!("hyprctl {} failed: {}", request, stderr.trim());
     }
 
-    Ok(())
+    format.check_reply(&String::from_utf8_lossy(&output.stdout))
 }
 
-/// Apply all monitors from a profile at runtime
-pub fn apply_runtime(profile: &Profile) -> Result<()> {
-    if !is_running() {
-        return Ok(());
-    }
-
-    // Apply monitors
-    for monitor in &profile.monitors {
-        apply_monitor(monitor)?;
+/// Apply all monitors from a profile at runtime. Failing to apply a monitor
+/// is an error; later best-effort steps (workspace moves, reload, lid,
+/// orphan cleanup) are returned as warnings instead.
+pub fn apply_runtime(compositor: &dyn Compositor, profile: &Profile) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    if !compositor.is_running() {
+        return Ok(warnings);
+    }
+    let dialect = compositor.dialect();
+
+    // Apply monitors, all in one round-trip when the socket allows.
+    // Hyprland 0.55's non-legacy (Lua) parser rejects `hyprctl keyword
+    // monitor`, so there the steps call the native `hl.monitor({...})` API
+    // through `hyprctl eval`; older releases get the keyword.
+    for (monitor, result) in profile
+        .monitors
+        .iter()
+        .zip(compositor.apply_monitors(&profile.monitors))
+    {
+        result.with_context(|| format!("Failed to apply monitor {}", monitor.name))?;
     }
 
     // Wait for monitor changes to take effect
-    thread::sleep(Duration::from_millis(MONITOR_APPLY_DELAY_MS));
+    compositor.sleep(Duration::from_millis(MONITOR_APPLY_DELAY_MS));
 
     // Move existing workspaces to correct monitors
     for ws in &profile.workspaces {
         for attempt in 0..WORKSPACE_MOVE_MAX_RETRIES {
-            match move_workspace(ws.id, &ws.monitor) {
+            match compositor.move_workspace(ws.id, &ws.monitor) {
                 Ok(_) => break,
                 Err(_) if attempt < WORKSPACE_MOVE_MAX_RETRIES - 1 => {
-                    thread::sleep(Duration::from_millis(WORKSPACE_MOVE_RETRY_DELAY_MS));
+                    compositor.sleep(Duration::from_millis(WORKSPACE_MOVE_RETRY_DELAY_MS));
                 }
                 Err(e) => {
-                    eprintln!("Warning: Failed to move workspace {}: {}", ws.id, e);
+                    warnings.push(format!("Failed to move workspace {}: {}", ws.id, e));
                 }
             }
         }
@@ -546,8 +979,8 @@ pub fn apply_runtime(profile: &Profile) -> Result<()> {
     // Hyprland's in-memory state, and `configreloaded` fires for bars/tools
     // that cache workspace rules. Eval-style apply above can't re-evaluate
     // workspace_rule lines, so a reload is the only way to refresh them.
-    if let Err(e) = hyprctl_reload() {
-        eprintln!("Warning: hyprctl reload failed after apply: {}", e);
+    if let Err(e) = compositor.reload() {
+        warnings.push(format!("hyprctl reload failed after apply: {}", e));
     }
 
     // The switch:on:Lid Switch bind is edge-triggered: applying a profile
@@ -556,7 +989,7 @@ pub fn apply_runtime(profile: &Profile) -> Result<()> {
     // monitor from monitors.lua. Re-assert the disable from current lid
     // state. Must run after the reload, which would otherwise undo it.
     if let Some(ref lid) = profile.lid_switch {
-        if lid.enabled && lid_is_closed() {
+        if lid.enabled && crate::events::lid_closed() == Some(true) {
             if let Some(m) = profile
                 .monitors
                 .iter()
@@ -564,11 +997,11 @@ pub fn apply_runtime(profile: &Profile) -> Result<()> {
             {
                 let mut disabled = m.clone();
                 disabled.enabled = false;
-                if let Err(e) = apply_monitor(&disabled) {
-                    eprintln!(
-                        "Warning: Failed to disable {} for closed lid: {}",
+                if let Err(e) = compositor.apply_monitor(&disabled) {
+                    warnings.push(format!(
+                        "Failed to disable {} for closed lid: {}",
                         lid.monitor, e
-                    );
+                    ));
                 }
             }
         }
@@ -576,34 +1009,26 @@ pub fn apply_runtime(profile: &Profile) -> Result<()> {
 
     // Last, after any lid-disable re-homed its workspaces: fold fallback
     // workspaces that appeared mid-transition back into the profile layout.
-    if let Err(e) = cleanup_orphan_workspaces(profile) {
-        eprintln!("Warning: Failed to clean up orphan workspaces: {}", e);
+    match cleanup_orphan_workspaces(compositor, profile, dialect) {
+        Ok(w) => warnings.extend(w),
+        Err(e) => warnings.push(format!("Failed to clean up orphan workspaces: {}", e)),
     }
 
-    Ok(())
-}
-
-/// Whether the laptop lid is currently closed, per the kernel's ACPI lid
-/// device (/proc/acpi/button/lid/*/state). Returns false when no lid device
-/// exists (desktops) or the state can't be read.
-fn lid_is_closed() -> bool {
-    let Ok(entries) = std::fs::read_dir("/proc/acpi/button/lid") else {
-        return false;
-    };
-    for entry in entries.flatten() {
-        if let Ok(state) = std::fs::read_to_string(entry.path().join("state")) {
-            if state.contains("closed") {
-                return true;
-            }
-        }
-    }
-    false
+    Ok(warnings)
 }
 
 /// Run `hyprctl reload` to re-evaluate hyprland.lua (and the monitors.lua
 /// it sources via pcall) so workspace_rule lines and other top-level state
 /// pick up the freshly-written profile.
 fn hyprctl_reload() -> Result<()> {
+    if let Some(stream) = ipc_stream() {
+        let reply = ipc::request(stream, "", "reload")?;
+        if reply.trim() != "ok" {
+            anyhow::bail!("hyprctl reload failed: {}", reply.trim());
+        }
+        return Ok(());
+    }
+
     let output = hyprctl_command()
         .arg("reload")
         .output()
@@ -825,6 +1250,7 @@ pub fn generate_lid_switch(monitors: &[Monitor]) -> Option<LidSwitch> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compositor::WorkspaceRef;
 
     #[test]
     fn arrange_monitors_no_gaps_with_mixed_scales() {
@@ -881,6 +1307,8 @@ mod tests {
                 Workspace { id: 9, monitor: "DP-6".to_string(), default: true },
             ],
             lid_switch: None,
+            revert_timeout: None,
+            hooks: Default::default(),
         };
         let current = vec![
             monitor_with_desc("DP-8", "Display A"),  // was DP-10 in profile
@@ -920,6 +1348,8 @@ mod tests {
                 enabled: true,
                 monitor: "eDP-1".to_string(),
             }),
+            revert_timeout: None,
+            hooks: Default::default(),
         };
         let current = vec![monitor_with_desc("eDP-1", "Laptop")];
         resolve_monitor_names_with(&mut profile, &current);
@@ -944,13 +1374,15 @@ mod tests {
                 enabled: true,
                 monitor: "eDP-1".to_string(),
             }),
+            revert_timeout: None,
+            hooks: Default::default(),
         }
     }
 
     #[test]
     fn generate_config_emits_lua_monitor_calls() {
         let p = make_profile();
-        let out = generate_config(&p);
+        let out = generate_config(&p, ConfigFormat::Lua);
         assert!(out.contains("hl.monitor({ output = \"eDP-1\", mode = \"1920x1200@60\", position = \"0x0\", scale = 1.0 })"), "got:\n{}", out);
         assert!(out.contains("hl.monitor({ output = \"DP-2\", mode = \"3840x2160@60\", position = \"0x0\", scale = 1.5, transform = 1 })"), "got:\n{}", out);
     }
@@ -959,20 +1391,20 @@ mod tests {
     fn generate_config_emits_disabled_monitor() {
         let mut p = make_profile();
         p.monitors[1].enabled = false;
-        let out = generate_config(&p);
+        let out = generate_config(&p, ConfigFormat::Lua);
         assert!(out.contains("hl.monitor({ output = \"DP-2\", disabled = true })"), "got:\n{}", out);
     }
 
     #[test]
     fn generate_config_emits_workspace_rules() {
-        let out = generate_config(&make_profile());
+        let out = generate_config(&make_profile(), ConfigFormat::Lua);
         assert!(out.contains("hl.workspace_rule({ workspace = 1, monitor = \"eDP-1\", default = true })"), "got:\n{}", out);
         assert!(out.contains("hl.workspace_rule({ workspace = 2, monitor = \"DP-2\" })"), "got:\n{}", out);
     }
 
     #[test]
     fn generate_config_emits_native_lid_close_bind() {
-        let out = generate_config(&make_profile());
+        let out = generate_config(&make_profile(), ConfigFormat::Lua);
         assert!(
             out.contains("hl.bind(\"switch:on:Lid Switch\", function() hl.monitor({ output = \"eDP-1\", disabled = true }) end, { locked = true })"),
             "got:\n{}", out
@@ -983,11 +1415,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_legacy_config_emits_hyprlang_rules() {
+        let out = generate_config(&make_profile(), ConfigFormat::Legacy);
+        assert_eq!(
+            out,
+            "# Generated by hyprpier from profile: test\n\
+             # smoke\n\
+             \n\
+             monitor = eDP-1, 1920x1200@60, 0x0, 1.0\n\
+             monitor = DP-2, 3840x2160@60, 0x0, 1.5, transform, 1\n\
+             \n\
+             workspace = 1, monitor:eDP-1, default:true\n\
+             workspace = 2, monitor:DP-2\n\
+             \n\
+             # Lid switch handling\n\
+             bindl = , switch:on:Lid Switch, exec, hyprctl keyword monitor \"eDP-1, disable\"\n\
+             bindl = , switch:off:Lid Switch, exec, hyprctl reload && sleep 0.5 && hyprctl dispatch dpms on eDP-1\n"
+        );
+    }
+
+    #[test]
+    fn legacy_runtime_steps_use_keyword_and_dispatch() {
+        let mut m = Monitor::test_fixture("DP-2", "3840x2160", 1.5, 1);
+        assert_eq!(
+            monitor_step(ConfigFormat::Legacy, &m),
+            "keyword monitor DP-2,3840x2160@60,0x0,1.5,transform,1"
+        );
+        m.enabled = false;
+        assert_eq!(monitor_step(ConfigFormat::Legacy, &m), "keyword monitor DP-2,disable");
+        assert_eq!(
+            move_workspace_step(ConfigFormat::Legacy, 3, "DP-2"),
+            "dispatch moveworkspacetomonitor 3 DP-2"
+        );
+    }
+
+    #[test]
+    fn parses_available_modes() {
+        let json = r#"[{"name": "DP-3", "description": "Dell Inc. DELL U2723QE",
+            "width": 3840, "height": 2160, "refreshRate": 60.0, "x": 0, "y": 0,
+            "scale": 1.5, "transform": 0, "availableModes": ["3840x2160@60.00Hz",
+            "3840x2160@59.94Hz", "3840x2160@59.94Hz", "2560x1440@59.95Hz", "bogus"]}]"#;
+        let outputs: Vec<OutputModes> = serde_json::from_str::<Vec<HyprMonitor>>(json)
+            .unwrap()
+            .into_iter()
+            .map(output_modes)
+            .collect();
+        let modes: Vec<String> = outputs[0].modes.iter().map(Mode::mode_string).collect();
+        assert_eq!(
+            modes,
+            vec!["3840x2160@60", "3840x2160@59.94", "2560x1440@59.95"]
+        );
+        assert_eq!(outputs[0].modes[2].resolution(), "2560x1440");
+        assert_eq!(outputs[0].description.as_deref(), Some("Dell Inc. DELL U2723QE"));
+    }
+
+    #[test]
+    fn monitor_options_round_trip() {
+        let json = r#"[{"name": "DP-2", "description": "LG Electronics LG HDR 4K 0x0001",
+            "width": 3840, "height": 2160, "refreshRate": 59.997, "x": 0, "y": 0,
+            "scale": 1.5, "transform": 0, "vrr": true, "currentFormat": "XRGB2101010",
+            "mirrorOf": "none", "colorManagementPreset": "hdr", "sdrBrightness": 1.2},
+            {"name": "HDMI-A-1", "description": "", "width": 1920, "height": 1080,
+            "refreshRate": 60.0, "x": 0, "y": 0, "scale": 1.0, "transform": 0,
+            "vrr": false, "currentFormat": "XRGB8888", "mirrorOf": "DP-2",
+            "colorManagementPreset": "srgb", "sdrBrightness": 1.0}]"#;
+        let monitors: Vec<Monitor> = serde_json::from_str::<Vec<HyprMonitor>>(json)
+            .unwrap()
+            .into_iter()
+            .map(Monitor::from)
+            .collect();
+        let (lg, tv) = (&monitors[0], &monitors[1]);
+        assert_eq!(
+            lg.options,
+            MonitorOptions {
+                vrr: Some(1),
+                bitdepth: Some(10),
+                cm: Some("hdr".to_string()),
+                sdrbrightness: Some(1.2),
+                mirror: None,
+            }
+        );
+        // Defaults stay unset, so they don't end up pinned in the profile
+        assert_eq!(tv.options.mirror.as_deref(), Some("DP-2"));
+        assert_eq!(tv.options.vrr, None);
+        assert_eq!(tv.options.cm, None);
+
+        assert_eq!(
+            lua_monitor_fields(lg),
+            "output = \"DP-2\", mode = \"3840x2160@60\", position = \"0x0\", scale = 1.5, \
+             bitdepth = 10, vrr = 1, cm = \"hdr\", sdrbrightness = 1.2"
+        );
+        assert_eq!(
+            monitor_step(ConfigFormat::Legacy, tv),
+            "keyword monitor HDMI-A-1,1920x1080@60,0x0,1.0,mirror,DP-2"
+        );
+
+        // Saved and loaded again, and profiles from before the options load
+        let saved = serde_json::to_string(lg).unwrap();
+        let loaded: Monitor = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.options, lg.options);
+        let old: Monitor = serde_json::from_str(
+            r#"{"name": "eDP-1", "resolution": "1920x1200", "refresh_rate": 60.0,
+                "position": {"x": 0, "y": 0}, "mode": "1920x1200@60"}"#,
+        )
+        .unwrap();
+        assert_eq!(old.options, MonitorOptions::default());
+        assert!(!serde_json::to_string(&old).unwrap().contains("vrr"));
+    }
+
+    #[test]
+    fn config_format_from_version() {
+        assert_eq!(parse_version("v0.54.2-b1"), Some((0, 54)));
+        assert_eq!(
+            parse_version("Hyprland 0.55.0 built from branch main at commit abc"),
+            Some((0, 55))
+        );
+        assert_eq!(parse_version("unknown"), None);
+        assert_eq!(ConfigFormat::for_version((0, 54)), ConfigFormat::Legacy);
+        assert_eq!(ConfigFormat::for_version((0, 55)), ConfigFormat::Lua);
+        assert_eq!(ConfigFormat::for_version((1, 0)), ConfigFormat::Lua);
+    }
+
     #[test]
     fn generate_config_emits_reload_plus_dpms_on_open() {
         // Runtime `hl.monitor({...})` cannot re-enable a previously-disabled
         // monitor on Hyprland 0.55. Open handler must reload + dpms-on.
-        let out = generate_config(&make_profile());
+        let out = generate_config(&make_profile(), ConfigFormat::Lua);
         assert!(
             out.contains("hl.bind(\"switch:off:Lid Switch\", function()"),
             "got:\n{}", out
@@ -1015,7 +1569,7 @@ mod tests {
         let mut p = make_profile();
         // Point lid_switch at an output that isn't in the profile
         p.lid_switch.as_mut().unwrap().monitor = "eDP-99".to_string();
-        let out = generate_config(&p);
+        let out = generate_config(&p, ConfigFormat::Lua);
         assert!(
             !out.contains("Lid Switch"),
             "lid binds must be skipped when the referenced output isn't in the profile:\n{}",
@@ -1025,7 +1579,7 @@ mod tests {
 
     #[test]
     fn generate_config_uses_lua_comment_header() {
-        let out = generate_config(&make_profile());
+        let out = generate_config(&make_profile(), ConfigFormat::Lua);
         assert!(out.starts_with("-- Generated by hyprpier from profile: test\n-- smoke"), "got:\n{}", out);
     }
 
@@ -1053,6 +1607,23 @@ mod tests {
         assert_eq!(ws.len(), 10);
     }
 
+    #[test]
+    fn is_monitor_event_matches_v1_and_v2() {
+        assert!(is_monitor_event("monitoradded>>DP-3"));
+        assert!(is_monitor_event("monitorremoved>>DP-3"));
+        assert!(is_monitor_event("monitoraddedv2>>2,DP-3,Dell Inc. DELL U2720Q"));
+        assert!(is_monitor_event("monitorremovedv2>>2,DP-3,Dell Inc. DELL U2720Q"));
+    }
+
+    #[test]
+    fn is_monitor_event_ignores_other_events() {
+        assert!(!is_monitor_event("workspace>>2"));
+        assert!(!is_monitor_event("focusedmon>>DP-3,2"));
+        // Prefix match would wrongly catch a hypothetical future event name
+        assert!(!is_monitor_event("monitoraddedv3>>DP-3"));
+        assert!(!is_monitor_event(""));
+    }
+
     #[test]
     fn arrange_monitors_unscaled_unchanged_behavior() {
         let mut monitors = vec![
@@ -1080,24 +1651,26 @@ mod tests {
                 Workspace { id: 6, monitor: "DP-6".to_string(), default: true },
             ],
             lid_switch: None,
+            revert_timeout: None,
+            hooks: Default::default(),
         }
     }
 
-    fn ws(id: i64, monitor: &str) -> HyprWorkspace {
-        HyprWorkspace { id, monitor: monitor.to_string() }
+    fn ws(id: i64, monitor: &str) -> LiveWorkspace {
+        LiveWorkspace { id, monitor: monitor.to_string() }
     }
 
-    fn client(address: &str, ws_id: i64) -> HyprClient {
-        HyprClient {
+    fn client(address: &str, ws_id: i64) -> LiveClient {
+        LiveClient {
             address: address.to_string(),
-            workspace: HyprWorkspaceRef { id: ws_id },
+            workspace: WorkspaceRef { id: ws_id },
         }
     }
 
-    fn mon(focused: bool, active_ws: i64) -> HyprMonitorState {
-        HyprMonitorState {
+    fn mon(focused: bool, active_ws: i64) -> LiveMonitorState {
+        LiveMonitorState {
             focused,
-            active_workspace: HyprWorkspaceRef { id: active_ws },
+            active_workspace: WorkspaceRef { id: active_ws },
         }
     }
 
@@ -1106,6 +1679,7 @@ mod tests {
         // The dock-while-lid-closed aftermath: fallback workspace 11 grabbed
         // DP-6 with a window on it, while the user is focused on DP-2/ws 1.
         let exprs = plan_orphan_cleanup(
+            Dialect::Hyprland(ConfigFormat::Lua),
             &orphan_profile(),
             &[ws(1, "DP-2"), ws(2, "DP-2"), ws(11, "DP-6")],
             &[client("0xabc", 1), client("0xdef", 11)],
@@ -1121,11 +1695,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn orphan_cleanup_legacy_uses_dispatchers() {
+        let exprs = plan_orphan_cleanup(
+            Dialect::Hyprland(ConfigFormat::Legacy),
+            &orphan_profile(),
+            &[ws(1, "DP-2"), ws(11, "DP-6")],
+            &[client("0xdef", 11)],
+            &[mon(true, 1), mon(false, 11)],
+        );
+        assert_eq!(
+            exprs,
+            vec![
+                "dispatch movetoworkspacesilent 6,address:0xdef",
+                "dispatch workspace 6",
+                "dispatch workspace 1",
+            ]
+        );
+    }
+
     #[test]
     fn orphan_cleanup_focuses_focused_monitor_last() {
         // Orphan is active on the focused monitor: switch it last, no
         // extra re-focus needed.
         let exprs = plan_orphan_cleanup(
+            Dialect::Hyprland(ConfigFormat::Lua),
             &orphan_profile(),
             &[ws(1, "DP-2"), ws(11, "DP-6")],
             &[],
@@ -1139,6 +1733,7 @@ mod tests {
         // eDP-1 has no profile workspaces; its fallback workspace is the
         // only one it can show, so leave it (and its windows) alone.
         let exprs = plan_orphan_cleanup(
+            Dialect::Hyprland(ConfigFormat::Lua),
             &orphan_profile(),
             &[ws(1, "DP-2"), ws(6, "DP-6"), ws(12, "eDP-1")],
             &[client("0xabc", 12)],
@@ -1147,9 +1742,27 @@ mod tests {
         assert!(exprs.is_empty(), "got: {:?}", exprs);
     }
 
+    #[test]
+    fn workspace_moves_only_for_live_workspaces_on_wrong_monitor() {
+        let moves = plan_workspace_moves(
+            &orphan_profile(),
+            &[ws(1, "DP-2"), ws(2, "DP-6"), ws(11, "DP-6")],
+        );
+        // ws 1 is already home, ws 6 doesn't exist yet, ws 11 isn't ours
+        assert_eq!(
+            moves,
+            vec![WorkspaceMove {
+                id: 2,
+                from: "DP-6".to_string(),
+                to: "DP-2".to_string(),
+            }]
+        );
+    }
+
     #[test]
     fn orphan_cleanup_noop_when_workspaces_match_profile() {
         let exprs = plan_orphan_cleanup(
+            Dialect::Hyprland(ConfigFormat::Lua),
             &orphan_profile(),
             &[ws(1, "DP-2"), ws(2, "DP-2"), ws(6, "DP-6")],
             &[client("0xabc", 1)],
@@ -0,0 +1,135 @@
+//! Direct client for Hyprland's request socket (.socket.sock)
+//!
+//! Speaks the same protocol as hyprctl: write one request, read the reply
+//! until Hyprland closes the connection. Talking to the socket directly
+//! saves a process spawn per query, which adds up when an apply evaluates
+//! one expression per monitor and per workspace, and keeps the daemon
+//! working when hyprctl isn't on its PATH.
+
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+const BATCH_PREFIX: &str = "[[BATCH]]";
+/// Hyprland joins the replies of a batch request with blank lines
+const BATCH_REPLY_SEPARATOR: &str = "\n\n\n";
+/// A wedged compositor must not hang an apply (or the daemon) forever
+const IO_TIMEOUT_MS: u64 = 5000;
+
+/// Connect to the request socket. Callers treat a failure here as "socket
+/// unavailable" and fall back to the hyprctl binary; nothing has been sent.
+pub fn connect(path: &Path) -> std::io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS)))?;
+    Ok(stream)
+}
+
+/// Send a single request on a fresh connection and return the reply.
+///
+/// Requests use hyprctl's `<flags>/<command>` form. The separator is always
+/// present, even with no flags, so a `/` inside an eval expression is never
+/// mistaken for the end of a flag list.
+pub fn request(stream: UnixStream, flags: &str, command: &str) -> Result<String> {
+    exchange(stream, &format!("{}/{}", flags, command))
+}
+
+/// Send several commands as one `[[BATCH]]` request and return one reply
+/// per command.
+///
+/// Batch commands are separated by `;`, so a command containing one can't
+/// be batched; callers must check with `can_batch` first.
+pub fn batch(stream: UnixStream, commands: &[String]) -> Result<Vec<String>> {
+    let joined = commands
+        .iter()
+        .map(|c| format!("/{}", c))
+        .collect::<Vec<_>>()
+        .join(";");
+    let reply = exchange(stream, &format!("{}{}", BATCH_PREFIX, joined))?;
+    Ok(split_batch_reply(&reply, commands.len()))
+}
+
+/// Write one message and read until Hyprland closes the connection
+fn exchange(mut stream: UnixStream, msg: &str) -> Result<String> {
+    stream
+        .write_all(msg.as_bytes())
+        .context("Failed to write to Hyprland socket")?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .context("Failed to read from Hyprland socket")?;
+    Ok(reply)
+}
+
+/// Whether every command can travel in a single batch request
+pub fn can_batch(commands: &[String]) -> bool {
+    commands.iter().all(|c| !c.contains(';'))
+}
+
+/// Split a batch reply into per-command replies. If the count doesn't line
+/// up (an unexpected reply format), every command gets the whole reply so
+/// an error anywhere in it is still seen by each caller.
+fn split_batch_reply(reply: &str, count: usize) -> Vec<String> {
+    let parts: Vec<String> = reply
+        .split(BATCH_REPLY_SEPARATOR)
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() == count {
+        parts
+    } else {
+        vec![reply.trim().to_string(); count]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn split_batch_reply_one_per_command() {
+        assert_eq!(split_batch_reply("ok\n\n\nok\n\n\n", 2), vec!["ok", "ok"]);
+        assert_eq!(
+            split_batch_reply("ok\n\n\nerror: bad output", 2),
+            vec!["ok", "error: bad output"]
+        );
+    }
+
+    #[test]
+    fn split_batch_reply_mismatch_repeats_whole_reply() {
+        assert_eq!(
+            split_batch_reply("error: oops", 3),
+            vec!["error: oops", "error: oops", "error: oops"]
+        );
+    }
+
+    #[test]
+    fn can_batch_rejects_semicolons() {
+        assert!(can_batch(&["eval hl.monitor({ output = \"DP-1\" })".to_string()]));
+        assert!(!can_batch(&["eval a(); b()".to_string()]));
+    }
+
+    #[test]
+    fn request_round_trips_over_socket() {
+        let path = std::env::temp_dir().join(format!("hyprpier-ipc-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 256];
+            let n = conn.read(&mut buf).unwrap();
+            let req = String::from_utf8_lossy(&buf[..n]).to_string();
+            conn.write_all(b"[]").unwrap();
+            req
+        });
+
+        let reply = request(connect(&path).unwrap(), "j", "monitors all").unwrap();
+        assert_eq!(reply, "[]");
+        assert_eq!(server.join().unwrap(), "j/monitors all");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,681 @@
+//! kanshi config import and export
+//!
+//! `hyprpier import kanshi <file>` turns each `profile { output ... }` block
+//! into a hyprpier profile; `hyprpier export kanshi` writes every saved
+//! profile back out as kanshi config. kanshi matches outputs by connector
+//! name or by "make model serial" description, the same description
+//! hyprpier stores, so described outputs survive the trip both ways.
+//! Workspaces and lid handling have no kanshi equivalent; profile `exec`
+//! lines map to post_apply hooks.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::compositor;
+use crate::config;
+use crate::profile::{self, validate_profile_name, Monitor, MonitorOptions, Position, Profile};
+use crate::sway::TRANSFORMS;
+
+/// A word of kanshi config, with its byte span in the source
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word {
+        text: String,
+        start: usize,
+        end: usize,
+    },
+    Open,
+    Close,
+    Newline,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        match c {
+            '\n' => {
+                chars.next();
+                tokens.push((line, Token::Newline));
+                line += 1;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                while chars.peek().is_some_and(|&(_, c)| c != '\n') {
+                    chars.next();
+                }
+            }
+            '{' => {
+                chars.next();
+                tokens.push((line, Token::Open));
+            }
+            '}' => {
+                chars.next();
+                tokens.push((line, Token::Close));
+            }
+            '"' => {
+                chars.next();
+                let mut word = String::new();
+                let end = loop {
+                    match chars.next() {
+                        Some((j, '"')) => break j + 1,
+                        Some((_, '\n')) | None => {
+                            anyhow::bail!("line {}: unterminated quoted string", line)
+                        }
+                        Some((_, c)) => word.push(c),
+                    }
+                };
+                tokens.push((
+                    line,
+                    Token::Word {
+                        text: word,
+                        start: i,
+                        end,
+                    },
+                ));
+            }
+            _ => {
+                let mut end = i;
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | '"' | '#') {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push((
+                    line,
+                    Token::Word {
+                        text: text[i..end].to_string(),
+                        start: i,
+                        end,
+                    },
+                ));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// One directive: its words, its source text, and its `{ ... }` block
+#[derive(Debug)]
+struct Directive {
+    line: usize,
+    words: Vec<String>,
+    raw: String,
+    block: Option<Vec<Directive>>,
+}
+
+/// Parse directives until the closing brace (`nested`) or end of input
+fn parse_block(
+    text: &str,
+    tokens: &mut std::iter::Peekable<std::vec::IntoIter<(usize, Token)>>,
+    nested: bool,
+) -> Result<Vec<Directive>> {
+    let mut directives = Vec::new();
+    let mut current: Option<(usize, Vec<String>, usize, usize)> = None;
+    let finish = |current: &mut Option<(usize, Vec<String>, usize, usize)>,
+                  block: Option<Vec<Directive>>,
+                  out: &mut Vec<Directive>| {
+        if let Some((line, words, start, end)) = current.take() {
+            out.push(Directive {
+                line,
+                words,
+                raw: text[start..end].to_string(),
+                block,
+            });
+        }
+    };
+
+    while let Some((line, token)) = tokens.next() {
+        match token {
+            Token::Word {
+                text: word,
+                start,
+                end,
+            } => match current {
+                Some((_, ref mut words, _, ref mut last)) => {
+                    words.push(word);
+                    *last = end;
+                }
+                None => current = Some((line, vec![word], start, end)),
+            },
+            Token::Newline => finish(&mut current, None, &mut directives),
+            Token::Open => {
+                if current.is_none() {
+                    anyhow::bail!("line {}: block without a directive", line);
+                }
+                let block = parse_block(text, tokens, true)?;
+                finish(&mut current, Some(block), &mut directives);
+            }
+            Token::Close => {
+                if !nested {
+                    anyhow::bail!("line {}: unexpected '}}'", line);
+                }
+                finish(&mut current, None, &mut directives);
+                return Ok(directives);
+            }
+        }
+    }
+    if nested {
+        anyhow::bail!("unexpected end of file: missing '}}'");
+    }
+    finish(&mut current, None, &mut directives);
+    Ok(directives)
+}
+
+/// Settings of one `output` line inside a kanshi profile
+#[derive(Debug, Default, PartialEq)]
+struct OutputConfig {
+    criteria: String,
+    enabled: Option<bool>,
+    /// Resolution ("1920x1080") and refresh rate if given
+    mode: Option<(String, Option<f64>)>,
+    position: Option<(i32, i32)>,
+    scale: Option<f64>,
+    transform: Option<u8>,
+}
+
+fn parse_output(words: &[String], line: usize, warnings: &mut Vec<String>) -> Result<OutputConfig> {
+    let mut words = words.iter().map(String::as_str);
+    let criteria = words
+        .next()
+        .with_context(|| format!("line {}: output without a name", line))?;
+    let mut output = OutputConfig {
+        criteria: criteria.to_string(),
+        ..OutputConfig::default()
+    };
+    let arg = |words: &mut dyn Iterator<Item = &str>, option: &str| {
+        words
+            .next()
+            .with_context(|| format!("line {}: {} needs a value", line, option))
+            .map(str::to_string)
+    };
+
+    while let Some(option) = words.next() {
+        match option {
+            "enable" => output.enabled = Some(true),
+            "disable" => output.enabled = Some(false),
+            "mode" => {
+                let mut value = arg(&mut words, option)?;
+                if value == "--custom" {
+                    value = arg(&mut words, option)?;
+                }
+                output.mode = Some(
+                    parse_mode(&value)
+                        .with_context(|| format!("line {}: invalid mode '{}'", line, value))?,
+                );
+            }
+            "position" => {
+                let value = arg(&mut words, option)?;
+                let (x, y) = match value.split_once(',') {
+                    Some((x, y)) => (x.to_string(), y.to_string()),
+                    None => (value, arg(&mut words, option)?),
+                };
+                let parse = |v: &str| {
+                    v.trim()
+                        .parse::<i32>()
+                        .with_context(|| format!("line {}: invalid position '{}'", line, v))
+                };
+                output.position = Some((parse(&x)?, parse(&y)?));
+            }
+            "scale" => {
+                let value = arg(&mut words, option)?;
+                output.scale = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("line {}: invalid scale '{}'", line, value))?,
+                );
+            }
+            "transform" => {
+                let value = arg(&mut words, option)?;
+                let index = TRANSFORMS
+                    .iter()
+                    .position(|&t| t == value)
+                    .with_context(|| format!("line {}: invalid transform '{}'", line, value))?;
+                output.transform = Some(index as u8);
+            }
+            "adaptive_sync" | "alias" => {
+                let value = arg(&mut words, option)?;
+                warnings.push(format!(
+                    "line {}: ignored '{} {}' for {}",
+                    line, option, value, criteria
+                ));
+            }
+            other => anyhow::bail!("line {}: unknown output option '{}'", line, other),
+        }
+    }
+    Ok(output)
+}
+
+/// "1920x1080", "1920x1080@60" or "1920x1080@59.951Hz"
+fn parse_mode(value: &str) -> Option<(String, Option<f64>)> {
+    let (resolution, refresh) = match value.split_once('@') {
+        Some((res, rate)) => {
+            let rate = rate.strip_suffix("Hz").unwrap_or(rate);
+            (res, Some(rate.parse::<f64>().ok()?))
+        }
+        None => (value, None),
+    };
+    let (w, h) = resolution.split_once('x')?;
+    w.parse::<u32>().ok()?;
+    h.parse::<u32>().ok()?;
+    Some((resolution.to_string(), refresh))
+}
+
+/// Whether an output criteria names a connector ("eDP-1", "HDMI-A-1")
+/// rather than a description
+fn is_connector(criteria: &str) -> bool {
+    let Some((kind, index)) = criteria.rsplit_once('-') else {
+        return false;
+    };
+    !kind.is_empty()
+        && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !index.is_empty()
+        && index.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Profiles parsed from a kanshi config, plus anything that didn't carry over
+#[derive(Debug, Serialize)]
+pub struct Import {
+    pub profiles: Vec<Profile>,
+    pub warnings: Vec<String>,
+}
+
+/// Parse kanshi config into profiles. Described outputs that are connected
+/// take their port name from `connected`; outputs without a mode take the
+/// connected monitor's current mode, or Hyprland's `preferred`.
+pub fn parse(text: &str, connected: &[Monitor]) -> Result<Import> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    let directives = parse_block(text, &mut tokens, false)?;
+    let mut import = Import {
+        profiles: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    for directive in directives {
+        match (directive.words[0].as_str(), directive.block) {
+            ("profile", Some(block)) => {
+                let name = match directive.words.get(1) {
+                    Some(name) => name.clone(),
+                    None => format!("kanshi-{}", import.profiles.len() + 1),
+                };
+                let profile = build_profile(name, block, connected, &mut import.warnings)?;
+                import.profiles.push(profile);
+            }
+            (other, _) => import.warnings.push(format!(
+                "line {}: ignored top-level '{}' (only profile blocks are imported)",
+                directive.line, other
+            )),
+        }
+    }
+    Ok(import)
+}
+
+fn build_profile(
+    name: String,
+    block: Vec<Directive>,
+    connected: &[Monitor],
+    warnings: &mut Vec<String>,
+) -> Result<Profile> {
+    let mut profile = Profile::new(name);
+    profile.description = Some("Imported from kanshi".to_string());
+
+    for directive in block {
+        match directive.words[0].as_str() {
+            "output" => {
+                let output = parse_output(&directive.words[1..], directive.line, warnings)?;
+                if output.criteria == "*" {
+                    warnings.push(format!(
+                        "line {}: skipped wildcard output in profile {}",
+                        directive.line, profile.name
+                    ));
+                    continue;
+                }
+                profile
+                    .monitors
+                    .push(build_monitor(output, connected, warnings));
+            }
+            "exec" => {
+                let command = directive.raw["exec".len()..].trim().to_string();
+                profile.hooks.post_apply.push(command);
+            }
+            other => warnings.push(format!(
+                "line {}: ignored '{}' in profile {}",
+                directive.line, other, profile.name
+            )),
+        }
+    }
+    Ok(profile)
+}
+
+fn build_monitor(
+    output: OutputConfig,
+    connected: &[Monitor],
+    warnings: &mut Vec<String>,
+) -> Monitor {
+    let (name, description, live) = if is_connector(&output.criteria) {
+        let live = connected.iter().find(|m| m.name == output.criteria);
+        (output.criteria.clone(), None, live)
+    } else {
+        let live = connected
+            .iter()
+            .find(|m| m.description.as_deref() == Some(output.criteria.as_str()));
+        if live.is_none() {
+            warnings.push(format!(
+                "\"{}\" isn't connected; its port is resolved by description when applied",
+                output.criteria
+            ));
+        }
+        let name = live.map_or_else(|| output.criteria.clone(), |m| m.name.clone());
+        (name, Some(output.criteria.clone()), live)
+    };
+
+    let (resolution, refresh_rate, mode) = match output.mode {
+        Some((resolution, Some(rate))) => {
+            let mode = format!("{}@{}", resolution, rate);
+            (resolution, rate, mode)
+        }
+        Some((resolution, None)) => (resolution.clone(), 0.0, resolution),
+        None => match live {
+            Some(m) => (m.resolution.clone(), m.refresh_rate, m.mode.clone()),
+            None => ("preferred".to_string(), 0.0, "preferred".to_string()),
+        },
+    };
+    let (x, y) = output.position.unwrap_or((0, 0));
+
+    Monitor {
+        name,
+        description,
+        enabled: output.enabled.unwrap_or(true),
+        resolution,
+        refresh_rate,
+        position: Position { x, y },
+        scale: output.scale.unwrap_or(1.0),
+        transform: output.transform.unwrap_or(0),
+        mode,
+        options: MonitorOptions::default(),
+    }
+}
+
+/// Quote a word for kanshi config if it isn't a bare word
+fn quote(word: &str) -> String {
+    if !word.is_empty()
+        && !word
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '{' | '}' | '"' | '#'))
+    {
+        word.to_string()
+    } else {
+        format!("\"{}\"", word)
+    }
+}
+
+/// One kanshi `output` line for a monitor, matched by description when
+/// the profile has one
+fn output_line(monitor: &Monitor) -> String {
+    let criteria = quote(monitor.description.as_deref().unwrap_or(&monitor.name));
+    if !monitor.enabled {
+        return format!("output {} disable", criteria);
+    }
+    let mut fields = vec![format!("output {} enable", criteria)];
+    if parse_mode(&monitor.resolution).is_some() {
+        if monitor.refresh_rate > 0.0 {
+            fields.push(format!(
+                "mode {}@{:.3}Hz",
+                monitor.resolution, monitor.refresh_rate
+            ));
+        } else {
+            fields.push(format!("mode {}", monitor.resolution));
+        }
+    }
+    fields.push(format!(
+        "position {},{}",
+        monitor.position.x, monitor.position.y
+    ));
+    fields.push(format!("scale {}", monitor.scale));
+    if let Some(transform) = TRANSFORMS.get(usize::from(monitor.transform)) {
+        fields.push(format!("transform {}", transform));
+    }
+    fields.join(" ")
+}
+
+/// Render profiles as kanshi config. Returns the config and a note for
+/// each setting kanshi can't express.
+pub fn export(profiles: &[Profile]) -> (String, Vec<String>) {
+    let mut lines = vec!["# Generated by hyprpier".to_string()];
+    let mut warnings = Vec::new();
+
+    for profile in profiles {
+        lines.push(String::new());
+        lines.push(format!("profile {} {{", quote(&profile.name)));
+        for monitor in &profile.monitors {
+            lines.push(format!("    {}", output_line(monitor)));
+        }
+        for command in &profile.hooks.post_apply {
+            lines.push(format!("    exec {}", command));
+        }
+        lines.push("}".to_string());
+
+        if !profile.workspaces.is_empty() || profile.lid_switch.is_some() {
+            warnings.push(format!(
+                "{}: workspace and lid settings have no kanshi equivalent",
+                profile.name
+            ));
+        }
+        if profile
+            .monitors
+            .iter()
+            .any(|m| m.options != MonitorOptions::default())
+        {
+            warnings.push(format!(
+                "{}: vrr, bitdepth, cm, sdrbrightness and mirror aren't exported",
+                profile.name
+            ));
+        }
+        if !profile.hooks.pre_apply.is_empty() || !profile.hooks.on_fail.is_empty() {
+            warnings.push(format!(
+                "{}: only post_apply hooks are exported (as exec)",
+                profile.name
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    (lines.join("\n"), warnings)
+}
+
+/// What `import kanshi` did with each parsed profile
+#[derive(Debug, Serialize)]
+struct ImportSummary {
+    imported: Vec<String>,
+    /// Profiles left alone because one with that name exists
+    skipped: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// `hyprpier import kanshi <file>`: save each kanshi profile as a hyprpier
+/// profile, leaving existing profiles alone unless `force`
+pub fn import_file(path: &std::path::Path, force: bool, json: bool) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let compositor = compositor::detect();
+    let connected = if compositor.is_running() {
+        compositor.monitors(true).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let import =
+        parse(&text, &connected).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut summary = ImportSummary {
+        imported: Vec::new(),
+        skipped: Vec::new(),
+        warnings: import.warnings,
+    };
+    for profile in import.profiles {
+        if let Err(e) = validate_profile_name(&profile.name) {
+            summary
+                .warnings
+                .push(format!("skipped profile \"{}\": {}", profile.name, e));
+            continue;
+        }
+        if !force && config::profile_path(&profile.name)?.exists() {
+            summary.skipped.push(profile.name);
+            continue;
+        }
+        profile.save()?;
+        summary.imported.push(profile.name);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    for name in &summary.imported {
+        println!("Imported profile: {}", name);
+    }
+    for name in &summary.skipped {
+        println!(
+            "Skipped existing profile: {} (use --force to replace)",
+            name
+        );
+    }
+    for warning in &summary.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// `hyprpier export kanshi`: print all saved profiles as kanshi config
+pub fn export_all(json: bool) -> Result<()> {
+    let profiles = profile::list_profiles()?
+        .iter()
+        .map(|name| Profile::load(name))
+        .collect::<Result<Vec<_>>>()?;
+    let (config, warnings) = export(&profiles);
+
+    if json {
+        let out = serde_json::json!({ "config": config, "warnings": warnings });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+    print!("{}", config);
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# Home office
+profile desk {
+    output eDP-1 disable
+    output "Dell Inc. DELL U2720Q 8KX" mode 3840x2160@59.997Hz position 0,0 scale 1.5
+    output "Goldstar LG HDR 4K 0x0001" mode --custom 2560x1440 position 2560,0 transform 90 # portrait
+    exec notify-send "Docked at desk"
+}
+
+profile {
+    output eDP-1 enable scale 1.25 adaptive_sync on
+}
+"#;
+
+    fn connected() -> Vec<Monitor> {
+        let mut dell = Monitor::test_fixture("DP-3", "3840x2160", 1.5, 0);
+        dell.description = Some("Dell Inc. DELL U2720Q 8KX".to_string());
+        let laptop = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        vec![dell, laptop]
+    }
+
+    #[test]
+    fn parses_profiles_and_outputs() {
+        let import = parse(CONFIG, &connected()).unwrap();
+        assert_eq!(import.profiles.len(), 2);
+
+        let desk = &import.profiles[0];
+        assert_eq!(desk.name, "desk");
+        let names: Vec<&str> = desk.monitors.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["eDP-1", "DP-3", "Goldstar LG HDR 4K 0x0001"]);
+
+        assert!(!desk.monitors[0].enabled);
+        let dell = &desk.monitors[1];
+        assert_eq!(
+            dell.description.as_deref(),
+            Some("Dell Inc. DELL U2720Q 8KX")
+        );
+        assert_eq!(dell.mode, "3840x2160@59.997");
+        assert_eq!(dell.scale, 1.5);
+        let lg = &desk.monitors[2];
+        assert_eq!((lg.position.x, lg.position.y), (2560, 0));
+        assert_eq!(lg.transform, 1);
+        assert_eq!(lg.mode, "2560x1440");
+
+        assert_eq!(desk.hooks.post_apply, ["notify-send \"Docked at desk\""]);
+
+        // Unnamed profile; mode comes from the connected panel
+        let laptop = &import.profiles[1];
+        assert_eq!(laptop.name, "kanshi-2");
+        assert_eq!(laptop.monitors[0].mode, "1920x1200@60");
+        assert_eq!(laptop.monitors[0].scale, 1.25);
+
+        // The disconnected LG and the adaptive_sync option are reported
+        assert_eq!(import.warnings.len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_config() {
+        assert!(parse("profile desk {\n output eDP-1 disable\n", &[]).is_err());
+        assert!(parse("profile desk {\n output eDP-1 rotate 90\n}\n", &[]).is_err());
+        assert!(parse("profile desk {\n output \"eDP-1 disable\n}\n", &[]).is_err());
+        assert!(parse("}\n", &[]).is_err());
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let import = parse(CONFIG, &connected()).unwrap();
+        let (config, warnings) = export(&import.profiles);
+        assert!(warnings.is_empty());
+        assert!(config.contains(
+            "output \"Dell Inc. DELL U2720Q 8KX\" enable mode 3840x2160@59.997Hz position 0,0 scale 1.5 transform normal"
+        ));
+        assert!(config.contains("    output eDP-1 disable\n"));
+
+        let again = parse(&config, &connected()).unwrap();
+        assert_eq!(again.profiles.len(), 2);
+        for (a, b) in import.profiles.iter().zip(&again.profiles) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.hooks.post_apply, b.hooks.post_apply);
+            let summary = |p: &Profile| -> Vec<(String, bool, String, f64, u8)> {
+                p.monitors
+                    .iter()
+                    .map(|m| {
+                        (
+                            m.name.clone(),
+                            m.enabled,
+                            m.resolution.clone(),
+                            m.scale,
+                            m.transform,
+                        )
+                    })
+                    .collect()
+            };
+            assert_eq!(summary(a), summary(b));
+        }
+    }
+
+    #[test]
+    fn connector_names_are_told_apart_from_descriptions() {
+        assert!(is_connector("eDP-1"));
+        assert!(is_connector("HDMI-A-1"));
+        assert!(!is_connector("Dell Inc. DELL U2720Q 8KX"));
+        assert!(!is_connector("*"));
+    }
+}
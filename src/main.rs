@@ -1,12 +1,26 @@
 mod apply;
+mod bolt;
 mod cli;
+mod compositor;
 mod config;
 mod daemon;
 mod dock;
+mod doctor;
+mod events;
+mod history;
+mod hooks;
 mod hyprland;
+mod ipc;
+mod kanshi;
 mod metadata;
+mod plan;
 mod profile;
+mod protocol;
+mod revert;
+mod save;
 mod setup;
+mod sway;
+mod sysfs;
 mod thunderbolt;
 mod thunderbolt_cli;
 mod tui;
@@ -14,24 +28,97 @@ mod tui;
 use anyhow::Result;
 use clap::Parser;
 
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, ExternalFormat};
+use history::Trigger;
+use protocol::Request;
 
-fn main() -> Result<()> {
+fn main() {
     let cli = Cli::parse();
+    let json = cli.json;
+
+    if let Err(e) = run(cli) {
+        if json {
+            // Scripts get the error on stdout in the same shape as everything else
+            println!("{}", serde_json::json!({ "error": format!("{:#}", e) }));
+        } else {
+            eprintln!("Error: {:?}", e);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let json = cli.json;
 
     match cli.command {
         Commands::Apply {
             profile,
             auto,
             no_runtime,
+            dry_run,
+            confirm,
+            previous,
         } => {
-            if auto {
-                apply::apply_auto()?;
+            let profile = if previous {
+                Some(history::previous_profile()?)
+            } else {
+                profile
+            };
+            if dry_run {
+                let Some(name) = profile else {
+                    anyhow::bail!("--dry-run needs a profile name");
+                };
+                apply::dry_run(&name, json)?;
+            } else if auto {
+                // A running daemon does the apply so it can't race one of its own
+                let outcome = match daemon::request(&Request::ApplyAuto)? {
+                    Some(data) => {
+                        let outcome: apply::AutoOutcome = serde_json::from_value(data)?;
+                        if !json {
+                            println!("{}", outcome.describe());
+                        }
+                        outcome
+                    }
+                    None => apply::apply_auto(json, Trigger::Cli, false)?,
+                };
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&outcome)?);
+                }
             } else if let Some(name) = profile {
-                apply::apply_profile(&name, no_runtime)?;
+                // Confirmation needs this terminal, so those applies stay local
+                let routed = if apply::wants_confirmation(&name, confirm) {
+                    None
+                } else {
+                    daemon::request(&Request::Apply {
+                        profile: name.clone(),
+                        no_runtime,
+                    })?
+                };
+                let report = match routed {
+                    Some(data) => {
+                        let report: apply::ApplyReport = serde_json::from_value(data)?;
+                        if !json {
+                            println!("Applied profile: {}", report.profile);
+                            for warning in &report.warnings {
+                                eprintln!("Warning: {}", warning);
+                            }
+                        }
+                        report
+                    }
+                    None => apply::apply_profile(
+                        &name,
+                        no_runtime,
+                        confirm,
+                        json,
+                        Trigger::Cli,
+                        false,
+                    )?,
+                };
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
             } else {
-                eprintln!("Error: Either specify a profile name or use --auto");
-                std::process::exit(1);
+                anyhow::bail!("Either specify a profile name or use --auto or --previous");
             }
         }
 
@@ -41,26 +128,88 @@ fn main() -> Result<()> {
         }
 
         Commands::List => {
-            apply::list_profiles()?;
+            apply::list_profiles(json)?;
         }
 
         Commands::Current => {
-            apply::show_current()?;
+            apply::show_current(json)?;
+        }
+
+        Commands::Save {
+            name,
+            link_dock,
+            undocked,
+            keep_positions,
+            force,
+        } => {
+            save::save_live(&name, link_dock, undocked, keep_positions, force, json)?;
+        }
+
+        Commands::History { limit } => {
+            history::show(limit, json)?;
         }
 
-        Commands::Thunderbolt { list, status } => {
-            if status {
-                thunderbolt_cli::show_status()?;
-            } else if list {
-                thunderbolt_cli::list_devices()?;
+        Commands::Thunderbolt {
+            list,
+            tree,
+            status,
+            trust,
+            untrust,
+        } => {
+            if let Some(uuid) = trust {
+                thunderbolt_cli::set_trusted(&uuid, true, json)?;
+            } else if let Some(uuid) = untrust {
+                thunderbolt_cli::set_trusted(&uuid, false, json)?;
+            } else if status {
+                thunderbolt_cli::show_status(json)?;
+            } else if list || tree {
+                thunderbolt_cli::list_devices(json, tree)?;
             } else {
                 // Default to showing status if no flags provided
-                thunderbolt_cli::show_status()?;
+                thunderbolt_cli::show_status(json)?;
             }
         }
 
-        Commands::Setup { uninstall, resume } => {
-            if resume {
+        Commands::Import {
+            format,
+            file,
+            force,
+        } => match format {
+            ExternalFormat::Kanshi => kanshi::import_file(&file, force, json)?,
+        },
+
+        Commands::Export { format } => match format {
+            ExternalFormat::Kanshi => kanshi::export_all(json)?,
+        },
+
+        Commands::Setup {
+            uninstall,
+            resume,
+            daemon,
+        } => {
+            if daemon {
+                // Daemon user service management
+                if uninstall {
+                    setup::uninstall_daemon_service()?;
+                    println!("Daemon service disabled");
+                } else {
+                    setup::install_daemon_service()?;
+                    println!("Daemon service enabled and started (hyprpier.service)");
+                    if setup::graphical_session_active() {
+                        println!(
+                            "Remove any `exec-once = hyprpier daemon` line so it doesn't start \
+                             twice."
+                        );
+                    } else {
+                        println!(
+                            "graphical-session.target isn't active in this session, so the \
+                             service won't start at your next login. Start the compositor \
+                             through uwsm (or another session that activates the target), or \
+                             keep `exec-once = hyprpier daemon` instead."
+                        );
+                    }
+                }
+            } else if resume {
                 // Resume service management
                 if uninstall {
                     setup::uninstall_resume_service()?;
@@ -77,6 +226,16 @@ fn main() -> Result<()> {
             }
         }
 
+        Commands::Doctor => {
+            if !doctor::run(json)? {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Watch => {
+            daemon::watch(json)?;
+        }
+
         Commands::Daemon => {
             daemon::Daemon::new()?.run()?;
         }
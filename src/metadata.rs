@@ -4,6 +4,42 @@ use std::collections::HashMap;
 use std::fs;
 
 use crate::config;
+use crate::hyprland::ConfigFormat;
+use crate::thunderbolt;
+
+/// A profile for when every one of `docks` is connected at once, e.g. a
+/// desk dock plus the display hub daisy-chained behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DockCombo {
+    /// Dock UUIDs
+    pub docks: Vec<String>,
+    pub profile: String,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// A dock link or combination whose docks are all connected
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DockRule {
+    pub docks: Vec<String>,
+    pub profile: String,
+    pub priority: i32,
+}
+
+/// The rule auto-apply picked, and the matching rules it beat
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DockChoice {
+    pub chosen: DockRule,
+    pub passed_over: Vec<PassedOver>,
+}
+
+/// A matching rule that lost, and the tie-break it lost on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PassedOver {
+    #[serde(flatten)]
+    pub rule: DockRule,
+    pub reason: &'static str,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Metadata {
@@ -13,8 +49,34 @@ pub struct Metadata {
     pub last_modified: Option<String>,
     #[serde(default)]
     pub dock_profiles: HashMap<String, String>, // uuid -> profile name
+    /// Priority of a dock's link when several linked docks are connected
+    /// (uuid -> priority, higher wins; unlisted links are 0)
+    #[serde(default)]
+    pub dock_priorities: HashMap<String, i32>,
+    /// Profiles for combinations of docks connected together
+    #[serde(default)]
+    pub dock_combos: Vec<DockCombo>,
+    /// Thunderbolt UUIDs authorized on connect without being linked to a
+    /// profile (`thunderbolt --trust`)
+    #[serde(default)]
+    pub trusted_devices: Vec<String>,
     #[serde(default)]
     pub undocked_profile: Option<String>,
+    /// Pick profiles by the set of connected monitors when no dock link applies
+    #[serde(default)]
+    pub match_monitors: bool,
+    /// Profile whose last apply timed out and was reverted; auto-apply
+    /// leaves it alone until it's applied by hand again
+    #[serde(default)]
+    pub reverted_profile: Option<String>,
+    /// Force the generated config format instead of detecting it from the
+    /// Hyprland version
+    #[serde(default)]
+    pub config_format: Option<ConfigFormat>,
+    /// Description of every monitor connected during an apply, so `doctor`
+    /// can spot profile monitors that never show up
+    #[serde(default)]
+    pub seen_monitors: Vec<String>,
 }
 
 impl Metadata {
@@ -61,18 +123,166 @@ impl Metadata {
         self.touch();
     }
 
+    /// Link a combination of docks to a profile, replacing any combo of
+    /// exactly the same docks
+    pub fn link_dock_combo(&mut self, docks: &[String], profile: &str) {
+        let mut docks = docks.to_vec();
+        docks.sort();
+        docks.dedup();
+        self.dock_combos.retain(|c| {
+            let mut existing = c.docks.clone();
+            existing.sort();
+            existing != docks
+        });
+        self.dock_combos.push(DockCombo {
+            docks,
+            profile: profile.to_string(),
+            priority: 0,
+        });
+        self.touch();
+    }
+
     /// Unlink a dock UUID
     pub fn unlink_dock(&mut self, uuid: &str) {
         self.dock_profiles.remove(uuid);
+        self.dock_priorities.remove(uuid);
+        self.touch();
+    }
+
+    /// Priority of a dock's link (0 unless set)
+    pub fn dock_priority(&self, uuid: &str) -> i32 {
+        self.dock_priorities.get(uuid).copied().unwrap_or(0)
+    }
+
+    /// Set the priority of a dock's link; 0 is the default and isn't stored
+    pub fn set_dock_priority(&mut self, uuid: &str, priority: i32) {
+        if priority == 0 {
+            self.dock_priorities.remove(uuid);
+        } else {
+            self.dock_priorities.insert(uuid.to_string(), priority);
+        }
+        self.touch();
+    }
+
+    /// Raise (or, with a negative step, lower) a dock's link priority,
+    /// stopping at the ends of the range rather than wrapping
+    pub fn step_dock_priority(&mut self, uuid: &str, step: i32) {
+        let priority = self.dock_priority(uuid).saturating_add(step);
+        self.set_dock_priority(uuid, priority);
+    }
+
+    /// Remember connected monitor descriptions; returns true if any is new
+    pub fn record_seen_monitors(&mut self, descriptions: &[String]) -> bool {
+        let mut changed = false;
+        for description in descriptions {
+            if !self.seen_monitors.contains(description) {
+                self.seen_monitors.push(description.clone());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Trust a Thunderbolt device so the udev rules authorize it
+    pub fn trust_device(&mut self, uuid: &str) {
+        if !self.trusted_devices.iter().any(|t| t == uuid) {
+            self.trusted_devices.push(uuid.to_string());
+        }
         self.touch();
     }
 
+    /// Stop trusting a Thunderbolt device. A dock linked to a profile stays
+    /// on the allowlist until it's unlinked.
+    pub fn untrust_device(&mut self, uuid: &str) {
+        self.trusted_devices.retain(|t| t != uuid);
+        self.touch();
+    }
+
+    /// Thunderbolt UUIDs `hyprpier setup` authorizes: every trusted device
+    /// and every dock in a link or combination, sorted. Ids of other dock
+    /// sources, and anything that isn't a well-formed UUID, are left out.
+    pub fn allowlist(&self) -> Vec<String> {
+        let mut uuids: Vec<String> = self
+            .trusted_devices
+            .iter()
+            .chain(self.dock_profiles.keys())
+            .chain(self.dock_combos.iter().flat_map(|c| &c.docks))
+            .filter(|uuid| thunderbolt::is_valid_uuid(uuid))
+            .cloned()
+            .collect();
+        uuids.sort();
+        uuids.dedup();
+        uuids
+    }
+
+    /// Pick the profile for the connected docks (UUIDs, in device order).
+    ///
+    /// Every link whose dock is connected and every combination whose docks
+    /// all are is a candidate. The highest priority wins; among equal
+    /// priorities a combination of more docks beats fewer, then the rule
+    /// whose first dock comes earliest in device order, then the profile
+    /// name, so the choice never depends on map iteration order.
+    pub fn resolve_docks(&self, connected: &[String]) -> Option<DockChoice> {
+        let position = |uuid: &String| connected.iter().position(|c| c == uuid);
+
+        let links = self
+            .dock_profiles
+            .iter()
+            .filter(|(uuid, _)| position(uuid).is_some())
+            .map(|(uuid, profile)| DockRule {
+                docks: vec![uuid.clone()],
+                profile: profile.clone(),
+                priority: self.dock_priority(uuid),
+            });
+        let combos = self
+            .dock_combos
+            .iter()
+            .filter(|c| !c.docks.is_empty() && c.docks.iter().all(|d| position(d).is_some()))
+            .map(|c| DockRule {
+                docks: c.docks.clone(),
+                profile: c.profile.clone(),
+                priority: c.priority,
+            });
+
+        let first = |rule: &DockRule| rule.docks.iter().filter_map(position).min();
+        let mut rules: Vec<DockRule> = links.chain(combos).collect();
+        rules.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(b.docks.len().cmp(&a.docks.len()))
+                .then(first(a).cmp(&first(b)))
+                .then(a.profile.cmp(&b.profile))
+        });
+
+        let mut rules = rules.into_iter();
+        let chosen = rules.next()?;
+        let passed_over = rules
+            .map(|rule| {
+                let reason = if rule.priority != chosen.priority {
+                    "lower priority"
+                } else if rule.docks.len() != chosen.docks.len() {
+                    "fewer docks"
+                } else if first(&rule) != first(&chosen) {
+                    "later dock in device order"
+                } else {
+                    "profile name sorts later"
+                };
+                PassedOver { rule, reason }
+            })
+            .collect();
+        Some(DockChoice {
+            chosen,
+            passed_over,
+        })
+    }
+
     /// Get the profile linked to a dock UUID
     pub fn get_dock_profile(&self, uuid: &str) -> Option<&String> {
         self.dock_profiles.get(uuid)
     }
 
-    /// Remove every reference to a profile (active, dock links, undocked).
+    /// Remove every reference to a profile (active, dock links, undocked,
+    /// reverted).
     /// Returns true if anything changed.
     pub fn remove_profile_references(&mut self, profile: &str) -> bool {
         let mut changed = false;
@@ -84,11 +294,22 @@ impl Metadata {
             self.undocked_profile = None;
             changed = true;
         }
+        if self.reverted_profile.as_deref() == Some(profile) {
+            self.reverted_profile = None;
+            changed = true;
+        }
         let before = self.dock_profiles.len();
         self.dock_profiles.retain(|_, p| p != profile);
         if self.dock_profiles.len() != before {
             changed = true;
         }
+        let linked = &self.dock_profiles;
+        self.dock_priorities.retain(|uuid, _| linked.contains_key(uuid));
+        let before = self.dock_combos.len();
+        self.dock_combos.retain(|c| c.profile != profile);
+        if self.dock_combos.len() != before {
+            changed = true;
+        }
         if changed {
             self.touch();
         }
@@ -106,12 +327,22 @@ impl Metadata {
             self.undocked_profile = Some(new.to_string());
             changed = true;
         }
+        if self.reverted_profile.as_deref() == Some(old) {
+            self.reverted_profile = Some(new.to_string());
+            changed = true;
+        }
         for p in self.dock_profiles.values_mut() {
             if p == old {
                 *p = new.to_string();
                 changed = true;
             }
         }
+        for combo in &mut self.dock_combos {
+            if combo.profile == old {
+                combo.profile = new.to_string();
+                changed = true;
+            }
+        }
         if changed {
             self.touch();
         }
@@ -126,6 +357,43 @@ impl Metadata {
     }
 }
 
+impl DockChoice {
+    /// Why this rule won, for `apply --auto` output. `name` turns a dock
+    /// UUID into something readable.
+    pub fn explain(&self, name: impl Fn(&str) -> String) -> String {
+        let docks = |rule: &DockRule| {
+            rule.docks
+                .iter()
+                .map(|d| name(d))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        };
+        let chosen = &self.chosen;
+        let kind = if chosen.docks.len() > 1 {
+            "Dock combination"
+        } else {
+            "Dock"
+        };
+        let mut text = format!(
+            "{} {} -> profile {} (priority {})",
+            kind,
+            docks(chosen),
+            chosen.profile,
+            chosen.priority
+        );
+        for other in &self.passed_over {
+            text.push_str(&format!(
+                "\n  not {} -> profile {} (priority {}): {}",
+                docks(&other.rule),
+                other.rule.profile,
+                other.rule.priority,
+                other.reason
+            ));
+        }
+        text
+    }
+}
+
 /// Get current Unix timestamp as a string
 fn unix_timestamp() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -134,3 +402,130 @@ fn unix_timestamp() -> String {
         .unwrap_or_default();
     format!("{}", duration.as_secs())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCK: &str = "dock-uuid";
+    const HUB: &str = "hub-uuid";
+    const TRAVEL: &str = "travel-uuid";
+
+    fn connected(uuids: &[&str]) -> Vec<String> {
+        uuids.iter().map(|u| u.to_string()).collect()
+    }
+
+    fn metadata() -> Metadata {
+        let mut m = Metadata::default();
+        m.link_dock(DOCK, "desk");
+        m.link_dock(HUB, "hub-only");
+        m.link_dock(TRAVEL, "travel");
+        m.dock_combos.push(DockCombo {
+            docks: vec![DOCK.to_string(), HUB.to_string()],
+            profile: "desk-full".to_string(),
+            priority: 0,
+        });
+        m
+    }
+
+    #[test]
+    fn combination_beats_single_links() {
+        let m = metadata();
+        let choice = m.resolve_docks(&connected(&[HUB, DOCK])).unwrap();
+        assert_eq!(choice.chosen.profile, "desk-full");
+        let lost: Vec<(&str, &str)> = choice
+            .passed_over
+            .iter()
+            .map(|p| (p.rule.profile.as_str(), p.reason))
+            .collect();
+        // Among the single links, device order breaks the tie
+        assert_eq!(
+            lost,
+            [("hub-only", "fewer docks"), ("desk", "fewer docks")]
+        );
+
+        // Half the combination connected: just the link
+        let choice = m.resolve_docks(&connected(&[DOCK])).unwrap();
+        assert_eq!(choice.chosen.profile, "desk");
+        assert!(choice.passed_over.is_empty());
+        assert!(m.resolve_docks(&connected(&["unknown"])).is_none());
+    }
+
+    #[test]
+    fn priority_overrides_specificity_and_order() {
+        let mut m = metadata();
+        m.set_dock_priority(TRAVEL, 5);
+        let choice = m.resolve_docks(&connected(&[DOCK, HUB, TRAVEL])).unwrap();
+        assert_eq!(choice.chosen.profile, "travel");
+        assert!(choice.passed_over.iter().all(|p| p.reason == "lower priority"));
+
+        // Same priority, same size: the dock that comes first wins
+        m.set_dock_priority(TRAVEL, 0);
+        let choice = m.resolve_docks(&connected(&[TRAVEL, DOCK])).unwrap();
+        assert_eq!(choice.chosen.profile, "travel");
+        assert_eq!(choice.passed_over[0].reason, "later dock in device order");
+        assert!(m.dock_priorities.is_empty());
+    }
+
+    #[test]
+    fn priority_steps_stop_at_the_ends() {
+        let mut m = metadata();
+        m.step_dock_priority(TRAVEL, -1);
+        assert_eq!(m.dock_priority(TRAVEL), -1);
+        m.set_dock_priority(TRAVEL, i32::MAX);
+        m.step_dock_priority(TRAVEL, 1);
+        assert_eq!(m.dock_priority(TRAVEL), i32::MAX);
+        m.set_dock_priority(TRAVEL, i32::MIN);
+        m.step_dock_priority(TRAVEL, -1);
+        assert_eq!(m.dock_priority(TRAVEL), i32::MIN);
+    }
+
+    #[test]
+    fn explains_the_choice() {
+        let m = metadata();
+        let choice = m.resolve_docks(&connected(&[DOCK, HUB])).unwrap();
+        let text = choice.explain(|uuid| uuid.trim_end_matches("-uuid").to_string());
+        assert_eq!(
+            text.lines().next(),
+            Some("Dock combination dock + hub -> profile desk-full (priority 0)")
+        );
+        assert!(text.contains("\n  not hub -> profile hub-only (priority 0): fewer docks"));
+    }
+
+    #[test]
+    fn profile_references_cover_combinations() {
+        let mut m = metadata();
+        m.set_dock_priority(DOCK, 2);
+        m.rename_profile_references("desk-full", "office");
+        assert_eq!(m.dock_combos[0].profile, "office");
+        assert!(m.remove_profile_references("office"));
+        assert!(m.dock_combos.is_empty());
+        assert!(m.remove_profile_references("desk"));
+        assert!(!m.dock_priorities.contains_key(DOCK));
+    }
+
+    #[test]
+    fn allowlist_holds_only_thunderbolt_uuids() {
+        let mut m = Metadata::default();
+        m.link_dock("f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b", "desk");
+        m.link_dock("usb:2109:0817@3-2", "travel");
+        m.trust_device("2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f");
+        m.trust_device("2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f");
+        m.trust_device("\" RUN+=\"/bin/sh");
+        m.dock_combos.push(DockCombo {
+            docks: vec!["f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b".to_string()],
+            profile: "desk-full".to_string(),
+            priority: 0,
+        });
+        assert_eq!(
+            m.allowlist(),
+            [
+                "2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f",
+                "f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b"
+            ]
+        );
+
+        m.untrust_device("2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f");
+        assert_eq!(m.allowlist(), ["f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b"]);
+    }
+}
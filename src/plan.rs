@@ -0,0 +1,427 @@
+//! Dry-run planning for `hyprpier apply --dry-run`
+//!
+//! Works out what applying a profile would change, compared against the
+//! live Hyprland layout and the generated config on disk, without writing or
+//! executing anything.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::compositor::Compositor;
+use crate::hyprland::{self, WorkspaceMove};
+use crate::profile::{Monitor, Profile};
+
+/// Lines of unchanged context around each hunk of the config diff
+const DIFF_CONTEXT: usize = 3;
+
+/// One changed field of a monitor that stays enabled
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// What applying the profile would do to one monitor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MonitorChange {
+    Enable { name: String },
+    Disable { name: String },
+    Modify { name: String, changes: Vec<FieldChange> },
+    Unchanged { name: String },
+    /// In the profile but not connected; its rule is written but does nothing
+    NotConnected { name: String },
+    /// Connected but not in the profile; apply leaves it alone
+    Unmanaged { name: String },
+}
+
+/// Everything an apply would change
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub profile: String,
+    pub config_path: String,
+    pub monitors: Vec<MonitorChange>,
+    pub workspace_moves: Vec<WorkspaceMove>,
+    pub orphan_cleanup: Vec<String>,
+    /// Unified diff of the generated config against the file on disk;
+    /// None when they're identical
+    pub config_diff: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Plan {
+    /// Compare an already-resolved profile (port names mapped, stacking
+    /// gaps fixed) against the live layout and the config on disk. Live
+    /// queries that fail become warnings rather than errors, so the config
+    /// diff is still shown when Hyprland isn't reachable.
+    pub fn build(compositor: &dyn Compositor, profile: &Profile) -> Result<Self> {
+        let mut warnings = Vec::new();
+        let dialect = compositor.dialect();
+        let path = dialect.config_path()?;
+        let on_disk = std::fs::read_to_string(&path).unwrap_or_default();
+        let generated = dialect.generate_config(profile);
+        let label = path.display().to_string();
+        let config_diff = unified_diff(&on_disk, &generated, &label, &label);
+
+        let mut monitors = Vec::new();
+        let mut workspace_moves = Vec::new();
+        let mut orphan_cleanup = Vec::new();
+        if compositor.is_running() {
+            match compositor.monitors(true) {
+                Ok(live) => monitors = monitor_changes(&profile.monitors, &live),
+                Err(e) => warnings.push(format!("Could not query monitors: {:#}", e)),
+            }
+            match hyprland::workspace_move_plan(compositor, profile) {
+                Ok(moves) => workspace_moves = moves,
+                Err(e) => warnings.push(format!("Could not query workspaces: {:#}", e)),
+            }
+            match hyprland::orphan_cleanup_plan(compositor, profile, dialect) {
+                Ok(exprs) => orphan_cleanup = exprs,
+                Err(e) => warnings.push(format!("Could not plan orphan cleanup: {:#}", e)),
+            }
+        } else {
+            warnings.push("The compositor is not running; only the config file would change".to_string());
+        }
+
+        Ok(Self {
+            profile: profile.name.clone(),
+            config_path: label,
+            monitors,
+            workspace_moves,
+            orphan_cleanup,
+            config_diff,
+            warnings,
+        })
+    }
+
+    /// Print the plan for humans
+    pub fn print(&self) {
+        println!("Dry run for profile: {} (nothing written or applied)", self.profile);
+
+        if !self.monitors.is_empty() {
+            println!();
+            println!("Monitors:");
+            for change in &self.monitors {
+                println!("  {}", describe_monitor_change(change));
+            }
+        }
+
+        println!();
+        if self.workspace_moves.is_empty() {
+            println!("Workspaces: no moves");
+        } else {
+            println!("Workspaces:");
+            for m in &self.workspace_moves {
+                println!("  move {}: {} -> {}", m.id, m.from, m.to);
+            }
+        }
+
+        if !self.orphan_cleanup.is_empty() {
+            println!();
+            println!("Orphan workspace cleanup (against the current layout):");
+            for expr in &self.orphan_cleanup {
+                println!("  {}", expr);
+            }
+        }
+
+        println!();
+        match &self.config_diff {
+            Some(diff) => {
+                println!("Config changes ({}):", self.config_path);
+                print!("{}", diff);
+            }
+            None => println!("Config unchanged ({})", self.config_path),
+        }
+
+        for warning in &self.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+}
+
+fn describe_monitor_change(change: &MonitorChange) -> String {
+    match change {
+        MonitorChange::Enable { name } => format!("{}: enable", name),
+        MonitorChange::Disable { name } => format!("{}: disable", name),
+        MonitorChange::Modify { name, changes } => {
+            let fields: Vec<String> = changes
+                .iter()
+                .map(|c| format!("{} {} -> {}", c.field, c.from, c.to))
+                .collect();
+            format!("{}: {}", name, fields.join(", "))
+        }
+        MonitorChange::Unchanged { name } => format!("{}: unchanged", name),
+        MonitorChange::NotConnected { name } => format!("{}: not connected", name),
+        MonitorChange::Unmanaged { name } => format!("{}: left as is (not in profile)", name),
+    }
+}
+
+/// Compare profile monitors against every live monitor (including disabled
+/// ones, as `enabled: false`), matching by port name.
+pub fn monitor_changes(profile: &[Monitor], live: &[Monitor]) -> Vec<MonitorChange> {
+    let mut changes = Vec::new();
+    for pm in profile {
+        let name = pm.name.clone();
+        let Some(lm) = live.iter().find(|l| l.name == pm.name) else {
+            changes.push(MonitorChange::NotConnected { name });
+            continue;
+        };
+        changes.push(match (pm.enabled, lm.enabled) {
+            (true, false) => MonitorChange::Enable { name },
+            (false, true) => MonitorChange::Disable { name },
+            (false, false) => MonitorChange::Unchanged { name },
+            (true, true) => {
+                let fields = field_changes(lm, pm);
+                if fields.is_empty() {
+                    MonitorChange::Unchanged { name }
+                } else {
+                    MonitorChange::Modify { name, changes: fields }
+                }
+            }
+        });
+    }
+    for lm in live {
+        if !profile.iter().any(|pm| pm.name == lm.name) {
+            changes.push(MonitorChange::Unmanaged {
+                name: lm.name.clone(),
+            });
+        }
+    }
+    changes
+}
+
+/// Fields that differ between the live monitor and the profile's
+fn field_changes(live: &Monitor, target: &Monitor) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut push = |field, from: String, to: String| {
+        if from != to {
+            changes.push(FieldChange { field, from, to });
+        }
+    };
+    push("mode", live.mode.clone(), target.mode.clone());
+    // Hyprland reports scales like 1.5000000 for 1.5; compare rounded
+    push("scale", format!("{:.3}", live.scale), format!("{:.3}", target.scale));
+    push(
+        "position",
+        format!("{}x{}", live.position.x, live.position.y),
+        format!("{}x{}", target.position.x, target.position.y),
+    );
+    push("transform", live.transform.to_string(), target.transform.to_string());
+    // Unset options are left alone by an apply, so only set ones can
+    // change; a live option that's unset is at Hyprland's default
+    let (live, target) = (&live.options, &target.options);
+    let options = [
+        ("vrr", live.vrr.map(|v| v.to_string()), target.vrr.map(|v| v.to_string()), "0"),
+        (
+            "bitdepth",
+            live.bitdepth.map(|b| b.to_string()),
+            target.bitdepth.map(|b| b.to_string()),
+            "8",
+        ),
+        ("cm", live.cm.clone(), target.cm.clone(), "srgb"),
+        (
+            "sdrbrightness",
+            live.sdrbrightness.map(|b| format!("{:.2}", b)),
+            target.sdrbrightness.map(|b| format!("{:.2}", b)),
+            "1.00",
+        ),
+        ("mirror", live.mirror.clone(), target.mirror.clone(), "none"),
+    ];
+    for (field, from, to, default) in options {
+        if let Some(to) = to {
+            push(field, from.unwrap_or_else(|| default.to_string()), to);
+        }
+    }
+    changes
+}
+
+/// A line of a diff: unchanged, removed from old, or added in new
+#[derive(Clone, Copy, PartialEq)]
+enum DiffOp {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// Unified diff of two texts, or None if they're identical. Uses a plain
+/// LCS table; fine for config files of a few dozen lines.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = length of the longest common subsequence of a[i..], b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Walk the table into (op, text, old index, new index) lines, where the
+    // indices are the positions before the line is consumed
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((DiffOp::Keep, a[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // Removals first on ties, so a replaced line reads -old then +new
+            lines.push((DiffOp::Remove, a[i], i, j));
+            i += 1;
+        } else {
+            lines.push((DiffOp::Add, b[j], i, j));
+            j += 1;
+        }
+    }
+
+    // Group changed lines into hunks, merging ones whose context overlaps
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&k| lines[k].0 != DiffOp::Keep)
+        .collect();
+    if changed.is_empty() {
+        // Only a trailing-newline difference; `lines()` hides it
+        return None;
+    }
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &k in &changed {
+        let lo = k.saturating_sub(DIFF_CONTEXT);
+        let hi = (k + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if lo <= last.1 => last.1 = hi,
+            _ => hunks.push((lo, hi)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    for (lo, hi) in hunks {
+        let hunk = &lines[lo..hi];
+        let old_len = hunk.iter().filter(|l| l.0 != DiffOp::Add).count();
+        let new_len = hunk.iter().filter(|l| l.0 != DiffOp::Remove).count();
+        // Empty ranges point at the line before, per the unified format
+        let old_start = hunk[0].2 + usize::from(old_len > 0);
+        let new_start = hunk[0].3 + usize::from(new_len > 0);
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_len, new_start, new_len
+        ));
+        for (op, text, _, _) in hunk {
+            let prefix = match op {
+                DiffOp::Keep => ' ',
+                DiffOp::Remove => '-',
+                DiffOp::Add => '+',
+            };
+            out.push(prefix);
+            out.push_str(text);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mon(name: &str, enabled: bool) -> Monitor {
+        let mut m = Monitor::test_fixture(name, "1920x1080", 1.0, 0);
+        m.enabled = enabled;
+        m
+    }
+
+    #[test]
+    fn monitor_changes_covers_every_case() {
+        let mut moved = mon("DP-1", true);
+        moved.position.x = 1920;
+        moved.scale = 1.25;
+        let profile = vec![
+            moved,
+            mon("DP-2", true),
+            mon("eDP-1", false),
+            mon("HDMI-A-1", true),
+            mon("DP-3", true),
+        ];
+        let live = vec![
+            mon("DP-1", true),
+            mon("DP-2", false),
+            mon("eDP-1", true),
+            mon("DP-3", true),
+            mon("DP-9", true),
+        ];
+
+        let changes = monitor_changes(&profile, &live);
+        assert_eq!(
+            changes,
+            vec![
+                MonitorChange::Modify {
+                    name: "DP-1".to_string(),
+                    changes: vec![
+                        FieldChange {
+                            field: "scale",
+                            from: "1.000".to_string(),
+                            to: "1.250".to_string(),
+                        },
+                        FieldChange {
+                            field: "position",
+                            from: "0x0".to_string(),
+                            to: "1920x0".to_string(),
+                        },
+                    ],
+                },
+                MonitorChange::Enable { name: "DP-2".to_string() },
+                MonitorChange::Disable { name: "eDP-1".to_string() },
+                MonitorChange::NotConnected { name: "HDMI-A-1".to_string() },
+                MonitorChange::Unchanged { name: "DP-3".to_string() },
+                MonitorChange::Unmanaged { name: "DP-9".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn unified_diff_identical_is_none() {
+        assert!(unified_diff("a\nb\n", "a\nb\n", "x", "x").is_none());
+    }
+
+    #[test]
+    fn unified_diff_single_hunk_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n";
+        let diff = unified_diff(old, new, "old", "new").unwrap();
+        assert_eq!(
+            diff,
+            "--- old\n+++ new\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_new_file_adds_everything() {
+        let diff = unified_diff("", "a\nb\n", "old", "new").unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+    }
+
+    #[test]
+    fn unified_diff_far_apart_changes_get_separate_hunks() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = old
+            .lines()
+            .enumerate()
+            .map(|(i, line)| match i + 1 {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                _ => format!("{}\n", line),
+            })
+            .collect();
+        let diff = unified_diff(&old, &new, "old", "new").unwrap();
+        let headers: Vec<&str> = diff.lines().filter(|l| l.starts_with("@@")).collect();
+        assert_eq!(headers, ["@@ -1,5 +1,5 @@", "@@ -16,5 +16,5 @@"], "got:\n{}", diff);
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 
 use crate::config;
@@ -17,6 +18,12 @@ pub struct Profile {
     pub workspaces: Vec<Workspace>,
     #[serde(default)]
     pub lid_switch: Option<LidSwitch>,
+    /// Seconds to wait for the user to keep a runtime apply before reverting
+    /// to the previous layout; None applies without asking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
 }
 
 
@@ -36,6 +43,31 @@ pub struct Monitor {
     #[serde(default)]
     pub transform: u8,
     pub mode: String,
+    #[serde(flatten)]
+    pub options: MonitorOptions,
+}
+
+/// Optional Hyprland monitor settings. Unset ones are left out of the
+/// generated rule, so Hyprland's defaults (and profiles saved before these
+/// existed) behave as before. Sway only honors `vrr` and `bitdepth`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct MonitorOptions {
+    /// Variable refresh rate: 0 off, 1 on, 2 fullscreen only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrr: Option<u8>,
+    /// Output bit depth; 10 for 10-bit color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitdepth: Option<u8>,
+    /// Color management preset: "auto", "srgb", "wide", "edid", "hdr" or
+    /// "hdredid"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cm: Option<String>,
+    /// Brightness of SDR content while in HDR mode (1.0 is unchanged)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdrbrightness: Option<f64>,
+    /// Port name of the monitor this one mirrors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -58,12 +90,74 @@ pub struct LidSwitch {
     pub monitor: String,
 }
 
+/// Shell commands run around an apply (see `hooks.rs`)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_apply: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_apply: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_fail: Vec<String>,
+    /// A failing pre_apply hook aborts the apply, and a failing post_apply
+    /// hook fails it; otherwise failures are only reported
+    #[serde(default)]
+    pub blocking: bool,
+}
+
+impl Hooks {
+    /// No hooks and no non-default setting, so nothing worth saving
+    pub fn is_empty(&self) -> bool {
+        self.pre_apply.is_empty()
+            && self.post_apply.is_empty()
+            && self.on_fail.is_empty()
+            && !self.blocking
+    }
+}
+
+/// How closely a profile's monitors match the displays currently connected,
+/// kanshi-style. Ordered so that a better match compares greater: any exact
+/// match beats any superset, and among supersets the profile that accounts
+/// for more of the connected displays wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MonitorMatch {
+    /// Every profile monitor is connected, alongside other displays
+    Superset { matched: usize },
+    /// The connected displays are exactly the profile's monitors
+    Exact,
+}
+
+impl std::fmt::Display for MonitorMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorMatch::Exact => write!(f, "exact match"),
+            MonitorMatch::Superset { matched } => {
+                write!(f, "{} of the connected monitors", matched)
+            }
+        }
+    }
+}
+
 impl Monitor {
     /// Hyprland positions monitors using logical (post-scale) coordinates: a
     /// 3840×2160 panel at scale 1.5 occupies a 2560×1440 region. All layout math
     /// (positions, gaps, adjacency) must use logical sizes to match what
     /// Hyprland actually does.
     pub fn logical_size(&self) -> (i32, i32) {
+        let (pw, ph) = self.rotated_size();
+        let scale = if self.scale.is_finite() && self.scale > 0.0 {
+            self.scale
+        } else {
+            1.0
+        };
+        (
+            (pw as f64 / scale).round() as i32,
+            (ph as f64 / scale).round() as i32,
+        )
+    }
+
+    /// Pixel size after the transform, before scaling
+    fn rotated_size(&self) -> (i32, i32) {
         let (w, h) = self.resolution
             .split_once('x')
             .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
@@ -71,20 +165,22 @@ impl Monitor {
         // Transforms follow wl_output_transform: bit 2 (value 4) is the
         // flipped flag, low 2 bits are rotation. Odd rotations (90/270,
         // flipped or not: 1, 3, 5, 7) swap width and height.
-        let (pw, ph) = if self.transform % 2 == 1 {
+        if self.transform % 2 == 1 {
             (h, w)
         } else {
             (w, h)
-        };
-        let scale = if self.scale.is_finite() && self.scale > 0.0 {
-            self.scale
-        } else {
-            1.0
-        };
-        (
-            (pw as f64 / scale).round() as i32,
-            (ph as f64 / scale).round() as i32,
-        )
+        }
+    }
+
+    /// Scales from 0.5 to 3 at which `logical_size` needs no rounding, i.e.
+    /// the resolution divides evenly. Candidates are multiples of 1/120,
+    /// the steps the fractional-scale protocol can express.
+    pub fn integer_scales(&self) -> Vec<f64> {
+        let (pw, ph) = self.rotated_size();
+        (60..=360)
+            .filter(|&k| (pw * 120) % k == 0 && (ph * 120) % k == 0)
+            .map(|k| f64::from(k) / 120.0)
+            .collect()
     }
 
     #[cfg(test)]
@@ -99,6 +195,7 @@ impl Monitor {
             scale,
             transform,
             mode: format!("{}@60", resolution),
+            options: MonitorOptions::default(),
         }
     }
 }
@@ -154,6 +251,8 @@ impl Profile {
             monitors: Vec::new(),
             workspaces: Vec::new(),
             lid_switch: None,
+            revert_timeout: None,
+            hooks: Hooks::default(),
         }
     }
 
@@ -192,6 +291,78 @@ impl Profile {
         }
         Ok(())
     }
+
+    /// Give freshly detected `monitors` the `vrr` this profile already has
+    /// for them. The compositor only reports whether VRR is active, which
+    /// can't tell fullscreen-only (2) from on or off, so the profile's value
+    /// wins; monitors new to the profile keep what was read live. Matched
+    /// by description (preferring the same port), or by port without one.
+    pub fn keep_vrr(&self, monitors: &mut [Monitor]) {
+        for monitor in monitors {
+            let saved = self
+                .monitors
+                .iter()
+                .filter(|s| match (&s.description, &monitor.description) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => s.name == monitor.name,
+                })
+                .min_by_key(|s| s.name != monitor.name);
+            if let Some(saved) = saved {
+                monitor.options.vrr = saved.options.vrr;
+            }
+        }
+    }
+
+    /// Match this profile's monitor descriptions against the descriptions of
+    /// the connected displays. Profiles with a monitor that has no stored
+    /// description can't be identified by hardware and never match.
+    /// Descriptions are counted, not deduplicated: identical monitors
+    /// without a serial share one, and a profile with two of them needs
+    /// two connected.
+    pub fn monitor_match(&self, connected: &[String]) -> Option<MonitorMatch> {
+        if self.monitors.is_empty() {
+            return None;
+        }
+        let mut wanted: HashMap<&str, usize> = HashMap::new();
+        for monitor in &self.monitors {
+            *wanted.entry(monitor.description.as_deref()?).or_default() += 1;
+        }
+        let mut available: HashMap<&str, usize> = HashMap::new();
+        for description in connected {
+            *available.entry(description.as_str()).or_default() += 1;
+        }
+
+        let covered = wanted
+            .iter()
+            .all(|(description, &count)| available.get(description).is_some_and(|&n| n >= count));
+        if !covered {
+            None
+        } else if wanted == available {
+            Some(MonitorMatch::Exact)
+        } else {
+            Some(MonitorMatch::Superset {
+                matched: self.monitors.len(),
+            })
+        }
+    }
+}
+
+/// Pick the profile whose monitors best match the connected displays.
+/// Ties go to the earliest profile, so callers passing name-sorted profiles
+/// get a deterministic choice.
+pub fn best_monitor_match<'a>(
+    profiles: &'a [Profile],
+    connected: &[String],
+) -> Option<(&'a Profile, MonitorMatch)> {
+    let mut best: Option<(&Profile, MonitorMatch)> = None;
+    for profile in profiles {
+        if let Some(m) = profile.monitor_match(connected) {
+            if best.is_none_or(|(_, b)| m > b) {
+                best = Some((profile, m));
+            }
+        }
+    }
+    best
 }
 
 /// List all available profile names
@@ -228,6 +399,118 @@ mod tests {
         Monitor::test_fixture("test", resolution, scale, transform)
     }
 
+    fn described(name: &str, monitors: &[&str]) -> Profile {
+        let mut p = Profile::new(name);
+        for desc in monitors {
+            let mut m = Monitor::test_fixture("DP-1", "1920x1080", 1.0, 0);
+            m.description = Some(desc.to_string());
+            p.monitors.push(m);
+        }
+        p
+    }
+
+    fn connected(descs: &[&str]) -> Vec<String> {
+        descs.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn keep_vrr_prefers_the_saved_value() {
+        let mut saved = described("desk", &["Dell U2720Q", "LG 27GN950"]);
+        saved.monitors[0].options.vrr = Some(2);
+        saved.monitors[1].name = "DP-2".to_string();
+
+        // Live, fullscreen-only VRR reads as on or off
+        let mut live = described("desk", &["Dell U2720Q", "LG 27GN950", "BenQ GW2480"]);
+        live.monitors[0].options.vrr = Some(1);
+        live.monitors[1].options.vrr = Some(1);
+        live.monitors[2].options.vrr = Some(1);
+        saved.keep_vrr(&mut live.monitors);
+        let vrr: Vec<Option<u8>> = live.monitors.iter().map(|m| m.options.vrr).collect();
+        assert_eq!(vrr, [Some(2), None, Some(1)]);
+    }
+
+    #[test]
+    fn monitor_match_exact_and_superset() {
+        let p = described("desk", &["Laptop", "Dell A"]);
+        assert_eq!(
+            p.monitor_match(&connected(&["Dell A", "Laptop"])),
+            Some(MonitorMatch::Exact)
+        );
+        assert_eq!(
+            p.monitor_match(&connected(&["Dell A", "Laptop", "Dell B"])),
+            Some(MonitorMatch::Superset { matched: 2 })
+        );
+        assert_eq!(p.monitor_match(&connected(&["Laptop"])), None);
+    }
+
+    #[test]
+    fn monitor_match_counts_identical_monitors() {
+        let p = described("twins", &["Dell A", "Dell A"]);
+        assert_eq!(p.monitor_match(&connected(&["Dell A"])), None);
+        assert_eq!(
+            p.monitor_match(&connected(&["Dell A", "Dell A"])),
+            Some(MonitorMatch::Exact)
+        );
+        assert_eq!(
+            p.monitor_match(&connected(&["Dell A", "Laptop", "Dell A"])),
+            Some(MonitorMatch::Superset { matched: 2 })
+        );
+        assert_eq!(
+            described("one", &["Dell A"]).monitor_match(&connected(&["Dell A", "Dell A"])),
+            Some(MonitorMatch::Superset { matched: 1 })
+        );
+    }
+
+    #[test]
+    fn unset_hooks_and_revert_timeout_are_not_saved() {
+        let mut p = described("desk", &["Laptop"]);
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(!json.contains("hooks") && !json.contains("revert_timeout"), "{}", json);
+
+        p.hooks.post_apply = vec!["true".to_string()];
+        let json = serde_json::to_value(&p).unwrap();
+        assert_eq!(
+            json["hooks"],
+            serde_json::json!({ "post_apply": ["true"], "blocking": false })
+        );
+    }
+
+    #[test]
+    fn monitor_match_requires_descriptions() {
+        let mut p = described("desk", &["Laptop"]);
+        p.monitors.push(Monitor::test_fixture("DP-2", "1920x1080", 1.0, 0));
+        assert_eq!(p.monitor_match(&connected(&["Laptop", "Dell A"])), None);
+        assert_eq!(Profile::new("empty").monitor_match(&connected(&["Laptop"])), None);
+    }
+
+    #[test]
+    fn best_monitor_match_prefers_exact_then_larger_superset() {
+        let profiles = vec![
+            described("laptop", &["Laptop"]),
+            described("desk-a", &["Laptop", "Dell A"]),
+            described("desk-b", &["Laptop", "Dell B"]),
+        ];
+        let (p, m) = best_monitor_match(&profiles, &connected(&["Laptop", "Dell B"])).unwrap();
+        assert_eq!((p.name.as_str(), m), ("desk-b", MonitorMatch::Exact));
+
+        // Nothing exact: the superset covering more displays wins
+        let (p, _) =
+            best_monitor_match(&profiles, &connected(&["Laptop", "Dell A", "Projector"])).unwrap();
+        assert_eq!(p.name, "desk-a");
+
+        assert!(best_monitor_match(&profiles, &connected(&["Projector"])).is_none());
+    }
+
+    #[test]
+    fn best_monitor_match_ties_go_to_first_profile() {
+        let profiles = vec![
+            described("a", &["Laptop", "Dell A"]),
+            described("b", &["Laptop", "Dell A"]),
+        ];
+        let (p, _) = best_monitor_match(&profiles, &connected(&["Laptop", "Dell A"])).unwrap();
+        assert_eq!(p.name, "a");
+    }
+
     #[test]
     fn logical_size_unscaled_unrotated() {
         assert_eq!(m("1920x1080", 1.0, 0).logical_size(), (1920, 1080));
@@ -268,6 +551,24 @@ mod tests {
         assert_eq!(m("1920x1080", 1.5, 7).logical_size(), (720, 1280));
     }
 
+    #[test]
+    fn integer_scales_divide_the_resolution_evenly() {
+        let scales = m("2560x1440", 1.0, 0).integer_scales();
+        for s in [1.0, 1.25, 2.0, 2.5] {
+            assert!(scales.contains(&s), "{} missing", s);
+        }
+        assert!(!scales.contains(&1.5));
+        for &s in &scales {
+            let mut monitor = m("2560x1440", s, 0);
+            let (w, h) = monitor.logical_size();
+            assert!((f64::from(w) * s - 2560.0).abs() < 1e-6);
+            assert!((f64::from(h) * s - 1440.0).abs() < 1e-6);
+            monitor.transform = 1;
+            assert_eq!(monitor.integer_scales(), scales);
+        }
+        assert!(m("3840x2160", 1.0, 0).integer_scales().contains(&1.5));
+    }
+
     #[test]
     fn logical_size_zero_scale_falls_back_to_one() {
         assert_eq!(m("1920x1080", 0.0, 0).logical_size(), (1920, 1080));
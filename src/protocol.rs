@@ -0,0 +1,175 @@
+//! Daemon socket protocol
+//!
+//! Newline-delimited JSON over $XDG_RUNTIME_DIR/hyprpier.sock. Every request,
+//! reply and event carries the protocol version in `v`. A client may send
+//! several requests on one connection and gets one reply line per request;
+//! `subscribe` turns the connection into a one-way stream of event lines.
+//!
+//! The plain-text `refresh` and `status` commands of earlier releases (still
+//! sent by installed udev rules) keep working: a message that doesn't start
+//! with `{` is treated as one of those.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::events::Event;
+
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// A request to the daemon, tagged by `cmd`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Request {
+    /// Apply a profile by name, queued behind any running apply
+    Apply {
+        profile: String,
+        #[serde(default)]
+        no_runtime: bool,
+    },
+    /// Run auto-apply now instead of after the debounce
+    ApplyAuto,
+    /// Queue a debounced auto-apply, like a udev or hotplug event does
+    Refresh,
+    /// Active profile, connected docks, pause state, pending refresh and
+    /// the last apply
+    Status,
+    /// Saved profiles, as `list --json` prints them
+    List,
+    /// Re-read the metadata and re-run auto-apply against it
+    ReloadMetadata,
+    /// Stop auto-switching on dock and monitor events
+    Pause,
+    Resume,
+    /// Stream events on this connection until the client hangs up
+    Subscribe,
+}
+
+/// Serialize a message as one line with the protocol version added
+fn encode(message: impl Serialize) -> String {
+    let mut value = serde_json::to_value(message).unwrap_or(Value::Null);
+    if let Value::Object(map) = &mut value {
+        map.insert("v".to_string(), PROTOCOL_VERSION.into());
+    }
+    format!("{}\n", value)
+}
+
+pub fn encode_request(request: &Request) -> String {
+    encode(request)
+}
+
+pub fn encode_event(event: &Event) -> String {
+    encode(event)
+}
+
+pub fn ok_reply(data: impl Serialize) -> String {
+    let data = serde_json::to_value(data).unwrap_or(Value::Null);
+    encode(serde_json::json!({ "ok": true, "data": data }))
+}
+
+pub fn error_reply(error: &str) -> String {
+    encode(serde_json::json!({ "ok": false, "error": error }))
+}
+
+/// Parse a line as a JSON value, rejecting other protocol versions
+fn parse_versioned(line: &str) -> Result<Value> {
+    let value: Value = serde_json::from_str(line).context("Invalid JSON")?;
+    match value.get("v").and_then(Value::as_u64) {
+        Some(PROTOCOL_VERSION) => Ok(value),
+        Some(v) => anyhow::bail!(
+            "Unsupported protocol version {} (expected {})",
+            v,
+            PROTOCOL_VERSION
+        ),
+        None => anyhow::bail!("Missing protocol version \"v\""),
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request> {
+    serde_json::from_value(parse_versioned(line)?).context("Invalid request")
+}
+
+pub fn parse_event(line: &str) -> Result<Event> {
+    serde_json::from_value(parse_versioned(line)?).context("Invalid event")
+}
+
+/// Unwrap a reply: its `data` on success, the daemon's error otherwise
+pub fn parse_reply(line: &str) -> Result<Value> {
+    let mut value = parse_versioned(line)?;
+    if value.get("ok").and_then(Value::as_bool) == Some(true) {
+        return Ok(value.get_mut("data").map(Value::take).unwrap_or(Value::Null));
+    }
+    let error = value
+        .get("error")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown daemon error");
+    anyhow::bail!("{}", error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_round_trip() {
+        let requests = [
+            Request::Apply {
+                profile: "desk".to_string(),
+                no_runtime: true,
+            },
+            Request::ApplyAuto,
+            Request::Status,
+            Request::ReloadMetadata,
+            Request::Subscribe,
+        ];
+        for request in requests {
+            let line = encode_request(&request);
+            assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+            assert_eq!(parse_request(&line).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn request_wire_format() {
+        assert_eq!(
+            parse_request(r#"{"v":1,"cmd":"apply","profile":"desk"}"#).unwrap(),
+            Request::Apply {
+                profile: "desk".to_string(),
+                no_runtime: false,
+            }
+        );
+        assert_eq!(
+            parse_request(r#"{"v":1,"cmd":"reload-metadata"}"#).unwrap(),
+            Request::ReloadMetadata
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_unknown_commands() {
+        assert!(parse_request(r#"{"cmd":"status"}"#).is_err());
+        assert!(parse_request(r#"{"v":2,"cmd":"status"}"#).is_err());
+        assert!(parse_request(r#"{"v":1,"cmd":"explode"}"#).is_err());
+        assert!(parse_request("status").is_err());
+    }
+
+    #[test]
+    fn replies_unwrap_data_or_error() {
+        let data = parse_reply(&ok_reply(serde_json::json!({ "paused": true }))).unwrap();
+        assert_eq!(data["paused"], true);
+
+        let err = parse_reply(&error_reply("no such profile")).unwrap_err();
+        assert_eq!(err.to_string(), "no such profile");
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let event = Event::ApplyFailed {
+            profile: Some("desk".to_string()),
+            trigger: "refresh".to_string(),
+            error: "boom".to_string(),
+        };
+        let line = encode_event(&event);
+        assert!(line.contains(r#""event":"apply-failed""#));
+        assert_eq!(parse_event(&line).unwrap(), event);
+    }
+}
@@ -0,0 +1,275 @@
+//! Revert-on-timeout safety net for risky applies
+//!
+//! A bad mode or scale can leave every screen black with a TTY as the only
+//! way back. When a profile sets `revert_timeout` (or `apply --confirm` is
+//! used), the previous generated config and live layout are snapshotted
+//! before the apply, and put back unless the user keeps the new layout in
+//! time.
+
+use anyhow::{Context, Result};
+use notify_rust::Notification;
+use std::io::{BufRead, IsTerminal, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::compositor::{self, Compositor, Dialect};
+use crate::history;
+use crate::hooks::{HookContext, HookEvent};
+use crate::hyprland;
+use crate::metadata::Metadata;
+use crate::profile::Monitor;
+
+/// Timeout used when confirmation is switched on from the TUI
+pub const DEFAULT_TIMEOUT_SECS: u64 = 15;
+
+/// Everything needed to put the previous layout back
+pub struct Snapshot {
+    dialect: Dialect,
+    /// Previous generated config; None if there wasn't one
+    config: Option<String>,
+    /// Live monitors, including disabled ones
+    monitors: Vec<Monitor>,
+    active_profile: Option<String>,
+}
+
+impl Snapshot {
+    /// Capture the current config file, live layout and active profile
+    pub fn capture(compositor: &dyn Compositor) -> Result<Self> {
+        let dialect = compositor.dialect();
+        let path = dialect.config_path()?;
+        let config = match std::fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+        let monitors = compositor.monitors(true)?;
+        let active_profile = Metadata::load()?.active_profile;
+        Ok(Self {
+            dialect,
+            config,
+            monitors,
+            active_profile,
+        })
+    }
+
+    /// Put the snapshot back: config file, runtime layout, active profile
+    pub fn restore(&self, compositor: &dyn Compositor) -> Result<()> {
+        match &self.config {
+            Some(content) => hyprland::write_config_text(self.dialect, content)?,
+            None => {
+                let path = self.dialect.config_path()?;
+                if path.exists() {
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                }
+            }
+        }
+
+        if compositor.is_running() {
+            // The reload brings back the old workspace rules and binds; the
+            // explicit monitor calls then pin the exact modes that were live,
+            // which the old config may not have described (hand-set modes).
+            if let Err(e) = compositor.reload() {
+                eprintln!("Warning: hyprctl reload failed during revert: {}", e);
+            }
+            // Enable before disabling so there's never a moment without an output
+            let (enabled, disabled): (Vec<&Monitor>, Vec<&Monitor>) =
+                self.monitors.iter().partition(|m| m.enabled);
+            let mut first_err = None;
+            for m in enabled.into_iter().chain(disabled) {
+                if let Err(e) = compositor.apply_monitor(m) {
+                    first_err.get_or_insert(e);
+                }
+            }
+            if let Some(e) = first_err {
+                return Err(e);
+            }
+        }
+
+        let mut metadata = Metadata::load()?;
+        metadata.set_active(self.active_profile.clone());
+        metadata.save()
+    }
+}
+
+/// An applied profile waiting to be kept or reverted. Its `post_apply`
+/// hooks wait with it: they run once the layout is kept, and a revert runs
+/// the `on_fail` hooks instead.
+pub struct PendingRevert {
+    profile: String,
+    snapshot: Snapshot,
+    timeout_secs: u64,
+    deadline: Instant,
+    hooks: HookContext,
+}
+
+impl PendingRevert {
+    pub fn new(profile: &str, snapshot: Snapshot, timeout_secs: u64, hooks: HookContext) -> Self {
+        Self {
+            profile: profile.to_string(),
+            snapshot,
+            timeout_secs,
+            deadline: Instant::now() + Duration::from_secs(timeout_secs),
+            hooks,
+        }
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Time left before the layout is reverted
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Keep the new layout and run the `post_apply` hooks held back for
+    /// it; returns their warnings, or an error from a blocking one
+    pub fn keep(self) -> Result<Vec<String>> {
+        self.hooks
+            .run(HookEvent::PostApply, None)
+            .context("Blocking post_apply hook failed")
+    }
+
+    /// Restore the previous layout and remember that this profile was
+    /// reverted, so auto-apply doesn't immediately try it again. The
+    /// `on_fail` hooks get the revert as their error.
+    pub fn revert(self) -> Result<()> {
+        self.snapshot.restore(&*compositor::detect())?;
+        let mut metadata = Metadata::load()?;
+        metadata.reverted_profile = Some(self.profile.clone());
+        metadata.save()?;
+        history::mark_reverted(&self.profile)?;
+
+        let reverted = anyhow::anyhow!(
+            "Profile '{}' was not kept; reverted to the previous layout",
+            self.profile
+        );
+        // on_fail never blocks, and its failures don't undo the revert
+        let _ = self.hooks.run(HookEvent::OnFail, Some(&reverted));
+        Ok(())
+    }
+
+    /// Block until the user keeps the layout or the timeout passes; ask on
+    /// the terminal when there is one, otherwise via a notification action.
+    /// Once kept, returns the `post_apply` hook warnings; returns an error
+    /// once the layout has been reverted.
+    pub fn wait(self) -> Result<Vec<String>> {
+        let kept = if std::io::stdin().is_terminal() {
+            self.confirmed_on_terminal()
+        } else {
+            self.confirmed_by_notification()
+        };
+        self.settle(kept)
+    }
+
+    /// Ask via a notification on a thread of its own and return at once,
+    /// for the daemon: nobody answers at its stdin, and its apply worker
+    /// shouldn't sit out the timeout. If another profile has been applied
+    /// by the time the answer comes, that layout is left alone instead of
+    /// being reverted.
+    pub fn wait_in_background(self) {
+        thread::spawn(move || {
+            let kept = self.confirmed_by_notification();
+            if !kept && self.superseded() {
+                eprintln!(
+                    "Profile '{}' was replaced before it was kept; not reverting",
+                    self.profile
+                );
+                return;
+            }
+            match self.settle(kept) {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        eprintln!("Warning: {}", warning);
+                    }
+                }
+                Err(e) => eprintln!("{:#}", e),
+            }
+        });
+    }
+
+    /// Whether another profile became active while this one waited
+    fn superseded(&self) -> bool {
+        Metadata::load()
+            .map(|m| m.active_profile.as_deref() != Some(self.profile.as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Keep or revert once the user has answered (or the time ran out)
+    fn settle(self, kept: bool) -> Result<Vec<String>> {
+        if kept {
+            return self.keep();
+        }
+
+        let name = self.profile.clone();
+        let secs = self.timeout_secs;
+        self.revert()
+            .with_context(|| format!("Failed to revert after profile '{}'", name))?;
+        anyhow::bail!(
+            "Profile '{}' was not kept within {}s; reverted to the previous layout",
+            name,
+            secs
+        )
+    }
+
+    /// The prompt goes to stderr so `--json` output stays clean
+    fn confirmed_on_terminal(&self) -> bool {
+        eprint!(
+            "Keep this layout? [y/N] (reverting in {}s) ",
+            self.remaining().as_secs()
+        );
+        let _ = std::io::stderr().flush();
+
+        // stdin can't be read with a timeout; a reader left blocked after
+        // the deadline is dropped when the process exits
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut line = String::new();
+            if std::io::stdin().lock().read_line(&mut line).is_ok() {
+                let _ = tx.send(line);
+            }
+        });
+        match rx.recv_timeout(self.remaining()) {
+            Ok(line) => matches!(line.trim().to_lowercase().as_str(), "y" | "yes"),
+            Err(_) => {
+                eprintln!();
+                false
+            }
+        }
+    }
+
+    /// A notification that's dismissed, or can't be shown at all, counts as
+    /// not kept: nobody can confirm in that case.
+    fn confirmed_by_notification(&self) -> bool {
+        let (tx, rx) = mpsc::channel();
+        let body = format!(
+            "Profile '{}' reverts in {}s unless kept",
+            self.profile, self.timeout_secs
+        );
+        let timeout_ms = self.remaining().as_millis().min(i32::MAX as u128) as i32;
+        thread::spawn(move || {
+            let handle = Notification::new()
+                .summary("Keep this display layout?")
+                .body(&body)
+                .appname("hyprpier")
+                .action("keep", "Keep")
+                .action("revert", "Revert")
+                .timeout(timeout_ms)
+                .show();
+            if let Ok(handle) = handle {
+                handle.wait_for_action(|action| {
+                    let _ = tx.send(action == "keep");
+                });
+            }
+        });
+        rx.recv_timeout(self.remaining()).unwrap_or(false)
+    }
+}
@@ -0,0 +1,315 @@
+//! `hyprpier save`: snapshot the live layout into a profile
+//!
+//! The non-interactive counterpart of the editor's detect (`d`): monitors
+//! come from the running compositor, and workspaces keep the monitors they
+//! are on right now instead of being redistributed. Positions are
+//! re-arranged left to right like the editor does, unless asked to keep
+//! the ones the compositor reports.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::bolt;
+use crate::compositor::{self, Compositor, LiveWorkspace};
+use crate::config;
+use crate::dock::{self, Dock, DockSource};
+use crate::hyprland;
+use crate::metadata::Metadata;
+use crate::profile::{self, Monitor, Profile, Workspace};
+use crate::setup;
+use crate::thunderbolt;
+
+/// How the saved profile was hooked up, printed by `save --json`
+#[derive(Debug, Serialize)]
+struct Saved {
+    profile: String,
+    monitors: usize,
+    workspaces: usize,
+    /// Docks the profile was linked to (several means a dock combination)
+    linked_docks: Vec<String>,
+    undocked: bool,
+}
+
+/// Profile workspaces from the live ones: numbered workspaces on enabled
+/// monitors keep their monitor, and the lowest on each monitor is its
+/// default. Named and special workspaces (id <= 0) are left out.
+fn live_workspaces(live: &[LiveWorkspace], monitors: &[Monitor]) -> Vec<Workspace> {
+    let mut live: Vec<&LiveWorkspace> = live
+        .iter()
+        .filter(|w| (1..=i64::from(u8::MAX)).contains(&w.id))
+        .filter(|w| monitors.iter().any(|m| m.enabled && m.name == w.monitor))
+        .collect();
+    live.sort_by_key(|w| w.id);
+
+    let mut workspaces: Vec<Workspace> = Vec::new();
+    for w in live {
+        let default = !workspaces.iter().any(|ws| ws.monitor == w.monitor);
+        workspaces.push(Workspace {
+            id: w.id as u8,
+            monitor: w.monitor.clone(),
+            default,
+        });
+    }
+    workspaces
+}
+
+/// The docks `--link-dock` links to. `detect_docks` also lists the hub
+/// inside a dock and every external display, and a combo of all of them
+/// would stop matching as soon as one monitor is swapped. So only the most
+/// specific source counts: Thunderbolt, then DisplayLink, then a USB hub.
+/// A display is linked only when nothing else is connected, and then just
+/// the first one. A Thunderbolt device daisy-chained behind another listed
+/// one belongs to that dock's desk, so only the upstream one is linked.
+fn dock_links(docks: Vec<Dock>) -> Vec<Dock> {
+    let rank = |dock: &Dock| match dock.source {
+        DockSource::Thunderbolt => 0,
+        DockSource::DisplayLink => 1,
+        DockSource::Usb => 2,
+        DockSource::Drm => 3,
+    };
+    let Some(best) = docks.iter().map(rank).min() else {
+        return docks;
+    };
+    let mut links: Vec<Dock> = docks.into_iter().filter(|d| rank(d) == best).collect();
+    match links[0].source {
+        DockSource::Drm => links.truncate(1),
+        DockSource::Thunderbolt => {
+            let keys: Vec<(u32, Vec<u8>)> = links
+                .iter()
+                .map(|d| thunderbolt::topology_key(&d.device_id))
+                .collect();
+            let downstream = |(domain, hops): &(u32, Vec<u8>)| {
+                keys.iter()
+                    .any(|(d, h)| d == domain && h.len() < hops.len() && hops.starts_with(h))
+            };
+            let chained: Vec<bool> = keys.iter().map(downstream).collect();
+            links = links
+                .into_iter()
+                .zip(chained)
+                .filter_map(|(dock, chained)| (!chained).then_some(dock))
+                .collect();
+        }
+        _ => {}
+    }
+    links
+}
+
+/// Build a profile from what the compositor shows now
+fn capture(compositor: &dyn Compositor, name: &str, keep_positions: bool) -> Result<Profile> {
+    if !compositor.is_running() {
+        bail!("No compositor running to save the layout from");
+    }
+    // Disabled outputs too, so a closed laptop lid stays disabled
+    let mut monitors = compositor.monitors(true)?;
+    if monitors.is_empty() {
+        bail!("The compositor reports no monitors");
+    }
+    hyprland::sort_monitors(&mut monitors);
+    if !keep_positions {
+        hyprland::arrange_monitors(&mut monitors);
+    }
+
+    let mut workspaces = live_workspaces(&compositor.workspaces()?, &monitors);
+    if workspaces.is_empty() {
+        workspaces = hyprland::generate_workspaces(&monitors);
+    }
+
+    let mut profile = Profile::new(name);
+    profile.lid_switch = hyprland::generate_lid_switch(&monitors);
+    profile.monitors = monitors;
+    profile.workspaces = workspaces;
+    Ok(profile)
+}
+
+/// Save the live layout as profile `name`, optionally linking it to the
+/// connected dock(s) and/or making it the undocked profile
+pub fn save_live(
+    name: &str,
+    link_dock: bool,
+    undocked: bool,
+    keep_positions: bool,
+    force: bool,
+    json: bool,
+) -> Result<()> {
+    profile::validate_profile_name(name)?;
+    if !force && config::profile_path(name)?.exists() {
+        bail!(
+            "Profile '{}' already exists (use --force to replace it)",
+            name
+        );
+    }
+    // Check for docks before writing anything, so a failed link doesn't
+    // leave a half-done save behind
+    let docks = if link_dock {
+        let docks = dock_links(dock::detect_docks()?);
+        if docks.is_empty() {
+            bail!("--link-dock: no dock connected");
+        }
+        docks
+    } else {
+        Vec::new()
+    };
+
+    let compositor = compositor::detect();
+    let mut profile = capture(&*compositor, name, keep_positions)?;
+    if let Ok(existing) = Profile::load(name) {
+        existing.keep_vrr(&mut profile.monitors);
+    }
+    profile.save()?;
+
+    let mut metadata = Metadata::load()?;
+    let uuids: Vec<String> = docks.iter().map(|d| d.uuid.clone()).collect();
+    match uuids.as_slice() {
+        [] => {}
+        [uuid] => metadata.link_dock(uuid, name),
+        _ => metadata.link_dock_combo(&uuids, name),
+    }
+    if undocked {
+        metadata.undocked_profile = Some(name.to_string());
+        metadata.touch();
+    }
+    if let Ok(connected) = hyprland::connected_monitor_descriptions(&*compositor) {
+        metadata.record_seen_monitors(&connected);
+    }
+    metadata.save()?;
+
+    if json {
+        let saved = Saved {
+            profile: name.to_string(),
+            monitors: profile.monitors.len(),
+            workspaces: profile.workspaces.len(),
+            linked_docks: uuids,
+            undocked,
+        };
+        println!("{}", serde_json::to_string_pretty(&saved)?);
+        return Ok(());
+    }
+
+    println!(
+        "Saved profile: {} ({} monitors, {} workspaces)",
+        name,
+        profile.monitors.len(),
+        profile.workspaces.len()
+    );
+    match docks.as_slice() {
+        [] => {}
+        [dock] => println!("Linked to dock: {} ({})", dock.name, dock.uuid),
+        _ => {
+            let names: Vec<&str> = docks.iter().map(|d| d.name.as_str()).collect();
+            println!("Linked to dock combination: {}", names.join(" + "));
+        }
+    }
+    if undocked {
+        println!("Set as the undocked profile");
+    }
+    // A linked Thunderbolt dock joins the allowlist: bolt enrolls it now,
+    // the udev rules only after a re-run of setup
+    if bolt::is_running() {
+        for dock in docks.iter().filter(|d| d.thunderbolt.is_some()) {
+            if let Err(e) = bolt::enroll(&dock.uuid) {
+                println!("Not enrolled with boltd yet: {:#}", e);
+            }
+        }
+    } else if link_dock && setup::rules_outdated() {
+        println!("Run `sudo hyprpier setup` to update the udev rules");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compositor::fake::FakeCompositor;
+    use crate::profile::Position;
+
+    fn desk() -> FakeCompositor {
+        let mut panel = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        panel.position = Position { x: 0, y: 1440 };
+        let mut dell = Monitor::test_fixture("DP-3", "2560x1440", 1.0, 0);
+        dell.position = Position { x: 0, y: 0 };
+        let mut tv = Monitor::test_fixture("HDMI-A-1", "1920x1080", 1.0, 0);
+        tv.enabled = false;
+
+        let fake = FakeCompositor::new(vec![panel, dell, tv]);
+        fake.state.borrow_mut().workspaces = vec![
+            LiveWorkspace {
+                id: 3,
+                monitor: "eDP-1".to_string(),
+            },
+            LiveWorkspace {
+                id: 1,
+                monitor: "DP-3".to_string(),
+            },
+            LiveWorkspace {
+                id: 2,
+                monitor: "DP-3".to_string(),
+            },
+            LiveWorkspace {
+                id: -98,
+                monitor: "DP-3".to_string(),
+            },
+        ];
+        fake
+    }
+
+    #[test]
+    fn captures_live_workspaces_and_positions() {
+        let profile = capture(&desk(), "desk", true).unwrap();
+
+        let names: Vec<&str> = profile.monitors.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["DP-3", "HDMI-A-1", "eDP-1"]);
+        let panel = &profile.monitors[2];
+        assert_eq!((panel.position.x, panel.position.y), (0, 1440));
+        assert!(!profile.monitors[1].enabled);
+
+        let workspaces: Vec<(u8, &str, bool)> = profile
+            .workspaces
+            .iter()
+            .map(|w| (w.id, w.monitor.as_str(), w.default))
+            .collect();
+        assert_eq!(
+            workspaces,
+            vec![(1, "DP-3", true), (2, "DP-3", false), (3, "eDP-1", true)]
+        );
+        assert_eq!(profile.lid_switch.unwrap().monitor, "eDP-1");
+    }
+
+    #[test]
+    fn links_only_the_dock_itself() {
+        crate::sysfs::set_test_root(crate::sysfs::fixture("usb-c-dock"));
+        let docks = dock::detect_docks().unwrap();
+        assert!(docks.iter().any(|d| d.source == DockSource::Drm));
+
+        let links = dock_links(docks.clone());
+        let uuids: Vec<&str> = links.iter().map(|d| d.uuid.as_str()).collect();
+        assert_eq!(uuids, ["displaylink:17e9:6006:1810150012"]);
+
+        // With only displays connected, one of them stands in for the dock
+        let displays: Vec<Dock> = docks
+            .into_iter()
+            .filter(|d| d.source == DockSource::Drm)
+            .flat_map(|d| [d.clone(), d])
+            .collect();
+        assert_eq!(dock_links(displays).len(), 1);
+        assert!(dock_links(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn links_a_daisy_chain_by_its_upstream_dock() {
+        crate::sysfs::set_test_root(crate::sysfs::fixture("daisy-chain"));
+        let docks = dock::detect_docks().unwrap();
+        let ids: Vec<&str> = docks.iter().map(|d| d.device_id.as_str()).collect();
+        assert_eq!(ids, ["0-1", "0-301"]);
+
+        let links = dock_links(docks);
+        let ids: Vec<&str> = links.iter().map(|d| d.device_id.as_str()).collect();
+        assert_eq!(ids, ["0-1"]);
+    }
+
+    #[test]
+    fn arranges_positions_unless_kept() {
+        let profile = capture(&desk(), "desk", false).unwrap();
+        let panel = &profile.monitors[2];
+        assert_eq!((panel.position.x, panel.position.y), (2560, 0));
+    }
+}
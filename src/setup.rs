@@ -1,31 +1,76 @@
 //! udev rules and systemd service installation for auto-switching
 
 use anyhow::{bail, Context, Result};
+use serde::Serialize;
 use std::fs;
 use std::path::Path;
+use std::process::Command;
 
+use crate::bolt;
+use crate::config;
+use crate::metadata::Metadata;
+use crate::sysfs;
 use crate::thunderbolt;
 
 const UDEV_RULES_PATH: &str = "/etc/udev/rules.d/99-hyprpier.rules";
 const SYSTEMD_SERVICE_PATH: &str = "/etc/systemd/system/hyprpier-resume.service";
-const THUNDERBOLT_PATH: &str = "/sys/bus/thunderbolt/devices";
+const DAEMON_UNIT: &str = "hyprpier.service";
+const SESSION_TARGET: &str = "graphical-session.target";
+
+/// State of the daemon's systemd user unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DaemonService {
+    NotInstalled,
+    /// Installed, but for another binary path
+    Outdated,
+    Installed,
+}
 
-/// Generate the udev rules content
-fn generate_rules() -> Result<String> {
+/// Generate the udev rules content. Only the allowlisted Thunderbolt
+/// UUIDs get authorized; every other device is left for the user to decide
+/// on. With `bolt_managed` there are no authorize rules at all: the docks
+/// are enrolled with boltd instead.
+fn generate_rules(allowlist: &[String], bolt_managed: bool) -> Result<String> {
     let exe_path = std::env::current_exe()
         .context("Failed to get hyprpier executable path")?;
 
     let exe = exe_path.to_string_lossy();
 
+    let authorize: String = if bolt_managed {
+        "# (boltd manages authorization; allowlisted docks are enrolled with it)\n".to_string()
+    } else if allowlist.is_empty() {
+        "# (none yet: link a dock to a profile or `hyprpier thunderbolt --trust <uuid>`)\n"
+            .to_string()
+    } else {
+        allowlist
+            .iter()
+            .map(|uuid| format!(
+                "ACTION==\"add\", SUBSYSTEM==\"thunderbolt\", ATTR{{unique_id}}==\"{uuid}\", ATTR{{authorized}}==\"0\", ATTR{{authorized}}=\"1\"\n"
+            ))
+            .collect()
+    };
+
     Ok(format!(r#"# Hyprpier - Hyprland monitor profile auto-switching
 # Generated by: hyprpier setup
 
-# Auto-authorize all Thunderbolt devices
-ACTION=="add", SUBSYSTEM=="thunderbolt", ATTR{{authorized}}=="0", ATTR{{authorized}}="1"
-
+# Authorize allowlisted Thunderbolt devices (docks linked to a profile and
+# trusted devices); re-run `hyprpier setup` after changing the links
+{authorize}
 # Notify daemon of dock events
 ACTION=="add", SUBSYSTEM=="thunderbolt", RUN+="{exe} notify"
 ACTION=="remove", SUBSYSTEM=="thunderbolt", RUN+="{exe} notify"
+
+# USB-C docks (their hub) and DisplayLink adapters. Attributes are gone by
+# the time a device is removed, so removals match the kernel's uevent
+# TYPE (class/subclass/protocol) and PRODUCT (vendor/product/bcdDevice)
+ACTION=="add", SUBSYSTEM=="usb", ENV{{DEVTYPE}}=="usb_device", ATTR{{bDeviceClass}}=="09", RUN+="{exe} notify"
+ACTION=="add", SUBSYSTEM=="usb", ENV{{DEVTYPE}}=="usb_device", ATTR{{idVendor}}=="17e9", RUN+="{exe} notify"
+ACTION=="remove", SUBSYSTEM=="usb", ENV{{DEVTYPE}}=="usb_device", ENV{{TYPE}}=="9/*", RUN+="{exe} notify"
+ACTION=="remove", SUBSYSTEM=="usb", ENV{{DEVTYPE}}=="usb_device", ENV{{PRODUCT}}=="17e9/*", RUN+="{exe} notify"
+
+# Displays on DRM connectors (USB-C DP alt mode)
+ACTION=="change", SUBSYSTEM=="drm", ENV{{HOTPLUG}}=="1", RUN+="{exe} notify"
 "#))
 }
 
@@ -34,9 +79,49 @@ pub fn is_installed() -> bool {
     Path::new(UDEV_RULES_PATH).exists()
 }
 
+/// Who authorizes Thunderbolt devices: "boltd" when it's running (whether
+/// or not our rules are installed), "udev" for our allowlist rules, else
+/// "none"
+pub fn authorizer() -> &'static str {
+    if bolt::is_running() {
+        "boltd"
+    } else if is_installed() {
+        "udev"
+    } else {
+        "none"
+    }
+}
+
+/// Whether the installed rules authorize a different set of devices (or
+/// call a different binary) than `hyprpier setup` would write now
+pub fn rules_outdated() -> bool {
+    let Ok(installed) = fs::read_to_string(UDEV_RULES_PATH) else {
+        return false;
+    };
+    let allowlist = Metadata::load().map(|m| m.allowlist()).unwrap_or_default();
+    generate_rules(&allowlist, bolt::is_running()).is_ok_and(|rules| rules != installed)
+}
+
+/// The binary the installed rules run on dock events, if they're installed
+pub fn rules_exe() -> Option<String> {
+    let installed = fs::read_to_string(UDEV_RULES_PATH).ok()?;
+    exe_in_rules(&installed).map(str::to_string)
+}
+
+/// The `<exe>` of the first `RUN+="<exe> notify"` in a rules file
+fn exe_in_rules(rules: &str) -> Option<&str> {
+    rules.lines().find_map(|line| {
+        let (_, run) = line.split_once("RUN+=\"")?;
+        run.split_once("\"")?.0.strip_suffix(" notify")
+    })
+}
+
 /// Install udev rules (called via sudo from TUI)
 pub fn install() -> Result<()> {
-    let rules = generate_rules()?;
+    // Under sudo this is still the invoking user's metadata
+    let allowlist = Metadata::load()?.allowlist();
+    let bolt_managed = bolt::is_running();
+    let rules = generate_rules(&allowlist, bolt_managed)?;
 
     fs::write(UDEV_RULES_PATH, &rules)
         .with_context(|| format!("Failed to write {}", UDEV_RULES_PATH))?;
@@ -45,9 +130,18 @@ pub fn install() -> Result<()> {
 
     let exe = std::env::current_exe()?;
     println!();
-    println!("Note: the rules auto-authorize every Thunderbolt device on connect.");
-    println!("      This disables Thunderbolt security checks (DMA attack surface);");
-    println!("      only use it on machines where you control physical access.");
+    if bolt_managed {
+        println!("Note: boltd is running, so it manages Thunderbolt authorization.");
+        println!("      Allowlisted docks are enrolled with it (policy auto) when");
+        println!("      connected; other devices are up to bolt's own policy.");
+    } else {
+        println!(
+            "Note: the rules authorize {} allowlisted Thunderbolt device(s) on connect.",
+            allowlist.len()
+        );
+        println!("      Other devices stay unauthorized; link or trust one, then re-run");
+        println!("      `hyprpier setup` to allow it.");
+    }
     println!("Note: the rules invoke this binary by absolute path:");
     println!("        {}", exe.display());
     println!("      If you move or delete it, re-run `hyprpier setup`.");
@@ -58,22 +152,48 @@ pub fn install() -> Result<()> {
         .args(["control", "--reload-rules"])
         .status();
 
-    // Authorize any currently connected unauthorized devices
-    authorize_connected_devices();
+    // Authorize allowlisted devices that are already connected
+    if bolt_managed {
+        enroll_connected_devices(&allowlist);
+    } else {
+        authorize_connected_devices(&allowlist);
+    }
 
     println!("Auto-switching enabled!");
 
     Ok(())
 }
 
-/// Authorize all currently connected Thunderbolt devices
-fn authorize_connected_devices() {
-    let tb_path = Path::new(THUNDERBOLT_PATH);
+/// Enroll the allowlisted devices boltd sees connected. The rest are
+/// enrolled by the daemon when they're next plugged in.
+fn enroll_connected_devices(allowlist: &[String]) {
+    let devices = match bolt::devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("Could not list boltd devices: {:#}", e);
+            return;
+        }
+    };
+    for device in devices {
+        if device.status == "disconnected" || !allowlist.contains(&device.uid) {
+            continue;
+        }
+        match bolt::enroll(&device.uid) {
+            Ok(true) => println!("Enrolled with boltd: {}", device.name),
+            Ok(false) => {}
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+}
+
+/// Authorize the currently connected Thunderbolt devices on the allowlist
+fn authorize_connected_devices(allowlist: &[String]) {
+    let tb_path = sysfs::thunderbolt_devices();
     if !tb_path.exists() {
         return;
     }
 
-    let entries = match fs::read_dir(tb_path) {
+    let entries = match fs::read_dir(&tb_path) {
         Ok(e) => e,
         Err(_) => return,
     };
@@ -93,11 +213,16 @@ fn authorize_connected_devices() {
         // Check if unauthorized
         if let Ok(status) = fs::read_to_string(&auth_path) {
             if status.trim() == "0" {
+                let device_name = fs::read_to_string(device_path.join("device_name"))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(|_| name_str.to_string());
+                let uuid = thunderbolt::read_attr(&device_path, "unique_id").unwrap_or_default();
+                if !allowlist.contains(&uuid) {
+                    println!("Not allowlisted, left unauthorized: {} ({})", device_name, uuid);
+                    continue;
+                }
                 // Authorize the device
                 if fs::write(&auth_path, "1").is_ok() {
-                    let device_name = fs::read_to_string(device_path.join("device_name"))
-                        .map(|s| s.trim().to_string())
-                        .unwrap_or_else(|_| name_str.to_string());
                     println!("Authorized: {}", device_name);
                 }
             }
@@ -134,7 +259,9 @@ pub fn is_resume_service_installed() -> bool {
     Path::new(SYSTEMD_SERVICE_PATH).exists()
 }
 
-/// Generate the systemd resume service content
+/// Generate the systemd resume service content. After the rescan it
+/// re-authorizes only devices that came back authorized (by the allowlist
+/// rules), so a reset never lets an unknown device in.
 fn generate_resume_service(pci_addresses: &[String]) -> String {
     let remove_cmds: String = pci_addresses
         .iter()
@@ -167,7 +294,7 @@ ExecStart=/bin/sh -c '\
   done; \
   sleep 2; \
   for dev in /sys/bus/thunderbolt/devices/[0-9]-[1-9]*; do \
-    [ -f "$dev/authorized" ] || continue; \
+    grep -q 1 "$dev/authorized" 2>/dev/null || continue; \
     echo 0 > "$dev/authorized" 2>/dev/null || true; \
     sleep 1; \
     echo 1 > "$dev/authorized" 2>/dev/null || true; \
@@ -245,3 +372,237 @@ pub fn uninstall_resume_service() -> Result<()> {
 
     Ok(())
 }
+
+/// Generate the daemon's systemd user unit, tied to the graphical session
+/// so it starts with the compositor and stops with it
+fn generate_daemon_unit() -> Result<String> {
+    let exe_path = std::env::current_exe()
+        .context("Failed to get hyprpier executable path")?;
+
+    let exe = exe_path.to_string_lossy();
+
+    Ok(format!(r#"# Hyprpier - monitor profile daemon
+# Generated by: hyprpier setup --daemon
+
+[Unit]
+Description=Hyprpier monitor profile daemon
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=simple
+ExecStart={exe} daemon
+Restart=on-failure
+
+[Install]
+WantedBy=graphical-session.target
+"#))
+}
+
+/// Run `systemctl --user`, with its stderr as the error
+fn systemctl_user(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context("Failed to run systemctl")?;
+    if !output.status.success() {
+        bail!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Whether the daemon's user unit is installed, and for this binary
+pub fn daemon_service() -> DaemonService {
+    let Some(installed) = config::daemon_user_unit()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+    else {
+        return DaemonService::NotInstalled;
+    };
+    match generate_daemon_unit() {
+        Ok(unit) if unit == installed => DaemonService::Installed,
+        _ => DaemonService::Outdated,
+    }
+}
+
+/// Whether systemd reports the daemon's user unit running
+pub fn daemon_service_active() -> bool {
+    user_unit_active(DAEMON_UNIT)
+}
+
+/// Whether the user manager has `graphical-session.target` up, which the
+/// daemon's unit starts with. uwsm and session managers that run the
+/// compositor as a systemd unit activate it; a compositor started straight
+/// from a TTY or a display manager's plain session doesn't.
+pub fn graphical_session_active() -> bool {
+    user_unit_active(SESSION_TARGET)
+}
+
+fn user_unit_active(unit: &str) -> bool {
+    Command::new("systemctl")
+        .args(["--user", "is-active", "--quiet", unit])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Write, enable and start the daemon's user unit. An outdated unit is
+/// rewritten and the daemon restarted on the new binary. Prints nothing,
+/// so the TUI can call it directly.
+pub fn install_daemon_service() -> Result<()> {
+    if std::env::var("SUDO_USER").is_ok_and(|u| !u.is_empty()) {
+        bail!("The daemon is a user service; run `hyprpier setup --daemon` without sudo");
+    }
+
+    let path = config::daemon_user_unit()?;
+    let unit = generate_daemon_unit()?;
+    let changed = fs::read_to_string(&path).ok().as_deref() != Some(unit.as_str());
+    if changed {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, &unit)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        systemctl_user(&["daemon-reload"])?;
+    }
+
+    systemctl_user(&["enable", "--now", DAEMON_UNIT])?;
+    if changed {
+        // A daemon that was already running is still the old binary
+        systemctl_user(&["try-restart", DAEMON_UNIT])?;
+    }
+    Ok(())
+}
+
+/// Stop, disable and remove the daemon's user unit
+pub fn uninstall_daemon_service() -> Result<()> {
+    let path = config::daemon_user_unit()?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    // Stopping fails harmlessly when it isn't running
+    let _ = systemctl_user(&["disable", "--now", DAEMON_UNIT]);
+    fs::remove_file(&path)
+        .with_context(|| format!("Failed to remove {}", path.display()))?;
+    systemctl_user(&["daemon-reload"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs;
+
+    fn copy_tree(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap().flatten() {
+            let target = to.join(entry.file_name());
+            if entry.path().is_dir() {
+                copy_tree(&entry.path(), &target);
+            } else {
+                fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    /// A scratch copy of a fixture, since authorizing writes to the tree
+    fn scratch_copy(fixture: &str, test: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "hyprpier-setup-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        copy_tree(&sysfs::fixture(fixture), &root);
+        sysfs::set_test_root(root.clone());
+        root
+    }
+
+    fn authorized(device: &str) -> Option<String> {
+        thunderbolt::read_attr(&sysfs::thunderbolt_devices().join(device), "authorized")
+    }
+
+    #[test]
+    fn authorizes_allowlisted_chained_devices() {
+        let root = scratch_copy("daisy-chain", "allowlisted");
+
+        authorize_connected_devices(&["2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f".to_string()]);
+
+        assert_eq!(authorized("0-1").as_deref(), Some("1"));
+        assert_eq!(authorized("0-301").as_deref(), Some("1"));
+        // Hosts have no authorized attribute and are left alone
+        assert_eq!(authorized("0-0"), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn leaves_unknown_devices_unauthorized() {
+        let root = scratch_copy("daisy-chain", "unknown");
+
+        authorize_connected_devices(&["f5a1e4b2-3c4d-4e5f-8a9b-0c1d2e3f4a5b".to_string()]);
+
+        assert_eq!(authorized("0-301").as_deref(), Some("0"));
+        assert!(thunderbolt::is_unauthorized("0-301"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn rules_match_each_allowlisted_uuid() {
+        let uuid = "2b7e4f91-1d3a-4c5b-8e6f-7a9b0c1d2e3f";
+        let rules = generate_rules(&[uuid.to_string()], false).unwrap();
+        let authorizing: Vec<&str> = rules
+            .lines()
+            .filter(|l| l.contains(r#"ATTR{authorized}="1""#))
+            .collect();
+        assert_eq!(
+            authorizing,
+            [format!(
+                r#"ACTION=="add", SUBSYSTEM=="thunderbolt", ATTR{{unique_id}}=="{uuid}", ATTR{{authorized}}=="0", ATTR{{authorized}}="1""#
+            )]
+        );
+
+        let rules = generate_rules(&[], false).unwrap();
+        assert!(!rules.contains(r#"ATTR{authorized}="1""#));
+
+        // boltd does the authorizing, so the rules only notify
+        let rules = generate_rules(&[uuid.to_string()], true).unwrap();
+        assert!(!rules.contains(r#"ATTR{authorized}="1""#));
+        assert!(rules.contains(r#"RUN+="#));
+    }
+
+    #[test]
+    fn reads_the_binary_the_rules_run() {
+        let rules = generate_rules(&[], false).unwrap();
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(exe_in_rules(&rules), Some(&*exe.to_string_lossy()));
+        assert_eq!(exe_in_rules("# nothing here\n"), None);
+    }
+
+    #[test]
+    fn daemon_unit_tracks_the_binary_path() {
+        let dir = std::env::temp_dir().join(format!("hyprpier-unit-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        config::set_test_config_dir(dir.clone());
+        assert_eq!(daemon_service(), DaemonService::NotInstalled);
+
+        let path = config::daemon_user_unit().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let unit = generate_daemon_unit().unwrap();
+        let exe = std::env::current_exe().unwrap();
+        assert!(unit.contains(&format!("ExecStart={} daemon\n", exe.display())));
+        assert!(unit.contains("WantedBy=graphical-session.target"));
+        fs::write(&path, &unit).unwrap();
+        assert_eq!(daemon_service(), DaemonService::Installed);
+
+        // The binary moved since the unit was written
+        fs::write(&path, unit.replace(&*exe.to_string_lossy(), "/old/hyprpier")).unwrap();
+        assert_eq!(daemon_service(), DaemonService::Outdated);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
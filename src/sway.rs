@@ -0,0 +1,624 @@
+//! Sway backend
+//!
+//! Profiles translate to sway `output` and `workspace ... output` directives
+//! in ~/.config/sway/monitors.conf (pulled in with `include`), and runtime
+//! applies go over sway's IPC socket ($SWAYSOCK) as `RUN_COMMAND` messages.
+//! Hyprland's monitor descriptions ("make model serial") are the same
+//! identifiers sway reports, so one profile works under both.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crate::compositor::{
+    Compositor, Dialect, LiveClient, LiveMonitorState, LiveWorkspace, Mode, OutputModes,
+    WorkspaceRef,
+};
+use crate::profile::{Monitor, MonitorOptions, Position, Profile};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LEN: usize = 14;
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+/// A wedged compositor must not hang an apply (or the daemon) forever
+const IO_TIMEOUT_MS: u64 = 5000;
+
+/// Sway (and kanshi) transform names, indexed by Hyprland's transform number
+pub const TRANSFORMS: [&str; 8] = [
+    "normal",
+    "90",
+    "180",
+    "270",
+    "flipped",
+    "flipped-90",
+    "flipped-180",
+    "flipped-270",
+];
+
+/// Whether this is a sway session: $SWAYSOCK set and no Hyprland instance
+/// (a Hyprland started from inside sway inherits SWAYSOCK)
+pub fn detected() -> bool {
+    std::env::var_os("SWAYSOCK").is_some()
+        && std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_none()
+}
+
+/// The running sway instance, reached over $SWAYSOCK
+pub struct Sway;
+
+impl Compositor for Sway {
+    fn is_running(&self) -> bool {
+        connect().is_ok()
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Sway
+    }
+
+    fn monitors(&self, all: bool) -> Result<Vec<Monitor>> {
+        let outputs: Vec<SwayOutput> = query(GET_OUTPUTS)?;
+        Ok(outputs
+            .into_iter()
+            .map(Monitor::from)
+            .filter(|m| all || m.enabled)
+            .collect())
+    }
+
+    fn available_modes(&self) -> Result<Vec<OutputModes>> {
+        let outputs: Vec<SwayOutput> = query(GET_OUTPUTS)?;
+        Ok(outputs
+            .iter()
+            .map(|o| OutputModes {
+                name: o.name.clone(),
+                description: o.description(),
+                modes: o.modes.iter().map(Mode::from).collect(),
+            })
+            .collect())
+    }
+
+    fn workspaces(&self) -> Result<Vec<LiveWorkspace>> {
+        let workspaces: Vec<SwayWorkspace> = query(GET_WORKSPACES)?;
+        Ok(workspaces
+            .into_iter()
+            .map(|w| LiveWorkspace {
+                id: w.num,
+                monitor: w.output,
+            })
+            .collect())
+    }
+
+    fn clients(&self) -> Result<Vec<LiveClient>> {
+        let tree: SwayNode = query(GET_TREE)?;
+        let mut clients = Vec::new();
+        collect_clients(&tree, None, &mut clients);
+        Ok(clients)
+    }
+
+    /// Each output's visible workspace, with the focused one marked
+    fn monitor_states(&self) -> Result<Vec<LiveMonitorState>> {
+        let workspaces: Vec<SwayWorkspace> = query(GET_WORKSPACES)?;
+        Ok(workspaces
+            .into_iter()
+            .filter(|w| w.visible)
+            .map(|w| LiveMonitorState {
+                focused: w.focused,
+                active_workspace: WorkspaceRef { id: w.num },
+            })
+            .collect())
+    }
+
+    /// One connection, one `RUN_COMMAND` per step: a step may itself be a
+    /// `;`-separated command list, so steps can't simply be joined
+    fn eval(&self, steps: &[String]) -> Vec<Result<()>> {
+        if steps.is_empty() {
+            return Vec::new();
+        }
+        let mut stream = match connect() {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("{:#}", e);
+                return steps.iter().map(|_| Err(anyhow::anyhow!("{}", msg))).collect();
+            }
+        };
+        steps
+            .iter()
+            .map(|step| {
+                let reply = message(&mut stream, RUN_COMMAND, step)?;
+                check_command_reply(&reply).with_context(|| format!("swaymsg {} failed", step))
+            })
+            .collect()
+    }
+
+    fn reload(&self) -> Result<()> {
+        let mut stream = connect()?;
+        check_command_reply(&message(&mut stream, RUN_COMMAND, "reload")?)
+            .context("swaymsg reload failed")
+    }
+
+    /// Sway can only move the focused workspace, so focus it, move it, and
+    /// put focus back. A workspace that doesn't exist yet needs no move;
+    /// its `workspace N output X` line takes effect when it's created.
+    fn move_workspace(&self, workspace_id: u8, monitor: &str) -> Result<()> {
+        let workspaces: Vec<SwayWorkspace> = query(GET_WORKSPACES)?;
+        let id = i64::from(workspace_id);
+        let Some(ws) = workspaces.iter().find(|w| w.num == id) else {
+            return Ok(());
+        };
+        if ws.output == monitor {
+            return Ok(());
+        }
+        let focused = workspaces.iter().find(|w| w.focused).map(|w| w.name.clone());
+        let mut step = move_workspace_command(workspace_id, monitor);
+        if let Some(name) = focused {
+            step.push_str(&format!("; workspace {}", quote(&name)));
+        }
+        self.eval(&[step])
+            .pop()
+            .unwrap_or(Ok(()))
+            .with_context(|| format!("Failed to move workspace {}", workspace_id))
+    }
+}
+
+/// Output info from GET_OUTPUTS
+#[derive(Debug, Deserialize)]
+struct SwayOutput {
+    name: String,
+    #[serde(default)]
+    make: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    serial: String,
+    #[serde(default)]
+    active: bool,
+    current_mode: Option<SwayMode>,
+    rect: SwayRect,
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    transform: Option<String>,
+    /// "enabled" or "disabled"
+    #[serde(default)]
+    adaptive_sync_status: Option<String>,
+    #[serde(default)]
+    modes: Vec<SwayMode>,
+}
+
+impl SwayOutput {
+    /// "make model serial", the same identifier Hyprland reports
+    fn description(&self) -> Option<String> {
+        let description = [&self.make, &self.model, &self.serial]
+            .into_iter()
+            .filter(|s| !s.is_empty() && *s != "Unknown")
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!description.is_empty()).then_some(description)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SwayMode {
+    width: i32,
+    height: i32,
+    /// Millihertz
+    refresh: i64,
+}
+
+impl From<&SwayMode> for Mode {
+    fn from(m: &SwayMode) -> Self {
+        Mode {
+            width: m.width,
+            height: m.height,
+            refresh_rate: m.refresh as f64 / 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+}
+
+impl From<SwayOutput> for Monitor {
+    fn from(o: SwayOutput) -> Self {
+        let description = o.description();
+        let (width, height, refresh_rate) = o
+            .current_mode
+            .map(|m| (m.width, m.height, m.refresh as f64 / 1000.0))
+            .unwrap_or((0, 0, 0.0));
+        Monitor {
+            resolution: format!("{}x{}", width, height),
+            mode: format!("{}x{}@{:.0}", width, height, refresh_rate),
+            name: o.name,
+            description,
+            enabled: o.active,
+            refresh_rate,
+            position: Position {
+                x: o.rect.x,
+                y: o.rect.y,
+            },
+            // Inactive outputs report scale -1
+            scale: o.scale.filter(|&s| s > 0.0).unwrap_or(1.0),
+            transform: o
+                .transform
+                .and_then(|t| TRANSFORMS.iter().position(|&name| name == t))
+                .unwrap_or(0) as u8,
+            options: MonitorOptions {
+                vrr: (o.adaptive_sync_status.as_deref() == Some("enabled")).then_some(1),
+                ..MonitorOptions::default()
+            },
+        }
+    }
+}
+
+/// Workspace info from GET_WORKSPACES
+#[derive(Debug, Deserialize)]
+struct SwayWorkspace {
+    /// -1 for named workspaces without a number
+    num: i64,
+    name: String,
+    output: String,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    visible: bool,
+}
+
+/// A node of the GET_TREE reply
+#[derive(Debug, Deserialize)]
+struct SwayNode {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    num: Option<i64>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+/// Every window (a leaf container) below `node`, with the number of the
+/// workspace it sits on
+fn collect_clients(node: &SwayNode, workspace: Option<i64>, out: &mut Vec<LiveClient>) {
+    let workspace = if node.kind == "workspace" {
+        node.num
+    } else {
+        workspace
+    };
+    let is_window = matches!(node.kind.as_str(), "con" | "floating_con")
+        && node.nodes.is_empty()
+        && node.floating_nodes.is_empty();
+    if let (true, Some(ws)) = (is_window, workspace) {
+        out.push(LiveClient {
+            address: node.id.to_string(),
+            workspace: WorkspaceRef { id: ws },
+        });
+    }
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_clients(child, workspace, out);
+    }
+}
+
+/// Reply to RUN_COMMAND: one `{"success": bool, "error": ...}` per command
+#[derive(Debug, Deserialize)]
+struct CommandResult {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+fn check_command_reply(reply: &str) -> Result<()> {
+    let results: Vec<CommandResult> =
+        serde_json::from_str(reply).context("Failed to parse sway command reply")?;
+    match results.into_iter().find(|r| !r.success) {
+        Some(r) => anyhow::bail!("{}", r.error.unwrap_or_else(|| "unknown error".to_string())),
+        None => Ok(()),
+    }
+}
+
+fn connect() -> Result<UnixStream> {
+    let path = std::env::var_os("SWAYSOCK").context("SWAYSOCK not set - is sway running?")?;
+    let stream = UnixStream::connect(&path).context("Failed to connect to sway IPC socket")?;
+    stream.set_read_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(IO_TIMEOUT_MS)))?;
+    Ok(stream)
+}
+
+/// A connection subscribed to output events (displays connected,
+/// disconnected or reconfigured). Reads block until the next event.
+pub fn subscribe_outputs() -> Result<UnixStream> {
+    let mut stream = connect()?;
+    let reply = message(&mut stream, SUBSCRIBE, r#"["output"]"#)?;
+    let reply: serde_json::Value =
+        serde_json::from_str(&reply).context("Failed to parse sway subscribe reply")?;
+    if reply.get("success").and_then(serde_json::Value::as_bool) != Some(true) {
+        anyhow::bail!("sway refused the output event subscription");
+    }
+    stream.set_read_timeout(None)?;
+    Ok(stream)
+}
+
+/// Wait for the next event on a subscribed connection
+pub fn next_event(stream: &mut UnixStream) -> Result<String> {
+    read_message(stream)
+}
+
+/// Frame an IPC message: magic, payload length, type (native endian), payload
+fn encode_message(kind: u32, payload: &str) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(HEADER_LEN + payload.len());
+    msg.extend_from_slice(MAGIC);
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&kind.to_ne_bytes());
+    msg.extend_from_slice(payload.as_bytes());
+    msg
+}
+
+/// Send one message and read its reply on an open connection
+fn message(stream: &mut UnixStream, kind: u32, payload: &str) -> Result<String> {
+    stream
+        .write_all(&encode_message(kind, payload))
+        .context("Failed to write to sway IPC socket")?;
+    read_message(stream)
+}
+
+/// Read one framed message (a reply or an event) and return its payload
+fn read_message(stream: &mut UnixStream) -> Result<String> {
+    let mut header = [0u8; HEADER_LEN];
+    stream
+        .read_exact(&mut header)
+        .context("Failed to read from sway IPC socket")?;
+    if &header[..6] != MAGIC {
+        anyhow::bail!("Unexpected reply on sway IPC socket");
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let mut body = vec![0u8; len];
+    stream
+        .read_exact(&mut body)
+        .context("Failed to read from sway IPC socket")?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn query<T: DeserializeOwned>(kind: u32) -> Result<T> {
+    let reply = message(&mut connect()?, kind, "")?;
+    serde_json::from_str(&reply).context("Failed to parse sway IPC reply")
+}
+
+/// Quote an argument for a sway command
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Arguments of an `output` directive (everything after the output name)
+fn output_fields(monitor: &Monitor) -> String {
+    if !monitor.enabled {
+        return "disable".to_string();
+    }
+    let mut fields = Vec::new();
+    // Profiles imported without a mode say "preferred", which sway spells
+    // by leaving the mode out
+    if monitor.resolution.contains('x') {
+        if monitor.refresh_rate > 0.0 {
+            fields.push(format!("mode {}@{:.3}Hz", monitor.resolution, monitor.refresh_rate));
+        } else {
+            fields.push(format!("mode {}", monitor.resolution));
+        }
+    }
+    fields.push(format!("position {} {}", monitor.position.x, monitor.position.y));
+    fields.push(format!("scale {}", monitor.scale));
+    let transform = TRANSFORMS
+        .get(usize::from(monitor.transform))
+        .copied()
+        .unwrap_or("normal");
+    fields.push(format!("transform {}", transform));
+    // sway has no per-output color management or mirroring
+    if let Some(vrr) = monitor.options.vrr {
+        let state = if vrr == 0 { "off" } else { "on" };
+        fields.push(format!("adaptive_sync {}", state));
+    }
+    if let Some(bitdepth) = monitor.options.bitdepth {
+        fields.push(format!("render_bit_depth {}", bitdepth));
+    }
+    fields.push("enable".to_string());
+    fields.join(" ")
+}
+
+/// Runtime command (and config directive) that applies one monitor
+pub fn output_command(monitor: &Monitor) -> String {
+    format!("output {} {}", quote(&monitor.name), output_fields(monitor))
+}
+
+/// Runtime command that moves a workspace to an output. Leaves the
+/// workspace focused; see `Sway::move_workspace` for the focus-preserving
+/// version.
+pub fn move_workspace_command(workspace_id: u8, monitor: &str) -> String {
+    format!(
+        "workspace number {}; move workspace to output {}",
+        workspace_id,
+        quote(monitor)
+    )
+}
+
+/// Runtime command that moves a window (by container id) to a workspace
+pub fn move_window_command(con_id: &str, workspace_id: u8) -> String {
+    format!(
+        "[con_id={}] move container to workspace number {}",
+        con_id, workspace_id
+    )
+}
+
+pub fn focus_workspace_command(workspace_id: i64) -> String {
+    format!("workspace number {}", workspace_id)
+}
+
+/// Generate the sway include file for a profile. Loaded from the sway
+/// config via `include ~/.config/sway/monitors.conf`.
+pub fn generate_config(profile: &Profile) -> String {
+    let mut lines = vec![format!(
+        "# Generated by hyprpier from profile: {}",
+        profile.name
+    )];
+    lines.push(format!(
+        "# {}",
+        profile.description.as_deref().unwrap_or("No description")
+    ));
+    lines.push(String::new());
+
+    for monitor in &profile.monitors {
+        lines.push(output_command(monitor));
+    }
+
+    if !profile.workspaces.is_empty() {
+        lines.push(String::new());
+        for ws in &profile.workspaces {
+            lines.push(format!("workspace {} output {}", ws.id, quote(&ws.monitor)));
+        }
+    }
+
+    // Sway re-enables an output on lid open by itself; --reload keeps the
+    // bind from firing on every config reload with the lid already closed
+    if let Some(ref lid) = profile.lid_switch {
+        if lid.enabled && profile.monitors.iter().any(|m| m.name == lid.monitor) {
+            lines.push(String::new());
+            lines.push("# Lid switch handling".to_string());
+            lines.push(format!(
+                "bindswitch --locked --reload lid:on output {} disable",
+                quote(&lid.monitor)
+            ));
+            lines.push(format!(
+                "bindswitch --locked --reload lid:off output {} enable",
+                quote(&lid.monitor)
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{LidSwitch, Workspace};
+
+    #[test]
+    fn generate_config_emits_outputs_and_workspaces() {
+        let mut external = Monitor::test_fixture("DP-1", "2560x1440", 1.25, 1);
+        external.refresh_rate = 59.951;
+        let mut panel = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        panel.enabled = false;
+        let mut profile = Profile::new("desk");
+        profile.monitors = vec![external, panel];
+        profile.workspaces = vec![Workspace {
+            id: 1,
+            monitor: "DP-1".to_string(),
+            default: true,
+        }];
+        profile.lid_switch = Some(LidSwitch {
+            enabled: true,
+            monitor: "eDP-1".to_string(),
+        });
+
+        let config = generate_config(&profile);
+        assert!(config.contains(
+            "output \"DP-1\" mode 2560x1440@59.951Hz position 0 0 scale 1.25 transform 90 enable"
+        ));
+        assert!(config.contains("output \"eDP-1\" disable"));
+        assert!(config.contains("workspace 1 output \"DP-1\""));
+        assert!(config.contains("bindswitch --locked --reload lid:on output \"eDP-1\" disable"));
+    }
+
+    #[test]
+    fn output_json_becomes_monitor() {
+        let json = r#"[
+            {"name": "DP-3", "make": "Dell Inc.", "model": "DELL U2720Q", "serial": "8KX",
+             "active": true, "scale": 1.5, "transform": "flipped-90",
+             "current_mode": {"width": 3840, "height": 2160, "refresh": 59997},
+             "rect": {"x": 1920, "y": 0, "width": 2560, "height": 1440}},
+            {"name": "HDMI-A-1", "make": "Unknown", "model": "Unknown", "serial": "Unknown",
+             "active": false, "scale": -1.0, "current_mode": null,
+             "rect": {"x": 0, "y": 0, "width": 0, "height": 0}}
+        ]"#;
+        let outputs: Vec<SwayOutput> = serde_json::from_str(json).unwrap();
+        let monitors: Vec<Monitor> = outputs.into_iter().map(Monitor::from).collect();
+
+        let dell = &monitors[0];
+        assert_eq!(dell.description.as_deref(), Some("Dell Inc. DELL U2720Q 8KX"));
+        assert_eq!(dell.mode, "3840x2160@60");
+        assert_eq!((dell.position.x, dell.position.y), (1920, 0));
+        assert_eq!(dell.scale, 1.5);
+        assert_eq!(dell.transform, 5);
+
+        let hdmi = &monitors[1];
+        assert!(!hdmi.enabled);
+        assert_eq!(hdmi.description, None);
+        assert_eq!(hdmi.scale, 1.0);
+    }
+
+    #[test]
+    fn tree_windows_are_clients_of_their_workspace() {
+        let json = r#"{"id": 1, "type": "root", "nodes": [
+            {"id": 2, "type": "output", "nodes": [
+                {"id": 3, "type": "workspace", "num": 4, "nodes": [
+                    {"id": 10, "type": "con", "nodes": [
+                        {"id": 11, "type": "con"},
+                        {"id": 12, "type": "con"}
+                    ]}
+                ], "floating_nodes": [{"id": 13, "type": "floating_con"}]}
+            ]}
+        ]}"#;
+        let tree: SwayNode = serde_json::from_str(json).unwrap();
+        let mut clients = Vec::new();
+        collect_clients(&tree, None, &mut clients);
+        let found: Vec<(String, i64)> = clients
+            .into_iter()
+            .map(|c| (c.address, c.workspace.id))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("11".to_string(), 4),
+                ("12".to_string(), 4),
+                ("13".to_string(), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn command_reply_reports_first_failure() {
+        assert!(check_command_reply(r#"[{"success": true}]"#).is_ok());
+        let err = check_command_reply(
+            r#"[{"success": true}, {"success": false, "error": "Unknown output"}]"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Unknown output");
+    }
+
+    #[test]
+    fn message_round_trips_over_socket() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut header = [0u8; HEADER_LEN];
+            server.read_exact(&mut header).unwrap();
+            let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+            let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+            let mut body = vec![0u8; len];
+            server.read_exact(&mut body).unwrap();
+            server
+                .write_all(&encode_message(kind, r#"[{"success": true}]"#))
+                .unwrap();
+            (kind, String::from_utf8(body).unwrap())
+        });
+
+        let reply = message(&mut client, RUN_COMMAND, "output DP-1 enable").unwrap();
+        assert_eq!(reply, r#"[{"success": true}]"#);
+        assert_eq!(handle.join().unwrap(), (RUN_COMMAND, "output DP-1 enable".to_string()));
+    }
+}
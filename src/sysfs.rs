@@ -0,0 +1,70 @@
+//! Kernel sysfs/procfs paths
+//!
+//! Every read of /sys and /proc goes through here, rooted at
+//! $HYPRPIER_SYSFS_ROOT when it's set. Pointing it at a captured tree
+//! (e.g. one attached to a bug report, or the fixtures under
+//! `fixtures/sysfs/`) replays that machine's docks, controllers and lid.
+
+use std::path::PathBuf;
+
+const ROOT_ENV: &str = "HYPRPIER_SYSFS_ROOT";
+
+#[cfg(test)]
+thread_local! {
+    /// Root for the current test thread, so tests can run against
+    /// different fixture trees in parallel
+    static TEST_ROOT: std::cell::RefCell<Option<PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Use `dir` as the sysfs/procfs root for the rest of this test thread
+#[cfg(test)]
+pub fn set_test_root(dir: PathBuf) {
+    TEST_ROOT.with(|r| *r.borrow_mut() = Some(dir));
+}
+
+/// A fixture tree from `fixtures/sysfs/`
+#[cfg(test)]
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/sysfs")
+        .join(name)
+}
+
+/// The filesystem root the kernel paths below hang off: "/" normally
+fn root() -> PathBuf {
+    #[cfg(test)]
+    if let Some(dir) = TEST_ROOT.with(|r| r.borrow().clone()) {
+        return dir;
+    }
+
+    match std::env::var_os(ROOT_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from("/"),
+    }
+}
+
+/// /sys/bus/thunderbolt/devices
+pub fn thunderbolt_devices() -> PathBuf {
+    root().join("sys/bus/thunderbolt/devices")
+}
+
+/// /sys/bus/pci/devices
+pub fn pci_devices() -> PathBuf {
+    root().join("sys/bus/pci/devices")
+}
+
+/// /sys/bus/usb/devices
+pub fn usb_devices() -> PathBuf {
+    root().join("sys/bus/usb/devices")
+}
+
+/// /sys/class/drm
+pub fn drm_class() -> PathBuf {
+    root().join("sys/class/drm")
+}
+
+/// /proc/acpi/button/lid
+pub fn lid_dir() -> PathBuf {
+    root().join("proc/acpi/button/lid")
+}
@@ -4,11 +4,12 @@
 //! without requiring boltd/boltctl.
 
 use anyhow::Result;
+use serde::Serialize;
 use std::fs;
 use std::path::Path;
 
-const THUNDERBOLT_PATH: &str = "/sys/bus/thunderbolt/devices";
-const PCI_DEVICES_PATH: &str = "/sys/bus/pci/devices";
+use crate::sysfs;
+
 const THUNDERBOLT_PCI_CLASS: &str = "0x088000";
 const USB4_PCI_CLASS: &str = "0x0c0340";
 
@@ -18,9 +19,148 @@ pub fn read_attr(device_path: &Path, attr: &str) -> Option<String> {
     fs::read_to_string(path).ok().map(|s| s.trim().to_string())
 }
 
+/// Whether `s` looks like a Thunderbolt unique_id (hex digits and dashes).
+/// Anything else, like the prefixed ids of USB and DRM docks, is rejected,
+/// which also keeps it safe to paste into a udev rule.
+pub fn is_valid_uuid(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Whether the device (e.g. "0-1") is connected but not authorized
+pub fn is_unauthorized(device_id: &str) -> bool {
+    let path = sysfs::thunderbolt_devices().join(device_id);
+    read_attr(&path, "authorized").as_deref() == Some("0")
+}
+
+/// Where a Thunderbolt device sits in the topology and how it's linked
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ThunderboltInfo {
+    pub domain: u32,
+    /// PCI address of the controller the domain hangs off
+    pub controller: Option<String>,
+    /// device_id of the upstream device (the host for one plugged straight
+    /// into the laptop); None for hosts
+    pub parent: Option<String>,
+    /// Thunderbolt generation (USB4 is 4)
+    pub generation: Option<u32>,
+    /// e.g. "20.0 Gb/s", per lane
+    pub rx_speed: Option<String>,
+    pub rx_lanes: Option<u32>,
+    pub tx_speed: Option<String>,
+    pub tx_lanes: Option<u32>,
+    /// None for hosts, which have no `authorized` attribute
+    pub authorized: Option<bool>,
+    /// Authorized by the firmware before the OS took over
+    pub boot: Option<bool>,
+}
+
+impl ThunderboltInfo {
+    /// "authorized, boot, gen 4, rx 20.0 Gb/s x2, tx 20.0 Gb/s x2"
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match self.authorized {
+            Some(true) => parts.push("authorized".to_string()),
+            Some(false) => parts.push("unauthorized".to_string()),
+            None => {}
+        }
+        if self.boot == Some(true) {
+            parts.push("boot".to_string());
+        }
+        if let Some(generation) = self.generation {
+            parts.push(format!("gen {}", generation));
+        }
+        for (direction, speed, lanes) in [
+            ("rx", &self.rx_speed, self.rx_lanes),
+            ("tx", &self.tx_speed, self.tx_lanes),
+        ] {
+            match (speed, lanes) {
+                (Some(speed), Some(lanes)) => {
+                    parts.push(format!("{} {} x{}", direction, speed, lanes))
+                }
+                (Some(speed), None) => parts.push(format!("{} {}", direction, speed)),
+                _ => {}
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+/// Topology and link details of the device at `device_path` (e.g.
+/// /sys/bus/thunderbolt/devices/0-301)
+pub fn device_info(device_path: &Path, device_id: &str) -> ThunderboltInfo {
+    let attr = |name: &str| read_attr(device_path, name);
+    let number = |name: &str| attr(name).and_then(|v| v.parse().ok());
+    let domain = parse_device_id(device_id).map_or(0, |(domain, _)| domain);
+    ThunderboltInfo {
+        domain,
+        controller: controller_for_domain(domain),
+        parent: parent_device(device_path, device_id),
+        generation: number("generation"),
+        rx_speed: attr("rx_speed"),
+        rx_lanes: number("rx_lanes"),
+        tx_speed: attr("tx_speed"),
+        tx_lanes: number("tx_lanes"),
+        // "2" is authorized with a secure key
+        authorized: attr("authorized").map(|a| a != "0"),
+        boot: attr("boot").map(|b| b == "1"),
+    }
+}
+
+/// Sort key that puts devices in topology order: by domain, then by the
+/// ports along the route from the host, so every device follows the one
+/// it's plugged into ("0-1", "0-301", "0-3"). `hops.len()` is the depth.
+pub fn topology_key(device_id: &str) -> (u32, Vec<u8>) {
+    let (domain, route) = parse_device_id(device_id).unwrap_or((u32::MAX, 0));
+    let hops = route
+        .to_le_bytes()
+        .into_iter()
+        .take_while(|&port| port != 0)
+        .collect();
+    (domain, hops)
+}
+
+/// "0-301" -> (domain 0, route 0x301)
+fn parse_device_id(device_id: &str) -> Option<(u32, u64)> {
+    let (domain, route) = device_id.split_once('-')?;
+    Some((domain.parse().ok()?, u64::from_str_radix(route, 16).ok()?))
+}
+
+/// The upstream device. In the kernel's device tree a device's directory
+/// sits inside its parent's, which is where the /sys/bus entry links to.
+/// Flat copies (snapshots, fixtures) don't have that, so fall back to the
+/// route string in the name: each byte is one hop, and dropping the last
+/// hop gives the parent ("0-301" -> "0-1" -> "0-0").
+fn parent_device(device_path: &Path, device_id: &str) -> Option<String> {
+    let linked_parent = fs::canonicalize(device_path).ok().and_then(|real| {
+        let parent = real.parent()?.file_name()?.to_string_lossy().to_string();
+        parse_device_id(&parent).map(|_| parent)
+    });
+    if linked_parent.is_some() {
+        return linked_parent;
+    }
+
+    let (domain, route) = parse_device_id(device_id)?;
+    if route == 0 {
+        return None;
+    }
+    let hops = (64 - route.leading_zeros()).div_ceil(8);
+    let parent_route = route & !(0xff << ((hops - 1) * 8));
+    Some(format!("{}-{:x}", domain, parent_route))
+}
+
+/// PCI address of the controller with a domainN directory for `domain`
+fn controller_for_domain(domain: u32) -> Option<String> {
+    let domain_dir = format!("domain{}", domain);
+    fs::read_dir(sysfs::pci_devices())
+        .ok()?
+        .flatten()
+        .find(|entry| entry.path().join(&domain_dir).is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+}
+
 /// Get Thunderbolt security mode from sysfs
 pub fn get_security_mode() -> Result<String> {
-    let sys_path = Path::new(THUNDERBOLT_PATH).join("domain0/security");
+    let sys_path = sysfs::thunderbolt_devices().join("domain0/security");
 
     if sys_path.exists() {
         let mode = fs::read_to_string(&sys_path)
@@ -41,13 +181,13 @@ pub fn get_security_mode() -> Result<String> {
 ///
 /// Returns PCI addresses (e.g., ["0000:00:0d.2", "0000:00:0d.3"])
 pub fn get_controller_pci_addresses() -> Vec<String> {
-    let pci_path = Path::new(PCI_DEVICES_PATH);
+    let pci_path = sysfs::pci_devices();
 
     if !pci_path.exists() {
         return Vec::new();
     }
 
-    let entries = match fs::read_dir(pci_path) {
+    let entries = match fs::read_dir(&pci_path) {
         Ok(e) => e,
         Err(_) => return Vec::new(),
     };
@@ -88,3 +228,67 @@ pub fn get_controller_pci_addresses() -> Vec<String> {
 
     addresses
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controllers() -> Vec<String> {
+        let mut addresses = get_controller_pci_addresses();
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn finds_usb4_controller() {
+        sysfs::set_test_root(sysfs::fixture("laptop-usb4"));
+        // The xHCI and GPU functions next to it aren't controllers
+        assert_eq!(controllers(), ["0000:00:0d.2"]);
+        assert_eq!(get_security_mode().unwrap(), "user");
+    }
+
+    #[test]
+    fn finds_thunderbolt_3_controller_but_not_its_bridges() {
+        sysfs::set_test_root(sysfs::fixture("dock"));
+        // 0000:06:01.0 has the Thunderbolt class but no domain
+        assert_eq!(controllers(), ["0000:05:00.0"]);
+    }
+
+    #[test]
+    fn finds_one_controller_per_domain() {
+        sysfs::set_test_root(sysfs::fixture("daisy-chain"));
+        assert_eq!(controllers(), ["0000:00:0d.2", "0000:00:0d.3"]);
+        assert_eq!(get_security_mode().unwrap(), "secure");
+    }
+
+    #[test]
+    fn reads_daisy_chain_topology() {
+        sysfs::set_test_root(sysfs::fixture("daisy-chain"));
+        let info = |id: &str| device_info(&sysfs::thunderbolt_devices().join(id), id);
+
+        let host = info("0-0");
+        assert_eq!(host.parent, None);
+        assert_eq!(host.authorized, None);
+        assert_eq!(host.controller.as_deref(), Some("0000:00:0d.2"));
+
+        let dock = info("0-1");
+        assert_eq!(dock.parent.as_deref(), Some("0-0"));
+        assert_eq!(dock.boot, Some(true));
+        assert_eq!(dock.rx_lanes, Some(2));
+
+        let display = info("0-301");
+        assert_eq!(display.parent.as_deref(), Some("0-1"));
+        assert_eq!(display.generation, Some(3));
+        assert_eq!(display.authorized, Some(false));
+        assert_eq!(display.rx_speed.as_deref(), Some("20.0 Gb/s"));
+
+        assert_eq!(info("1-0").controller.as_deref(), Some("0000:00:0d.3"));
+    }
+
+    #[test]
+    fn no_thunderbolt_hardware() {
+        sysfs::set_test_root(sysfs::fixture("does-not-exist"));
+        assert!(controllers().is_empty());
+        assert_eq!(get_security_mode().unwrap(), "unknown");
+    }
+}
@@ -1,21 +1,40 @@
-//! Thunderbolt device information commands
+//! Thunderbolt and dock information commands
 //!
-//! Provides CLI commands for viewing Thunderbolt device information.
+//! Provides CLI commands for viewing Thunderbolt security and the devices
+//! dock detection sees.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use serde_json::json;
+use std::fmt::Write;
 
-use crate::dock;
+use crate::bolt;
+use crate::daemon::{self, Health};
+use crate::dock::{self, Dock};
+use crate::metadata::Metadata;
+use crate::setup::{self, DaemonService};
+use crate::thunderbolt::{self, ThunderboltInfo};
 
-/// List all Thunderbolt devices
-pub fn list_devices() -> Result<()> {
+/// List Thunderbolt devices and every other dock source, flat or as the
+/// Thunderbolt daisy-chain tree
+pub fn list_devices(json: bool, tree: bool) -> Result<()> {
     let devices = dock::list_all_devices()?;
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&json!({ "devices": devices }))?);
+        return Ok(());
+    }
+
     if devices.is_empty() {
-        println!("No Thunderbolt devices found");
+        println!("No Thunderbolt devices or docks found");
+        return Ok(());
+    }
+
+    if tree {
+        print!("{}", render_tree(&devices));
         return Ok(());
     }
 
-    println!("Thunderbolt devices:");
+    println!("Devices:");
     for device in devices {
         let vendor = device.vendor.as_deref().unwrap_or("unknown vendor");
         let device_type = if device.is_host { "host" } else { "peripheral" };
@@ -24,25 +43,285 @@ pub fn list_devices() -> Result<()> {
         println!("  {} ({})", device.name, vendor);
         println!("    UUID: {}", device.uuid);
         println!("    Device ID: {}", device.device_id);
+        println!("    Source: {}", device.source);
         println!("    Type: {}", device_type);
+        if let Some(tb) = &device.thunderbolt {
+            if let Some(parent) = &tb.parent {
+                println!("    Parent: {}", parent);
+            }
+            println!("    Link: {}", tb.summary());
+        }
     }
 
     Ok(())
 }
 
+/// Thunderbolt devices per domain, each under the device it's plugged into,
+/// then the docks from other sources
+fn render_tree(devices: &[Dock]) -> String {
+    let thunderbolt: Vec<(&Dock, &ThunderboltInfo)> = devices
+        .iter()
+        .filter_map(|d| d.thunderbolt.as_ref().map(|info| (d, info)))
+        .collect();
+    let mut domains: Vec<u32> = thunderbolt.iter().map(|(_, info)| info.domain).collect();
+    domains.sort();
+    domains.dedup();
+
+    let mut out = String::new();
+    for domain in domains {
+        let in_domain: Vec<_> = thunderbolt
+            .iter()
+            .filter(|(_, info)| info.domain == domain)
+            .collect();
+        let controller = in_domain
+            .iter()
+            .find_map(|(_, info)| info.controller.as_deref())
+            .unwrap_or("unknown");
+        let _ = writeln!(out, "domain{} (controller {})", domain, controller);
+        // Devices whose parent isn't listed hang off the domain directly
+        let roots: Vec<_> = in_domain
+            .iter()
+            .copied()
+            .filter(|(_, info)| {
+                info.parent
+                    .as_ref()
+                    .is_none_or(|p| !in_domain.iter().any(|(d, _)| &d.device_id == p))
+            })
+            .collect();
+        render_branch(&thunderbolt, &roots, "", &mut out);
+    }
+
+    let others: Vec<&Dock> = devices.iter().filter(|d| d.thunderbolt.is_none()).collect();
+    if !others.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("Other docks\n");
+        for dock in others {
+            let vendor = dock.vendor.as_deref().unwrap_or("unknown vendor");
+            let _ = writeln!(out, "  {} ({}) [{}]: {}", dock.name, vendor, dock.source, dock.uuid);
+        }
+    }
+    out
+}
+
+fn render_branch(
+    all: &[(&Dock, &ThunderboltInfo)],
+    nodes: &[&(&Dock, &ThunderboltInfo)],
+    prefix: &str,
+    out: &mut String,
+) {
+    for (i, (dock, info)) in nodes.iter().enumerate() {
+        let (branch, indent) = if i + 1 == nodes.len() {
+            ("└─ ", "   ")
+        } else {
+            ("├─ ", "│  ")
+        };
+        let vendor = dock.vendor.as_deref().unwrap_or("unknown vendor");
+        let _ = writeln!(
+            out,
+            "{}{}{} ({}) [{}]: {}",
+            prefix,
+            branch,
+            dock.name,
+            vendor,
+            dock.device_id,
+            if dock.is_host {
+                format!("host, {}", info.summary())
+            } else {
+                info.summary()
+            }
+        );
+        let children: Vec<_> = all
+            .iter()
+            .filter(|(_, child)| child.parent.as_deref() == Some(dock.device_id.as_str()))
+            .collect();
+        render_branch(all, &children, &format!("{}{}", prefix, indent), out);
+    }
+}
+
 /// Show Thunderbolt security status
-pub fn show_status() -> Result<()> {
+pub fn show_status(json: bool) -> Result<()> {
     let mode = dock::get_security_mode()?;
+    let description = match mode.as_str() {
+        "none" => "All devices are automatically authorized",
+        "user" => "Devices require user authorization",
+        "secure" => "Devices require secure key exchange",
+        "dponly" => "Only DisplayPort tunneling allowed (no PCIe/USB)",
+        _ => "Unknown security mode",
+    };
+
+    let authorizer = setup::authorizer();
+    let manager = bolt::manager();
+    let bolt_devices = if manager.is_some() {
+        bolt::devices().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let health = daemon::health();
+
+    if json {
+        let status = json!({
+            "security_mode": mode,
+            "description": description,
+            "authorization": authorizer,
+            "bolt": manager,
+            "bolt_devices": bolt_devices,
+            "daemon": health,
+        });
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
 
     println!("Thunderbolt security mode: {}", mode);
     println!();
-    match mode.as_str() {
-        "none" => println!("All devices are automatically authorized"),
-        "user" => println!("Devices require user authorization"),
-        "secure" => println!("Devices require secure key exchange"),
-        "dponly" => println!("Only DisplayPort tunneling allowed (no PCIe/USB)"),
-        _ => println!("Unknown security mode"),
+    println!("{}", description);
+    println!();
+    match (authorizer, &manager) {
+        ("boltd", Some(manager)) => println!(
+            "Authorization: managed by boltd {} (auth mode {})",
+            manager.version, manager.auth_mode
+        ),
+        ("udev", _) => println!(
+            "Authorization: hyprpier udev rules ({} allowlisted)",
+            Metadata::load()?.allowlist().len()
+        ),
+        _ => println!("Authorization: not set up (run `sudo hyprpier setup`)"),
+    }
+    for device in &bolt_devices {
+        let enrolled = if device.stored {
+            format!("enrolled, policy {}", device.policy)
+        } else {
+            "not enrolled".to_string()
+        };
+        println!(
+            "  {} ({}): {}, {}",
+            device.name, device.vendor, device.status, enrolled
+        );
+    }
+
+    println!();
+    print_daemon_health(&health);
+
+    Ok(())
+}
+
+/// "Daemon: running 0.4.0 (user service, active)" and what to do about it
+fn print_daemon_health(health: &Health) {
+    let service = match health.service {
+        DaemonService::NotInstalled => "no user service".to_string(),
+        DaemonService::Outdated => "user service for another binary".to_string(),
+        DaemonService::Installed if health.service_active => "user service, active".to_string(),
+        DaemonService::Installed => "user service, inactive".to_string(),
+    };
+    match (&health.version, health.socket_reachable) {
+        (_, false) => println!("Daemon: not reachable ({})", service),
+        (Some(version), true) => println!("Daemon: running {} ({})", version, service),
+        (None, true) => println!("Daemon: running an older version ({})", service),
+    }
+
+    if health.service == DaemonService::Outdated {
+        println!("  Run `hyprpier setup --daemon` to point the service at this binary");
+    } else if !health.socket_reachable && health.service == DaemonService::NotInstalled {
+        println!("  Run `hyprpier setup --daemon` to start it with your session");
+    } else if !health.socket_reachable {
+        println!("  Check `journalctl --user -u hyprpier` for why it isn't running");
+    } else if !health.version_matches {
+        println!(
+            "  This binary is {}; restart the daemon to pick it up",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+}
+
+/// Add a Thunderbolt device to (or drop it from) the trusted devices. The
+/// udev rules only pick the change up when `hyprpier setup` re-runs, which
+/// needs root, so this just says so. When boltd manages authorization the
+/// device is enrolled with (or forgotten by) bolt instead.
+pub fn set_trusted(uuid: &str, trusted: bool, json: bool) -> Result<()> {
+    let uuid = uuid.trim().to_ascii_lowercase();
+    if !thunderbolt::is_valid_uuid(&uuid) {
+        bail!(
+            "'{}' is not a Thunderbolt UUID (see `hyprpier thunderbolt --list`)",
+            uuid
+        );
+    }
+
+    let mut metadata = Metadata::load()?;
+    let before = metadata.allowlist();
+    if trusted {
+        metadata.trust_device(&uuid);
+    } else {
+        metadata.untrust_device(&uuid);
+    }
+    metadata.save()?;
+
+    let allowlisted = metadata.allowlist().contains(&uuid);
+    let rules_outdated = setup::rules_outdated();
+    // With boltd there are no rules to update: enroll right away if the
+    // device is connected, else the daemon does it on the next connect
+    let bolt_managed = bolt::is_running();
+    let enrolled = if trusted && bolt_managed {
+        Some(bolt::enroll(&uuid).map_err(|e| format!("{:#}", e)))
+    } else {
+        None
+    };
+    bolt::forget_dropped(&before, &metadata.allowlist())?;
+
+    if json {
+        let result = json!({
+            "uuid": uuid,
+            "trusted": trusted,
+            "allowlisted": allowlisted,
+            "rules_outdated": rules_outdated,
+            "bolt_enrolled": enrolled.as_ref().map(|r| r.is_ok()),
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    if trusted {
+        println!("Trusted {}", uuid);
+    } else if allowlisted {
+        println!("Untrusted {}, but it stays allowlisted while linked to a profile", uuid);
+    } else {
+        println!("Untrusted {}", uuid);
+    }
+    match &enrolled {
+        Some(Ok(true)) => println!("Enrolled with boltd"),
+        Some(Ok(false)) => println!("Already enrolled with boltd"),
+        Some(Err(e)) => println!("Not enrolled with boltd yet: {}", e),
+        None => {}
+    }
+    if rules_outdated {
+        println!("Run `sudo hyprpier setup` to update the udev rules");
+    } else if !bolt_managed && !setup::is_installed() {
+        println!("Run `sudo hyprpier setup` to authorize allowlisted devices on connect");
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs;
+
+    #[test]
+    fn renders_daisy_chain_tree() {
+        sysfs::set_test_root(sysfs::fixture("daisy-chain"));
+        let devices = dock::list_all_devices().unwrap();
+        assert_eq!(
+            render_tree(&devices),
+            "\
+domain0 (controller 0000:00:0d.2)
+└─ Raptor Lake (Intel) [0-0]: host, gen 4
+   └─ Thunderbolt 4 Dock (Lenovo) [0-1]: authorized, boot, gen 4, rx 20.0 Gb/s x2, tx 20.0 Gb/s x2
+      └─ LG UltraFine Display (LG Electronics) [0-301]: unauthorized, gen 3, rx 20.0 Gb/s x1, tx 20.0 Gb/s x1
+domain1 (controller 0000:00:0d.3)
+└─ Raptor Lake (Intel) [1-0]: host, gen 4
+"
+        );
+    }
+}
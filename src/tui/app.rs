@@ -15,6 +15,8 @@ use std::io;
 use std::time::Duration;
 
 use crate::profile::Profile;
+use crate::revert::PendingRevert;
+use crate::setup::DaemonService;
 
 use super::monitor_arrange::MonitorArrangeState;
 use super::profile_editor::ProfileEditorState;
@@ -38,6 +40,11 @@ enum Action {
     ArrangeCancel,
     /// Pause TUI, run sudo command, resume (args for hyprpier subcommand)
     RunSudo(Vec<String>),
+    /// Keep the pending apply (running its post_apply hooks) and return to
+    /// the profile list
+    Keep,
+    /// Revert the pending apply and return to the profile list
+    Revert,
 }
 
 /// The different screens/views in the TUI
@@ -47,6 +54,8 @@ pub enum Screen {
     MonitorArrange(MonitorArrangeState),
     Thunderbolt(ThunderboltState),
     Confirm(ConfirmDialog),
+    /// Waiting for the user to keep a just-applied layout
+    RevertPrompt(PendingRevert),
 }
 
 /// Generic confirmation dialog
@@ -144,6 +153,12 @@ impl App {
         while !self.should_quit {
             terminal.draw(|frame| self.render(frame))?;
 
+            // Nobody answered the keep-layout prompt in time
+            if matches!(&self.screen, Screen::RevertPrompt(p) if p.expired()) {
+                self.revert_pending()?;
+                continue;
+            }
+
             // Auto-refresh every REFRESH_INTERVAL_MS
             if last_refresh.elapsed().as_millis() >= REFRESH_INTERVAL_MS as u128 {
                 self.tick_refresh();
@@ -172,12 +187,49 @@ impl App {
                     Action::RunSudo(args) => {
                         self.run_sudo_command(terminal, &args)?;
                     }
+                    Action::Keep => self.keep_pending()?,
+                    Action::Revert => self.revert_pending()?,
                 }
             }
         }
         Ok(())
     }
 
+    /// Keep the pending apply and show any hook trouble on the profile list
+    fn keep_pending(&mut self) -> Result<()> {
+        let placeholder = Screen::ProfileList(ProfileListState::new()?);
+        let screen = std::mem::replace(&mut self.screen, placeholder);
+        if let Screen::RevertPrompt(pending) = screen {
+            let msg = match pending.keep() {
+                Ok(warnings) if warnings.is_empty() => None,
+                Ok(warnings) => Some(warnings.join("; ")),
+                Err(e) => Some(format!("{:#}", e)),
+            };
+            if let Screen::ProfileList(state) = &mut self.screen {
+                state.error_message = msg;
+            }
+        }
+        Ok(())
+    }
+
+    /// Revert the pending apply and show the outcome on the profile list
+    fn revert_pending(&mut self) -> Result<()> {
+        let placeholder = Screen::ProfileList(ProfileListState::new()?);
+        let screen = std::mem::replace(&mut self.screen, placeholder);
+        if let Screen::RevertPrompt(pending) = screen {
+            let name = pending.profile().to_string();
+            let msg = match pending.revert() {
+                Ok(()) => format!("'{}' not kept; reverted to the previous layout", name),
+                Err(e) => format!("revert failed: {:#}", e),
+            };
+            self.screen = Screen::ProfileList(ProfileListState::new()?);
+            if let Screen::ProfileList(state) = &mut self.screen {
+                state.error_message = Some(msg);
+            }
+        }
+        Ok(())
+    }
+
     /// Pause TUI, run sudo hyprpier <args>, resume
     fn run_sudo_command(
         &mut self,
@@ -268,6 +320,7 @@ impl App {
             Screen::MonitorArrange(state) => super::monitor_arrange::render(frame, state),
             Screen::Thunderbolt(state) => super::thunderbolt::render(frame, state),
             Screen::Confirm(dialog) => render_confirm_dialog(frame, dialog),
+            Screen::RevertPrompt(pending) => render_revert_prompt(frame, pending),
         }
     }
 
@@ -284,6 +337,7 @@ impl App {
                     Screen::MonitorArrange(state) => handle_monitor_arrange_keys(key.code, state)?,
                     Screen::Thunderbolt(state) => handle_thunderbolt_keys(key.code, state)?,
                     Screen::Confirm(dialog) => handle_confirm_keys(key.code, dialog)?,
+                    Screen::RevertPrompt(_) => handle_revert_prompt_keys(key.code)?,
                 };
 
                 return Ok(Some(action));
@@ -332,6 +386,44 @@ fn handle_confirm_keys(key: KeyCode, dialog: &mut ConfirmDialog) -> Result<Actio
     }
 }
 
+/// Render the keep-or-revert prompt shown after an apply that needs confirming
+fn render_revert_prompt(frame: &mut ratatui::Frame, pending: &PendingRevert) {
+    let area = frame.area();
+
+    let x = (area.width.saturating_sub(DIALOG_WIDTH)) / 2;
+    let y = (area.height.saturating_sub(DIALOG_HEIGHT)) / 2;
+    let dialog_area = Rect::new(x, y, DIALOG_WIDTH, DIALOG_HEIGHT);
+
+    frame.render_widget(Clear, dialog_area);
+
+    let block = Block::default()
+        .title(" Keep Layout? ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    // Round up so the countdown reads 15..1, not 14..0
+    let secs = (pending.remaining().as_millis() as u64).div_ceil(1000);
+    let text = format!(
+        "Applied profile '{}'.\nReverting in {}s unless kept.\n\n[y] Keep  [n] Revert now",
+        pending.profile(),
+        secs
+    );
+    let paragraph = Paragraph::new(text)
+        .block(block)
+        .alignment(ratatui::layout::Alignment::Center);
+
+    frame.render_widget(paragraph, dialog_area);
+}
+
+/// Handle keys for the keep-or-revert prompt
+fn handle_revert_prompt_keys(key: KeyCode) -> Result<Action> {
+    match key {
+        KeyCode::Char('y') | KeyCode::Char('Y') => Ok(Action::Keep),
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => Ok(Action::Revert),
+        _ => Ok(Action::None),
+    }
+}
+
 /// Save the editor's profile. If the name changed since the profile was
 /// opened, treat it as a rename: remove the old file and re-point metadata
 /// references (active, dock links, undocked) at the new name.
@@ -350,6 +442,14 @@ fn save_editor_profile(state: &mut ProfileEditorState) -> Result<()> {
     Ok(())
 }
 
+/// Have boltd forget docks that just left the allowlist; a message for
+/// the screen if it couldn't
+fn forget_dropped(allowlist: &[String], metadata: &crate::metadata::Metadata) -> Option<String> {
+    crate::bolt::forget_dropped(allowlist, &metadata.allowlist())
+        .err()
+        .map(|e| format!("boltd still authorizes the unlinked dock: {:#}", e))
+}
+
 /// Execute the confirmed action
 fn execute_confirm_action(action: &ConfirmAction) -> Result<Action> {
     match action {
@@ -358,12 +458,13 @@ fn execute_confirm_action(action: &ConfirmAction) -> Result<Action> {
             // Scrub references so the daemon doesn't keep trying to apply
             // a profile that no longer exists.
             let mut metadata = crate::metadata::Metadata::load()?;
+            let allowlist = metadata.allowlist();
+            let mut state = ProfileListState::new()?;
             if metadata.remove_profile_references(name) {
                 metadata.save()?;
+                state.error_message = forget_dropped(&allowlist, &metadata);
             }
-            Ok(Action::NewScreen(Box::new(Screen::ProfileList(
-                ProfileListState::new()?,
-            ))))
+            Ok(Action::NewScreen(Box::new(Screen::ProfileList(state))))
         }
         ConfirmAction::OverwriteProfile { editor_state } => {
             let mut state = editor_state.clone();
@@ -379,23 +480,25 @@ fn execute_confirm_action(action: &ConfirmAction) -> Result<Action> {
         }
         ConfirmAction::UnlinkDock { uuid, .. } => {
             let mut metadata = crate::metadata::Metadata::load()?;
+            let allowlist = metadata.allowlist();
             metadata.unlink_dock(uuid);
             metadata.save()?;
-            Ok(Action::NewScreen(Box::new(Screen::Thunderbolt(
-                ThunderboltState::new()?,
-            ))))
+            let mut state = ThunderboltState::new()?;
+            state.error_message = forget_dropped(&allowlist, &metadata);
+            Ok(Action::NewScreen(Box::new(Screen::Thunderbolt(state))))
         }
         ConfirmAction::SetUndocked {
             profile_name,
             dock_uuid,
         } => {
             let mut metadata = crate::metadata::Metadata::load()?;
+            let allowlist = metadata.allowlist();
             metadata.unlink_dock(dock_uuid);
             metadata.undocked_profile = Some(profile_name.clone());
             metadata.save()?;
-            Ok(Action::NewScreen(Box::new(Screen::ProfileList(
-                ProfileListState::new()?,
-            ))))
+            let mut state = ProfileListState::new()?;
+            state.error_message = forget_dropped(&allowlist, &metadata);
+            Ok(Action::NewScreen(Box::new(Screen::ProfileList(state))))
         }
         ConfirmAction::LinkRemoveUndocked {
             editor_state,
@@ -479,7 +582,15 @@ fn handle_profile_list_keys(key: KeyCode, state: &mut ProfileListState) -> Resul
                 // Show apply failures inline; a hyprctl hiccup shouldn't
                 // exit the whole TUI.
                 match crate::apply::apply_profile_quiet(&name, false) {
-                    Ok(()) => *state = ProfileListState::new()?,
+                    Ok(applied) => {
+                        if let Some(pending) = applied.pending {
+                            return Ok(Action::NewScreen(Box::new(Screen::RevertPrompt(pending))));
+                        }
+                        *state = ProfileListState::new()?;
+                        if !applied.report.warnings.is_empty() {
+                            state.error_message = Some(applied.report.warnings.join("; "));
+                        }
+                    }
                     Err(e) => state.error_message = Some(format!("apply failed: {:#}", e)),
                 }
             }
@@ -531,6 +642,14 @@ fn handle_profile_list_keys(key: KeyCode, state: &mut ProfileListState) -> Resul
             }
             Ok(Action::None)
         }
+        KeyCode::Char('m') => {
+            // Toggle picking profiles by connected monitor set
+            let mut metadata = crate::metadata::Metadata::load()?;
+            metadata.match_monitors = !metadata.match_monitors;
+            metadata.touch();
+            metadata.save()?;
+            Ok(Action::None)
+        }
         KeyCode::Up | KeyCode::Char('k') => {
             state.previous();
             Ok(Action::None)
@@ -598,14 +717,87 @@ fn handle_thunderbolt_keys(key: KeyCode, state: &mut ThunderboltState) -> Result
             }
             Ok(Action::None)
         }
+        KeyCode::Char('l') => {
+            // Link the selected dock, of any source, to the active profile
+            if state.section != Section::Connected {
+                return Ok(Action::None);
+            }
+            let Some(info) = state.selected_device().filter(|i| i.device.is_dock()) else {
+                return Ok(Action::None);
+            };
+            let uuid = info.device.uuid.clone();
+            let name = info.device.name.clone();
+            let bolt_managed = info.device.source == crate::dock::DockSource::Thunderbolt
+                && state.authorizer == "boltd";
+            let mut metadata = crate::metadata::Metadata::load()?;
+            match metadata.active_profile.clone() {
+                Some(profile) => {
+                    metadata.link_dock(&uuid, &profile);
+                    metadata.save()?;
+                    state.refresh();
+                    state.error_message = Some(if bolt_managed {
+                        match crate::bolt::enroll(&uuid) {
+                            Ok(_) => format!(
+                                "Linked {} to profile '{}' and enrolled it with boltd",
+                                name, profile
+                            ),
+                            Err(e) => format!("Linked {} to profile '{}'; {:#}", name, profile, e),
+                        }
+                    } else if state.rules_outdated {
+                        format!("Linked {} to profile '{}'; press s to authorize it", name, profile)
+                    } else {
+                        format!("Linked {} to profile '{}'", name, profile)
+                    });
+                }
+                None => {
+                    state.error_message =
+                        Some("No active profile to link; apply one first".to_string());
+                }
+            }
+            Ok(Action::None)
+        }
+        KeyCode::Char(c @ ('+' | '=' | '-')) => {
+            // Raise or lower the selected link's priority
+            if let Some((uuid, _)) = state.selected_link() {
+                let step = if c == '-' { -1 } else { 1 };
+                let mut metadata = crate::metadata::Metadata::load()?;
+                metadata.step_dock_priority(&uuid, step);
+                metadata.save()?;
+                state.refresh();
+            }
+            Ok(Action::None)
+        }
         KeyCode::Char('s') => {
-            // Setup or disable auto-switching
-            if state.auto_switch_enabled {
+            // Setup, update (new links or trusted devices) or disable
+            // auto-switching
+            if state.auto_switch_enabled && !state.rules_outdated {
                 Ok(Action::RunSudo(vec!["setup".to_string(), "--uninstall".to_string()]))
             } else {
                 Ok(Action::RunSudo(vec!["setup".to_string()]))
             }
         }
+        KeyCode::Char('d') => {
+            // Enable, update or disable the daemon's user service; no sudo
+            // needed, so it runs in place
+            let result = if state.daemon_service == DaemonService::Installed {
+                crate::setup::uninstall_daemon_service().map(|()| "Daemon service disabled")
+            } else {
+                crate::setup::install_daemon_service().map(|()| {
+                    if crate::setup::graphical_session_active() {
+                        "Daemon service enabled"
+                    } else {
+                        "Daemon service enabled, but graphical-session.target isn't active; \
+                         it won't start at the next login without uwsm"
+                    }
+                })
+            };
+            state.refresh();
+            state.error_message = Some(match result {
+                Ok(msg) => msg.to_string(),
+                Err(e) => format!("{:#}", e),
+            });
+            Ok(Action::None)
+        }
         KeyCode::Char('r') => {
             // Enable or disable resume service
             if state.resume_service_enabled {
@@ -709,6 +901,14 @@ fn handle_profile_editor_keys(key: KeyCode, state: &mut ProfileEditorState) -> R
                 ))))
             }
         }
+        KeyCode::Char('c') => {
+            // Toggle the keep-or-revert window for runtime applies
+            state.profile.revert_timeout = match state.profile.revert_timeout {
+                Some(_) => None,
+                None => Some(crate::revert::DEFAULT_TIMEOUT_SECS),
+            };
+            Ok(Action::None)
+        }
         KeyCode::Char('l') => {
             let metadata = crate::metadata::Metadata::load()?;
             let profile_name = &state.name_input;
@@ -717,9 +917,11 @@ fn handle_profile_editor_keys(key: KeyCode, state: &mut ProfileEditorState) -> R
             if let Some(uuid) = metadata.get_profile_dock(profile_name).cloned() {
                 // Unlink - no confirmation needed
                 let mut metadata = metadata;
+                let allowlist = metadata.allowlist();
                 metadata.unlink_dock(&uuid);
                 metadata.save()?;
                 state.refresh_dock_status();
+                state.error_message = forget_dropped(&allowlist, &metadata);
                 return Ok(Action::None);
             }
 
@@ -778,6 +980,17 @@ fn handle_profile_editor_keys(key: KeyCode, state: &mut ProfileEditorState) -> R
 }
 
 fn handle_monitor_arrange_keys(key: KeyCode, state: &mut MonitorArrangeState) -> Result<Action> {
+    state.message = None;
+    if state.picker.is_some() {
+        match key {
+            KeyCode::Esc => state.picker_cancel(),
+            KeyCode::Enter => state.picker_confirm(),
+            KeyCode::Up | KeyCode::Char('k') => state.picker_previous(),
+            KeyCode::Down | KeyCode::Char('j') => state.picker_next(),
+            _ => {}
+        }
+        return Ok(Action::None);
+    }
     match key {
         KeyCode::Esc => Ok(Action::ArrangeCancel),
         KeyCode::Char('s') => Ok(Action::ArrangeApply),
@@ -809,6 +1022,19 @@ fn handle_monitor_arrange_keys(key: KeyCode, state: &mut MonitorArrangeState) ->
             state.align_down();
             Ok(Action::None)
         }
+        KeyCode::Char('m') => {
+            let opened = crate::compositor::detect()
+                .available_modes()
+                .and_then(|outputs| state.open_mode_picker(&outputs));
+            if let Err(e) = opened {
+                state.message = Some(format!("{:#}", e));
+            }
+            Ok(Action::None)
+        }
+        KeyCode::Char('S') => {
+            state.open_scale_picker();
+            Ok(Action::None)
+        }
         KeyCode::Char('d') => {
             state.toggle_disable();
             Ok(Action::None)
@@ -1,3 +1,4 @@
+use anyhow::{bail, Result};
 use ratatui::{
     layout::{Constraint, Layout, Rect},
     style::{Color, Style},
@@ -11,8 +12,54 @@ use ratatui::{
 
 use super::profile_editor::ProfileEditorState;
 use super::styles;
+use crate::compositor::{Mode, OutputModes};
 use crate::profile::{Monitor, Workspace};
 
+/// Width of the picker list next to the preview
+const PICKER_WIDTH: u16 = 30;
+
+/// What a picker chooses for the selected monitor
+pub enum PickerKind {
+    /// Modes the compositor reports for the monitor
+    Mode(Vec<Mode>),
+    /// Scales with an integer logical size at the current resolution
+    Scale(Vec<f64>),
+}
+
+/// A list next to the preview. The highlighted entry is applied to the
+/// selected monitor right away so the preview follows it; Esc puts the
+/// layout back the way it was when the picker opened.
+pub struct Picker {
+    pub kind: PickerKind,
+    pub selected: usize,
+    saved_monitors: Vec<Monitor>,
+    saved_y_offsets: Vec<i32>,
+}
+
+impl Picker {
+    fn count(&self) -> usize {
+        match &self.kind {
+            PickerKind::Mode(modes) => modes.len(),
+            PickerKind::Scale(scales) => scales.len(),
+        }
+    }
+}
+
+/// Index of the scale closest to `scale`
+fn nearest_scale(scales: &[f64], scale: f64) -> Option<usize> {
+    scales
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - scale).abs().total_cmp(&(*b - scale).abs()))
+        .map(|(i, _)| i)
+}
+
+/// "1.25", "1.333", "2"
+fn format_scale(scale: f64) -> String {
+    let text = format!("{:.3}", scale);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
 pub struct MonitorArrangeState {
     pub monitors: Vec<Monitor>,
     pub workspaces: Vec<Workspace>,
@@ -22,6 +69,10 @@ pub struct MonitorArrangeState {
     pub rows: Vec<i32>,
     /// Vertical offset from row baseline per monitor (for alignment within multi-monitor rows)
     pub y_offsets: Vec<i32>,
+    /// Open mode or scale picker
+    pub picker: Option<Picker>,
+    /// Shown in place of the title until the next key
+    pub message: Option<String>,
 }
 
 impl MonitorArrangeState {
@@ -73,6 +124,8 @@ impl MonitorArrangeState {
             editor_state,
             rows,
             y_offsets,
+            picker: None,
+            message: None,
         }
     }
 
@@ -288,6 +341,111 @@ impl MonitorArrangeState {
         }
     }
 
+    /// Open the mode list for the selected monitor. Modes come from the
+    /// compositor's outputs, matched by description first, then by name.
+    pub fn open_mode_picker(&mut self, outputs: &[OutputModes]) -> Result<()> {
+        let Some(monitor) = self.monitors.get(self.selected) else {
+            return Ok(());
+        };
+        let output = outputs
+            .iter()
+            .find(|o| monitor.description.is_some() && o.description == monitor.description)
+            .or_else(|| outputs.iter().find(|o| o.name == monitor.name));
+        let modes = match output {
+            Some(output) if !output.modes.is_empty() => output.modes.clone(),
+            _ => bail!("{} isn't connected, no modes to pick from", monitor.name),
+        };
+        let selected = modes
+            .iter()
+            .position(|m| {
+                m.resolution() == monitor.resolution
+                    && (m.refresh_rate - monitor.refresh_rate).abs() < 0.01
+            })
+            .or_else(|| modes.iter().position(|m| m.resolution() == monitor.resolution))
+            .unwrap_or(0);
+        self.open_picker(PickerKind::Mode(modes), selected);
+        Ok(())
+    }
+
+    /// Open the scale list for the selected monitor, starting at the
+    /// closest scale to its current one
+    pub fn open_scale_picker(&mut self) {
+        let Some(monitor) = self.monitors.get(self.selected) else {
+            return;
+        };
+        let scales = monitor.integer_scales();
+        let selected = nearest_scale(&scales, monitor.scale).unwrap_or(0);
+        self.open_picker(PickerKind::Scale(scales), selected);
+    }
+
+    fn open_picker(&mut self, kind: PickerKind, selected: usize) {
+        self.picker = Some(Picker {
+            kind,
+            selected,
+            saved_monitors: self.monitors.clone(),
+            saved_y_offsets: self.y_offsets.clone(),
+        });
+    }
+
+    pub fn picker_next(&mut self) {
+        if let Some(picker) = &mut self.picker {
+            if picker.selected + 1 < picker.count() {
+                picker.selected += 1;
+                self.apply_pick();
+            }
+        }
+    }
+
+    pub fn picker_previous(&mut self) {
+        if let Some(picker) = &mut self.picker {
+            if picker.selected > 0 {
+                picker.selected -= 1;
+                self.apply_pick();
+            }
+        }
+    }
+
+    /// Keep what's highlighted
+    pub fn picker_confirm(&mut self) {
+        self.picker = None;
+    }
+
+    /// Close the picker and put the layout back
+    pub fn picker_cancel(&mut self) {
+        if let Some(picker) = self.picker.take() {
+            self.monitors = picker.saved_monitors;
+            self.y_offsets = picker.saved_y_offsets;
+        }
+    }
+
+    /// Set the highlighted mode or scale on the selected monitor
+    fn apply_pick(&mut self) {
+        let (Some(picker), Some(monitor)) = (&self.picker, self.monitors.get_mut(self.selected))
+        else {
+            return;
+        };
+        match &picker.kind {
+            PickerKind::Mode(modes) => {
+                if let Some(mode) = modes.get(picker.selected) {
+                    monitor.resolution = mode.resolution();
+                    monitor.mode = mode.mode_string();
+                    monitor.refresh_rate = mode.refresh_rate;
+                    // Keep the logical size whole at the new resolution
+                    let scales = monitor.integer_scales();
+                    if let Some(i) = nearest_scale(&scales, monitor.scale) {
+                        monitor.scale = scales[i];
+                    }
+                }
+            }
+            PickerKind::Scale(scales) => {
+                if let Some(&scale) = scales.get(picker.selected) {
+                    monitor.scale = scale;
+                }
+            }
+        }
+        self.recalculate_positions();
+    }
+
     pub fn remove_selected(&mut self) {
         if !self.monitors.is_empty() {
             let removed = self.monitors.remove(self.selected);
@@ -612,6 +770,55 @@ fn render_preview(frame: &mut Frame, area: Rect, state: &MonitorArrangeState) {
     frame.render_widget(canvas, area);
 }
 
+/// Render the open picker as a list, scrolled to keep the highlighted
+/// entry in view
+fn render_picker(frame: &mut Frame, area: Rect, picker: &Picker, monitor: &Monitor) {
+    let (title, labels): (&str, Vec<String>) = match &picker.kind {
+        PickerKind::Mode(modes) => (
+            " Mode ",
+            modes
+                .iter()
+                .map(|m| format!("{} @ {:.2}Hz", m.resolution(), m.refresh_rate))
+                .collect(),
+        ),
+        PickerKind::Scale(scales) => (
+            " Scale ",
+            scales
+                .iter()
+                .map(|&scale| {
+                    let (w, h) = Monitor { scale, ..monitor.clone() }.logical_size();
+                    format!("{:<6} {}x{}", format_scale(scale), w, h)
+                })
+                .collect(),
+        ),
+    };
+
+    let visible = area.height.saturating_sub(2).max(1) as usize;
+    let first = picker.selected.saturating_sub(visible - 1);
+    let lines: Vec<Line> = labels
+        .into_iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .map(|(i, label)| {
+            if i == picker.selected {
+                Line::styled(format!(">> {}", label), styles::list_selected())
+            } else {
+                Line::raw(format!("   {}", label))
+            }
+        })
+        .collect();
+
+    let list = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_style(styles::title_active())
+            .border_style(styles::border_active()),
+    );
+    frame.render_widget(list, area);
+}
+
 pub fn render(frame: &mut Frame, state: &mut MonitorArrangeState) {
     let chunks = Layout::vertical([
         Constraint::Length(1),  // Title
@@ -622,14 +829,25 @@ pub fn render(frame: &mut Frame, state: &mut MonitorArrangeState) {
     ])
     .split(frame.area());
 
-    // Title
-    let title = Paragraph::new("Monitor Arrangement")
-        .style(styles::page_title())
-        .alignment(ratatui::layout::Alignment::Center);
+    // Title, or the last error
+    let title = match &state.message {
+        Some(message) => Paragraph::new(message.as_str()).style(styles::error()),
+        None => Paragraph::new("Monitor Arrangement").style(styles::page_title()),
+    }
+    .alignment(ratatui::layout::Alignment::Center);
     frame.render_widget(title, chunks[0]);
 
-    // Preview
-    render_preview(frame, chunks[1], state);
+    // Preview, with the picker beside it while one is open
+    match (&state.picker, state.monitors.get(state.selected)) {
+        (Some(picker), Some(monitor)) => {
+            let [preview_area, picker_area] =
+                Layout::horizontal([Constraint::Min(10), Constraint::Length(PICKER_WIDTH)])
+                    .areas(chunks[1]);
+            render_preview(frame, preview_area, state);
+            render_picker(frame, picker_area, picker, monitor);
+        }
+        _ => render_preview(frame, chunks[1], state),
+    }
 
     // Monitor list
     let monitor_lines: Vec<Line> = state
@@ -664,10 +882,16 @@ pub fn render(frame: &mut Frame, state: &mut MonitorArrangeState) {
                 Style::default()
             };
 
+            let scale = if m.scale == 1.0 {
+                String::new()
+            } else {
+                format!(" x{}", format_scale(m.scale))
+            };
+
             Line::from(vec![Span::styled(
                 format!(
-                    "{}{}{}:  {} @ {}x{}{}{}",
-                    prefix, m.name, desc_info, m.resolution, m.position.x, m.position.y, rotation, status
+                    "{}{}{}:  {}{} @ {}x{}{}{}",
+                    prefix, m.name, desc_info, m.mode, scale, m.position.x, m.position.y, rotation, status
                 ),
                 style,
             )])
@@ -720,23 +944,102 @@ pub fn render(frame: &mut Frame, state: &mut MonitorArrangeState) {
     frame.render_widget(ws_para, chunks[3]);
 
     // Help
-    let help = Paragraph::new(vec![
-        Line::from(vec![
+    let help = if state.picker.is_some() {
+        Paragraph::new(vec![Line::from(vec![
             Span::styled("j,↓", styles::help_key()), Span::styled(" / ", styles::help()),
-            Span::styled("k,↑", styles::help_key()), Span::styled(" Select | ", styles::help()),
-            Span::styled("h,←", styles::help_key()), Span::styled(" / ", styles::help()),
-            Span::styled("l,→", styles::help_key()), Span::styled(" Move | ", styles::help()),
-            Span::styled("J,K", styles::help_key()), Span::styled(" Stack/Align | ", styles::help()),
-            Span::styled("r", styles::help_key()), Span::styled(" Rotate", styles::help()),
-        ]),
-        Line::from(vec![
-            Span::styled("d", styles::help_key()), Span::styled(" Disable | ", styles::help()),
-            Span::styled("x", styles::help_key()), Span::styled(" Remove | ", styles::help()),
-            Span::styled("1-0", styles::help_key()), Span::styled(" Workspace | ", styles::help()),
-            Span::styled("s", styles::help_key()), Span::styled(" Save | ", styles::help()),
-            Span::styled("Esc", styles::help_key()), Span::styled(" Cancel", styles::help()),
-        ]),
-    ])
+            Span::styled("k,↑", styles::help_key()), Span::styled(" Pick | ", styles::help()),
+            Span::styled("Enter", styles::help_key()), Span::styled(" Keep | ", styles::help()),
+            Span::styled("Esc", styles::help_key()), Span::styled(" Back", styles::help()),
+        ])])
+    } else {
+        Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled("j,↓", styles::help_key()), Span::styled(" / ", styles::help()),
+                Span::styled("k,↑", styles::help_key()), Span::styled(" Select | ", styles::help()),
+                Span::styled("h,←", styles::help_key()), Span::styled(" / ", styles::help()),
+                Span::styled("l,→", styles::help_key()), Span::styled(" Move | ", styles::help()),
+                Span::styled("J,K", styles::help_key()), Span::styled(" Stack/Align | ", styles::help()),
+                Span::styled("r", styles::help_key()), Span::styled(" Rotate", styles::help()),
+            ]),
+            Line::from(vec![
+                Span::styled("m", styles::help_key()), Span::styled(" Mode | ", styles::help()),
+                Span::styled("S", styles::help_key()), Span::styled(" Scale | ", styles::help()),
+                Span::styled("d", styles::help_key()), Span::styled(" Disable | ", styles::help()),
+                Span::styled("x", styles::help_key()), Span::styled(" Remove | ", styles::help()),
+                Span::styled("1-0", styles::help_key()), Span::styled(" Workspace | ", styles::help()),
+                Span::styled("s", styles::help_key()), Span::styled(" Save | ", styles::help()),
+                Span::styled("Esc", styles::help_key()), Span::styled(" Cancel", styles::help()),
+            ]),
+        ])
+    }
     .alignment(ratatui::layout::Alignment::Center);
     frame.render_widget(help, chunks[4]);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Position;
+
+    /// A 4K monitor at 1.5 with the laptop panel to its right
+    fn arrange() -> MonitorArrangeState {
+        let mut dell = Monitor::test_fixture("DP-1", "3840x2160", 1.5, 0);
+        dell.description = Some("Dell U2720Q".to_string());
+        let mut panel = Monitor::test_fixture("eDP-1", "1920x1200", 1.0, 0);
+        panel.position = Position { x: 2560, y: 0 };
+        let mut editor = ProfileEditorState::new();
+        editor.profile.monitors = vec![dell, panel];
+        MonitorArrangeState::new(editor)
+    }
+
+    fn dell_modes() -> Vec<OutputModes> {
+        vec![OutputModes {
+            name: "DP-3".to_string(),
+            description: Some("Dell U2720Q".to_string()),
+            modes: ["3840x2160@60.00Hz", "2560x1440@59.95Hz"]
+                .iter()
+                .filter_map(|m| Mode::parse(m))
+                .collect(),
+        }]
+    }
+
+    #[test]
+    fn mode_pick_snaps_scale_and_cancel_restores_layout() {
+        let mut state = arrange();
+        state.open_mode_picker(&dell_modes()).unwrap();
+        assert_eq!(state.picker.as_ref().map(|p| p.selected), Some(0));
+
+        state.picker_next();
+        let dell = &state.monitors[0];
+        assert_eq!(dell.mode, "2560x1440@59.95");
+        // 1.5 would make 1706.67 logical pixels; 1.6 is the closest whole one
+        assert_eq!(dell.scale, 1.6);
+        assert_eq!(dell.logical_size(), (1600, 900));
+        assert_eq!(state.monitors[1].position.x, 1600);
+
+        state.picker_cancel();
+        assert!(state.picker.is_none());
+        let dell = &state.monitors[0];
+        assert_eq!((dell.resolution.as_str(), dell.scale), ("3840x2160", 1.5));
+        assert_eq!(state.monitors[1].position.x, 2560);
+        assert_eq!(state.y_offsets, vec![0, 0]);
+    }
+
+    #[test]
+    fn scale_pick_is_kept_on_confirm() {
+        let mut state = arrange();
+        state.open_scale_picker();
+        let start = state.picker.as_ref().unwrap().selected;
+        state.picker_next();
+        state.picker_confirm();
+        assert!(state.picker.is_none());
+        let scale = state.monitors[0].scale;
+        assert!(scale > 1.5);
+        assert_eq!(state.monitors[0].integer_scales()[start + 1], scale);
+
+        // A monitor the compositor doesn't know has no modes to offer
+        state.selected = 1;
+        assert!(state.open_mode_picker(&dell_modes()).is_err());
+        assert!(state.picker.is_none());
+    }
+}
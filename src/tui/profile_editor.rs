@@ -90,7 +90,8 @@ impl ProfileEditorState {
     }
 
     pub fn detect_monitors(&mut self) -> Result<()> {
-        let mut monitors = hyprland::detect_monitors()?;
+        let mut monitors = crate::compositor::detect().monitors(false)?;
+        self.profile.keep_vrr(&mut monitors);
         hyprland::sort_monitors(&mut monitors);
         hyprland::arrange_monitors(&mut monitors);
 
@@ -205,11 +206,17 @@ pub fn render(frame: &mut Frame, state: &mut ProfileEditorState) {
         None => String::new(),
     };
 
+    let confirm_status = match state.profile.revert_timeout {
+        Some(secs) => format!("{}s", secs),
+        None => "off".to_string(),
+    };
+
     let help = Paragraph::new(vec![
         Line::from(vec![
             Span::styled("d", styles::help_key()), Span::styled(" Detect | ", styles::help()),
             Span::styled("a", styles::help_key()), Span::styled(" Arrange | ", styles::help()),
             Span::styled("l", styles::help_key()), Span::styled(format!(" Link/Unlink{dock_status} | "), styles::help()),
+            Span::styled("c", styles::help_key()), Span::styled(format!(" Confirm ({confirm_status}) | "), styles::help()),
             Span::styled("s", styles::help_key()), Span::styled(" Save", styles::help()),
         ]),
         Line::from(vec![
@@ -223,6 +223,7 @@ pub fn render(frame: &mut Frame, state: &mut ProfileListState) {
             Span::styled("d", styles::help_key()), Span::styled(" Delete | ", styles::help()),
             Span::styled("a", styles::help_key()), Span::styled(" Apply | ", styles::help()),
             Span::styled("u", styles::help_key()), Span::styled(" Undocked | ", styles::help()),
+            Span::styled("m", styles::help_key()), Span::styled(" Match monitors | ", styles::help()),
             Span::styled("t", styles::help_key()), Span::styled(" Thunderbolt", styles::help()),
         ]),
         Line::from(vec![
@@ -239,10 +240,9 @@ fn build_status_line() -> String {
     let mut parts = Vec::new();
 
     // Current active profile
-    if let Ok(metadata) = Metadata::load() {
-        if let Some(ref active) = metadata.active_profile {
-            parts.push(format!("Active: {}", active));
-        }
+    let metadata = Metadata::load().ok();
+    if let Some(active) = metadata.as_ref().and_then(|m| m.active_profile.as_ref()) {
+        parts.push(format!("Active: {}", active));
     }
 
     // Connected dock
@@ -252,6 +252,10 @@ fn build_status_line() -> String {
         }
     }
 
+    if metadata.is_some_and(|m| m.match_monitors) {
+        parts.push("Match: monitors".to_string());
+    }
+
     if parts.is_empty() {
         "No active profile".to_string()
     } else {
@@ -8,8 +8,10 @@ use ratatui::{
 };
 
 use super::styles;
-use crate::dock::{self, ThunderboltDevice};
+use crate::dock::{self, Dock};
 use crate::metadata::Metadata;
+use crate::setup::DaemonService;
+use crate::thunderbolt;
 
 #[derive(Clone, PartialEq)]
 pub enum Section {
@@ -27,19 +29,27 @@ pub struct ThunderboltState {
     pub security_mode: String,
     pub error_message: Option<String>,
     pub auto_switch_enabled: bool,
+    /// The installed rules authorize a different set of devices than the
+    /// current links and trusted devices call for
+    pub rules_outdated: bool,
+    /// Who authorizes devices: "boltd", "udev" or "none"
+    pub authorizer: &'static str,
     pub resume_service_enabled: bool,
+    pub daemon_service: DaemonService,
 }
 
 #[derive(Clone)]
 pub struct DeviceInfo {
-    pub device: ThunderboltDevice,
+    pub device: Dock,
     pub linked_profile: Option<String>,
+    pub priority: i32,
 }
 
 #[derive(Clone)]
 pub struct DisconnectedDock {
     pub uuid: String,
     pub profile: String,
+    pub priority: i32,
 }
 
 impl ThunderboltState {
@@ -61,9 +71,11 @@ impl ThunderboltState {
                     .find(|(uuid, _)| *uuid == &device.uuid)
                     .map(|(_, profile)| profile.clone());
 
+                let priority = metadata.dock_priority(&device.uuid);
                 DeviceInfo {
                     device,
                     linked_profile,
+                    priority,
                 }
             })
             .collect();
@@ -76,6 +88,7 @@ impl ThunderboltState {
             .map(|(uuid, profile)| DisconnectedDock {
                 uuid: uuid.clone(),
                 profile: profile.clone(),
+                priority: metadata.dock_priority(uuid),
             })
             .collect();
 
@@ -98,7 +111,10 @@ impl ThunderboltState {
             security_mode,
             error_message: None,
             auto_switch_enabled: crate::setup::is_installed(),
+            rules_outdated: crate::setup::rules_outdated(),
+            authorizer: crate::setup::authorizer(),
             resume_service_enabled: crate::setup::is_resume_service_installed(),
+            daemon_service: crate::setup::daemon_service(),
         })
     }
 
@@ -114,6 +130,19 @@ impl ThunderboltState {
             .and_then(|i| self.disconnected.get(i))
     }
 
+    /// UUID and link priority of the selected dock, if it's linked
+    pub fn selected_link(&self) -> Option<(String, i32)> {
+        match self.section {
+            Section::Connected => self
+                .selected_device()
+                .filter(|info| info.linked_profile.is_some())
+                .map(|info| (info.device.uuid.clone(), info.priority)),
+            Section::Disconnected => self
+                .selected_disconnected()
+                .map(|dock| (dock.uuid.clone(), dock.priority)),
+        }
+    }
+
     pub fn next(&mut self) {
         match self.section {
             Section::Connected => {
@@ -202,7 +231,10 @@ impl ThunderboltState {
             self.disconnected_table = new_state.disconnected_table;
             self.security_mode = new_state.security_mode;
             self.auto_switch_enabled = new_state.auto_switch_enabled;
+            self.rules_outdated = new_state.rules_outdated;
+            self.authorizer = new_state.authorizer;
             self.resume_service_enabled = new_state.resume_service_enabled;
+            self.daemon_service = new_state.daemon_service;
 
             // Restore section, but switch if current section is now empty
             self.section = old_section;
@@ -225,14 +257,35 @@ impl ThunderboltState {
     }
 }
 
+/// Where the selected Thunderbolt device sits: "0-301 on domain0
+/// (0000:00:0d.2), plugged into 0-1"
+fn topology_details(device: &Dock) -> Option<String> {
+    let tb = device.thunderbolt.as_ref()?;
+    let mut text = format!(
+        "{} on domain{} ({})",
+        device.device_id,
+        tb.domain,
+        tb.controller.as_deref().unwrap_or("unknown controller")
+    );
+    if let Some(parent) = &tb.parent {
+        text.push_str(&format!(", plugged into {}", parent));
+    }
+    Some(text)
+}
+
 pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
     let has_disconnected = !state.disconnected.is_empty();
     let has_error = state.error_message.is_some();
+    let details = match state.section {
+        Section::Connected => state.selected_device().and_then(|info| topology_details(&info.device)),
+        Section::Disconnected => None,
+    };
 
     let chunks = Layout::vertical([
         Constraint::Length(1), // Title
         Constraint::Length(1), // Security mode
         Constraint::Min(6),    // Connected devices table
+        Constraint::Length(if details.is_some() { 1 } else { 0 }), // Selected device topology
         Constraint::Length(if has_disconnected { 6 } else { 0 }), // Disconnected table
         Constraint::Length(if has_error { 1 } else { 0 }), // Error message
         Constraint::Length(2), // Help (no box)
@@ -252,23 +305,38 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         "secure" => Color::Red,
         _ => Color::Gray,
     };
-    let (auto_switch_word, auto_switch_color) = if state.auto_switch_enabled {
+    let (auto_switch_word, auto_switch_color) = if state.rules_outdated {
+        ("outdated", Color::Yellow)
+    } else if state.auto_switch_enabled {
         ("enabled", Color::Green)
     } else {
         ("disabled", Color::DarkGray)
     };
+    let authorizer_color = match state.authorizer {
+        "boltd" | "udev" => Color::Green,
+        _ => Color::DarkGray,
+    };
     let (resume_word, resume_color) = if state.resume_service_enabled {
         ("enabled", Color::Green)
     } else {
         ("disabled", Color::DarkGray)
     };
+    let (daemon_word, daemon_color) = match state.daemon_service {
+        DaemonService::Installed => ("enabled", Color::Green),
+        DaemonService::Outdated => ("outdated", Color::Yellow),
+        DaemonService::NotInstalled => ("disabled", Color::DarkGray),
+    };
     let security = Paragraph::new(Line::from(vec![
         Span::raw("Security: "),
         Span::styled(&state.security_mode, Style::default().fg(security_color)),
         Span::raw(" | Auto-switch: "),
         Span::styled(auto_switch_word, Style::default().fg(auto_switch_color)),
+        Span::raw(" | Authorization: "),
+        Span::styled(state.authorizer, Style::default().fg(authorizer_color)),
         Span::raw(" | Resume fix: "),
         Span::styled(resume_word, Style::default().fg(resume_color)),
+        Span::raw(" | Daemon service: "),
+        Span::styled(daemon_word, Style::default().fg(daemon_color)),
     ]))
     .alignment(ratatui::layout::Alignment::Center);
     frame.render_widget(security, chunks[1]);
@@ -284,7 +352,9 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         Cell::from("Device").style(connected_header_style),
         Cell::from("Vendor").style(connected_header_style),
         Cell::from("Type").style(connected_header_style),
+        Cell::from("Link").style(connected_header_style),
         Cell::from("Profile").style(connected_header_style),
+        Cell::from("Priority").style(connected_header_style),
     ])
     .height(1);
 
@@ -294,14 +364,39 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         .map(|info| {
             let device = &info.device;
             let vendor = device.vendor.as_deref().unwrap_or("-");
-            let device_type = if device.is_host { "host" } else { "dock" };
+            let device_type = if device.is_host {
+                "host".to_string()
+            } else {
+                device.source.to_string()
+            };
+            // Chained Thunderbolt devices are indented under their parent
+            let depth = match &device.thunderbolt {
+                Some(_) => thunderbolt::topology_key(&device.device_id).1.len(),
+                None => 0,
+            };
+            let name = if depth > 0 {
+                format!("{}└ {}", "  ".repeat(depth - 1), device.name)
+            } else {
+                device.name.clone()
+            };
+            let link = device
+                .thunderbolt
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |tb| tb.summary());
             let profile = info.linked_profile.as_deref().unwrap_or("-");
+            let priority = if info.linked_profile.is_some() {
+                info.priority.to_string()
+            } else {
+                "-".to_string()
+            };
 
             Row::new(vec![
-                Cell::from(device.name.clone()),
+                Cell::from(name),
                 Cell::from(vendor.to_string()),
                 Cell::from(device_type),
+                Cell::from(link),
                 Cell::from(profile.to_string()),
+                Cell::from(priority),
             ])
         })
         .collect();
@@ -309,10 +404,12 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
     let connected_table = Table::new(
         connected_rows,
         [
-            Constraint::Percentage(35),
-            Constraint::Percentage(20),
-            Constraint::Percentage(15),
-            Constraint::Percentage(30),
+            Constraint::Percentage(24),
+            Constraint::Percentage(14),
+            Constraint::Percentage(10),
+            Constraint::Percentage(26),
+            Constraint::Percentage(18),
+            Constraint::Percentage(8),
         ],
     )
     .header(connected_header)
@@ -328,6 +425,10 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
 
     frame.render_stateful_widget(connected_table, chunks[2], &mut state.connected_table);
 
+    if let Some(details) = details {
+        frame.render_widget(Paragraph::new(format!(" {}", details)).style(styles::help()), chunks[3]);
+    }
+
     // Disconnected docks table
     if has_disconnected {
         let disconnected_active = state.section == Section::Disconnected;
@@ -339,6 +440,7 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         let disconnected_header = Row::new(vec![
             Cell::from("UUID").style(disconnected_header_style),
             Cell::from("Linked Profile").style(disconnected_header_style),
+            Cell::from("Priority").style(disconnected_header_style),
         ])
         .height(1);
 
@@ -354,13 +456,18 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
                 Row::new(vec![
                     Cell::from(short_uuid),
                     Cell::from(dock.profile.clone()),
+                    Cell::from(dock.priority.to_string()),
                 ])
             })
             .collect();
 
         let disconnected_table = Table::new(
             disconnected_rows,
-            [Constraint::Percentage(50), Constraint::Percentage(50)],
+            [
+                Constraint::Percentage(45),
+                Constraint::Percentage(45),
+                Constraint::Percentage(10),
+            ],
         )
         .header(disconnected_header)
         .block(
@@ -373,17 +480,19 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         .row_highlight_style(if disconnected_active { styles::row_highlight() } else { Style::default() })
         .highlight_symbol(if disconnected_active { ">> " } else { "   " });
 
-        frame.render_stateful_widget(disconnected_table, chunks[3], &mut state.disconnected_table);
+        frame.render_stateful_widget(disconnected_table, chunks[4], &mut state.disconnected_table);
     }
 
     // Status message (if any)
     if let Some(msg) = &state.error_message {
         let msg_para = Paragraph::new(format!(" {}", msg)).style(styles::warning());
-        frame.render_widget(msg_para, chunks[4]);
+        frame.render_widget(msg_para, chunks[5]);
     }
 
     // Help
-    let setup_action = if state.auto_switch_enabled {
+    let setup_action = if state.rules_outdated {
+        " Update"
+    } else if state.auto_switch_enabled {
         " Disable"
     } else {
         " Enable"
@@ -394,10 +503,19 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         " Enable"
     };
 
+    let daemon_action = match state.daemon_service {
+        DaemonService::Installed => " Disable",
+        DaemonService::Outdated => " Update",
+        DaemonService::NotInstalled => " Enable",
+    };
+
     let mut line1_spans = vec![
+        Span::styled("l", styles::help_key()), Span::styled(" Link to active | ", styles::help()),
         Span::styled("x", styles::help_key()), Span::styled(" Unlink | ", styles::help()),
+        Span::styled("+/-", styles::help_key()), Span::styled(" Priority | ", styles::help()),
         Span::styled("s", styles::help_key()), Span::styled(format!("{} Auto-switch | ", setup_action), styles::help()),
-        Span::styled("r", styles::help_key()), Span::styled(format!("{} Resume fix", resume_action), styles::help()),
+        Span::styled("r", styles::help_key()), Span::styled(format!("{} Resume fix | ", resume_action), styles::help()),
+        Span::styled("d", styles::help_key()), Span::styled(format!("{} Daemon", daemon_action), styles::help()),
     ];
     if has_disconnected {
         line1_spans.push(Span::styled(" | ", styles::help()));
@@ -414,5 +532,5 @@ pub fn render(frame: &mut Frame, state: &mut ThunderboltState) {
         ]),
     ])
     .alignment(ratatui::layout::Alignment::Center);
-    frame.render_widget(help, chunks[5]);
+    frame.render_widget(help, chunks[6]);
 }